@@ -1,3 +1,4 @@
+use std::path::PathBuf;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -6,6 +7,14 @@ pub enum Error {
     Serialization(bincode::Error),
     #[error(transparent)]
     IO(std::io::Error),
+    #[error("File {} does not exist", .0.display())]
+    NotFound(PathBuf),
+    #[error("File {} and temp file {} are both unreadable: {reason}", path.display(), temp_file_path.display())]
+    Corrupt {
+        path: PathBuf,
+        temp_file_path: PathBuf,
+        reason: String,
+    },
     #[error(transparent)]
     Other(#[from] anyhow::Error),
 }
@@ -0,0 +1,333 @@
+//! Recording changes to a [`FileLinked`] object as events appended to a write-ahead log, so the whole object only has
+//! to be written to its file every so often
+
+use crate::{error::Error, temp_file_path, DataFormat, FileLinked, LinkedFile};
+use anyhow::{anyhow, Context};
+use log::{info, warn};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    ffi::OsString,
+    fs::{File, OpenOptions},
+    io::{BufReader, ErrorKind, Write},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+/// An object whose changes can be described as events, so that a journaled [`FileLinked`] object only has to append
+/// each event to its log instead of rewriting the whole file, see [`FileLinked::new_journaled`].
+pub trait Journaled {
+    /// A change to the object
+    type Event: Serialize + DeserializeOwned;
+
+    /// Applies `event` to the object. Called when the event is appended and again when it's replayed from the log
+    /// after a restart, so both have to give the same result.
+    fn apply(&mut self, event: &Self::Event);
+}
+
+/// The write-ahead log kept next to the file of a journaled [`FileLinked`] object.
+#[derive(Debug)]
+pub(crate) struct Journal {
+    path: PathBuf,
+    /// The number of events applied to the object since it was created, written to the file along with the object so
+    /// events it already includes are skipped when the log is replayed
+    pub(crate) sequence: u64,
+    /// The number of events appended since the object was last written to its file
+    pending: u64,
+    snapshot_interval: u64,
+}
+
+/// Builds the path of the log kept next to `path`.
+fn journal_path(path: &Path) -> Result<PathBuf, Error> {
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| anyhow!("Unable to get filename for log {}", path.display()))?;
+
+    let mut journal_file_name = OsString::from(file_name);
+    journal_file_name.push(".wal");
+
+    Ok(path.with_file_name(journal_file_name))
+}
+
+impl<T> FileLinked<T>
+where
+    T: Journaled + Serialize + DeserializeOwned,
+{
+    /// Creates a new [`FileLinked`] object in the same way as [`new`](FileLinked::new), whose changes are recorded
+    /// with [`append`](FileLinked::append). Each event is appended to a log next to `path` with the extension `.wal`,
+    /// and the whole object is only written to `path` once every `snapshot_interval` events. The file has to be read
+    /// back with [`from_file_journaled`](FileLinked::from_file_journaled).
+    ///
+    /// # Examples
+    /// ```
+    /// # use file_linked::*;
+    /// # use file_linked::error::Error;
+    /// # use file_linked::journal::Journaled;
+    /// # use serde::{Deserialize, Serialize};
+    /// # use std::path::PathBuf;
+    /// #
+    /// #[derive(Deserialize, Serialize)]
+    /// struct Counter(u64);
+    ///
+    /// impl Journaled for Counter {
+    ///     type Event = u64;
+    ///
+    ///     fn apply(&mut self, event: &u64) {
+    ///         self.0 += event;
+    ///     }
+    /// }
+    ///
+    /// # fn main() -> Result<(), Error> {
+    /// let path = PathBuf::from("./temp_journaled");
+    /// let mut linked_counter = FileLinked::new_journaled(Counter(0), &path, 100)?;
+    ///
+    /// linked_counter.append(2)?;
+    /// linked_counter.append(3)?;
+    /// drop(linked_counter);
+    ///
+    /// // The events are replayed from the log since the file was only written when it was created
+    /// let linked_counter = FileLinked::<Counter>::from_file_journaled(&path, 100)?;
+    /// assert_eq!(linked_counter.readonly().0, 5);
+    /// #
+    /// # drop(linked_counter);
+    /// #
+    /// # std::fs::remove_file("./temp_journaled").expect("Unable to remove file");
+    /// # std::fs::remove_file("./temp_journaled.wal").expect("Unable to remove file");
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn new_journaled(
+        val: T,
+        path: &Path,
+        snapshot_interval: u64,
+    ) -> Result<FileLinked<T>, Error> {
+        let mut result = FileLinked {
+            val,
+            file: Some(LinkedFile {
+                path: path.to_path_buf(),
+                temp_file_path: temp_file_path(path)?,
+                format: DataFormat::Bincode,
+                journal: Some(Journal {
+                    path: journal_path(path)?,
+                    sequence: 0,
+                    pending: 0,
+                    snapshot_interval,
+                }),
+            }),
+            file_thread: None,
+            last_written: Arc::default(),
+        };
+
+        result.write_snapshot()?;
+        Ok(result)
+    }
+
+    /// Reads a journaled object written with [`new_journaled`](FileLinked::new_journaled) from the file given by
+    /// `path`, falling back to the temp file in the same way as [`from_file`](FileLinked::from_file), then replays the
+    /// events from the log that the file doesn't include yet. An event that was only partly appended, because writing
+    /// it was interrupted, is ignored.
+    pub fn from_file_journaled(
+        path: &Path,
+        snapshot_interval: u64,
+    ) -> Result<FileLinked<T>, Error> {
+        let temp_file_path = temp_file_path(path)?;
+        let (mut sequence, mut val) =
+            FileLinked::<(u64, T)>::read_file(path, &temp_file_path, DataFormat::Bincode)?;
+
+        let journal_path = journal_path(path)?;
+        let mut replayed = 0;
+        let logged = match File::open(&journal_path) {
+            Ok(file) => {
+                let mut reader = BufReader::new(file);
+
+                loop {
+                    let (event_sequence, event) =
+                        match bincode::deserialize_from::<_, (u64, T::Event)>(&mut reader) {
+                            Ok(entry) => entry,
+                            Err(e) => match *e {
+                                bincode::ErrorKind::Io(ref io)
+                                    if io.kind() == ErrorKind::UnexpectedEof =>
+                                {
+                                    break
+                                }
+                                _ => {
+                                    return Err(Error::Other(anyhow!(
+                                        "Unable to read log {}: {}",
+                                        journal_path.display(),
+                                        e
+                                    )))
+                                }
+                            },
+                        };
+
+                    // Events written before the file was last written are already included in it
+                    if event_sequence <= sequence {
+                        continue;
+                    }
+                    if event_sequence != sequence + 1 {
+                        return Err(Error::Other(anyhow!(
+                            "Log {} is missing events {} to {}",
+                            journal_path.display(),
+                            sequence + 1,
+                            event_sequence - 1
+                        )));
+                    }
+
+                    val.apply(&event);
+                    sequence = event_sequence;
+                    replayed += 1;
+                }
+
+                true
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => false,
+            Err(e) => return Err(Error::IO(e)),
+        };
+
+        if replayed > 0 {
+            info!(
+                "Replayed {} events from log {}",
+                replayed,
+                journal_path.display()
+            );
+        }
+
+        let mut result = FileLinked {
+            val,
+            file: Some(LinkedFile {
+                path: path.to_path_buf(),
+                temp_file_path,
+                format: DataFormat::Bincode,
+                journal: Some(Journal {
+                    path: journal_path,
+                    sequence,
+                    pending: replayed,
+                    snapshot_interval,
+                }),
+            }),
+            file_thread: None,
+            last_written: Arc::default(),
+        };
+
+        // New events can't be appended after one that was cut off, so the log is started over
+        if logged {
+            result.write_snapshot()?;
+        }
+
+        Ok(result)
+    }
+
+    /// Applies `event` to the object and records it. Journaled objects append the event to their log and are written
+    /// to their file in full once enough events have been appended, other objects are written in full every time in
+    /// the same way as [`mutate`](FileLinked::mutate).
+    pub fn append(&mut self, event: T::Event) -> Result<(), Error> {
+        self.val.apply(&event);
+
+        let journal = match self.file.as_mut().and_then(|f| f.journal.as_mut()) {
+            Some(journal) => journal,
+            None => return self.write_data(),
+        };
+
+        journal.sequence += 1;
+        let entry =
+            bincode::serialize(&(journal.sequence, &event)).map_err(Error::Serialization)?;
+
+        // The entry is written at once so an interrupted write can only cut off the end of the log
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&journal.path)
+            .and_then(|mut file| file.write_all(&entry))
+            .with_context(|| format!("Unable to append to log {}", journal.path.display()))?;
+
+        journal.pending += 1;
+        if journal.pending >= journal.snapshot_interval {
+            self.write_snapshot()?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the whole object to its file and starts the log over once the write has finished.
+    fn write_snapshot(&mut self) -> Result<(), Error> {
+        self.write_data()?;
+        self.join_file_thread()?;
+
+        if let Some(journal) = self.file.as_mut().and_then(|f| f.journal.as_mut()) {
+            // Entries left behind if this fails are skipped when replaying, since the file already includes them
+            if let Err(e) = File::create(&journal.path) {
+                warn!("Unable to clear log {}: {}", journal.path.display(), e);
+            }
+            journal.pending = 0;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+    use std::fs;
+
+    #[derive(Deserialize, Serialize, Debug, PartialEq)]
+    struct History {
+        pub values: Vec<u32>,
+    }
+
+    impl Journaled for History {
+        type Event = u32;
+
+        fn apply(&mut self, event: &u32) {
+            self.values.push(*event);
+        }
+    }
+
+    #[test]
+    fn test_journaled() -> Result<(), Error> {
+        let path = PathBuf::from("test_journaled");
+        let log = journal_path(&path)?;
+
+        let result = (|| {
+            let mut linked = FileLinked::new_journaled(History { values: vec![] }, &path, 4)?;
+            for i in 1..=6 {
+                linked.append(i)?;
+            }
+            assert_eq!(linked.readonly().values, vec![1, 2, 3, 4, 5, 6]);
+            drop(linked);
+
+            // Only the first 4 events were written to the file, the rest are replayed from the log
+            let (sequence, written) = bincode::deserialize::<(u64, History)>(&fs::read(&path)?)
+                .map_err(Error::Serialization)?;
+            assert_eq!(sequence, 4);
+            assert_eq!(written.values, vec![1, 2, 3, 4]);
+
+            let mut linked = FileLinked::<History>::from_file_journaled(&path, 4)?;
+            assert_eq!(linked.readonly().values, vec![1, 2, 3, 4, 5, 6]);
+
+            // A log left behind by a write that was interrupted after the file was written isn't applied twice, and
+            // an entry that was cut off is ignored
+            linked.append(7)?;
+            linked.append(8)?;
+            let stale = fs::read(&log)?;
+            linked.append(9)?;
+            linked.append(10)?;
+            drop(linked);
+
+            let mut cut_off = stale.clone();
+            cut_off.extend_from_slice(&[9, 0, 0]);
+            fs::write(&log, cut_off)?;
+
+            let linked = FileLinked::<History>::from_file_journaled(&path, 4)?;
+            assert_eq!(linked.readonly().values, (1..=10).collect::<Vec<u32>>());
+            assert_eq!(fs::metadata(&log)?.len(), 0);
+
+            Ok(())
+        })();
+
+        fs::remove_file(&path)?;
+        fs::remove_file(&log)?;
+        result
+    }
+}
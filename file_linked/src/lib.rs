@@ -1,17 +1,27 @@
 //! A wrapper around an object that ties it to a physical file
 
 pub mod error;
+pub mod journal;
+pub mod shared;
 
 use anyhow::{anyhow, Context};
 use error::Error;
-use log::info;
+use journal::Journal;
+use log::{error, info};
 use serde::{de::DeserializeOwned, Serialize};
 use std::{
-    fs::{copy, remove_file, File},
-    io::{ErrorKind, Write},
+    ffi::OsString,
+    fs::{self, copy, remove_file, File},
+    io::{BufReader, BufWriter, ErrorKind, Read, Write},
     path::{Path, PathBuf},
+    process,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex, PoisonError,
+    },
     thread,
     thread::JoinHandle,
+    time::{SystemTime, UNIX_EPOCH},
 };
 
 /// A wrapper around an object `T` that ties the object to a physical file
@@ -21,9 +31,105 @@ where
     T: Serialize,
 {
     val: T,
+    file: Option<LinkedFile>,
+    file_thread: Option<JoinHandle<Result<(), Error>>>,
+    last_written: Arc<Mutex<Option<SystemTime>>>,
+}
+
+/// The file a [`FileLinked`] object is written to, along with the temp file used while writing.
+#[derive(Debug)]
+struct LinkedFile {
     path: PathBuf,
     temp_file_path: PathBuf,
-    file_thread: Option<JoinHandle<()>>,
+    format: DataFormat,
+    /// The write-ahead log of a journaled object, see [`journal`]
+    journal: Option<Journal>,
+}
+
+/// The format a [`FileLinked`] object is written to its file in.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum DataFormat {
+    /// A compact binary format, used unless another format is requested
+    #[default]
+    Bincode,
+    /// JSON, which can be inspected and edited by hand. `pretty` spreads the output over multiple indented lines,
+    /// files written either way can be read back.
+    #[cfg(feature = "json")]
+    Json { pretty: bool },
+}
+
+impl DataFormat {
+    fn serialize<T: Serialize>(&self, val: &T) -> Result<Vec<u8>, Error> {
+        match self {
+            DataFormat::Bincode => Ok(bincode::serialize(val)
+                .with_context(|| "Unable to serialize object into bincode".to_string())?),
+            #[cfg(feature = "json")]
+            DataFormat::Json { pretty: true } => Ok(serde_json::to_vec_pretty(val)
+                .with_context(|| "Unable to serialize object into json".to_string())?),
+            #[cfg(feature = "json")]
+            DataFormat::Json { pretty: false } => Ok(serde_json::to_vec(val)
+                .with_context(|| "Unable to serialize object into json".to_string())?),
+        }
+    }
+
+    fn serialize_into<W: Write, T: Serialize>(&self, writer: W, val: &T) -> Result<(), Error> {
+        match self {
+            DataFormat::Bincode => Ok(bincode::serialize_into(writer, val)
+                .with_context(|| "Unable to serialize object into bincode".to_string())?),
+            #[cfg(feature = "json")]
+            DataFormat::Json { pretty: true } => Ok(serde_json::to_writer_pretty(writer, val)
+                .with_context(|| "Unable to serialize object into json".to_string())?),
+            #[cfg(feature = "json")]
+            DataFormat::Json { pretty: false } => Ok(serde_json::to_writer(writer, val)
+                .with_context(|| "Unable to serialize object into json".to_string())?),
+        }
+    }
+
+    fn deserialize_from<R: Read, T: DeserializeOwned>(&self, reader: R) -> Result<T, Error> {
+        match self {
+            DataFormat::Bincode => Ok(bincode::deserialize_from(reader)
+                .with_context(|| "Unable to deserialize object from bincode".to_string())?),
+            #[cfg(feature = "json")]
+            DataFormat::Json { .. } => Ok(serde_json::from_reader(reader)
+                .with_context(|| "Unable to deserialize object from json".to_string())?),
+        }
+    }
+}
+
+/// Builds the path of the temp file kept next to `path`. The filename is handled as an [`OsString`] so paths that
+/// aren't valid UTF-8 work as well.
+fn temp_file_path(path: &Path) -> Result<PathBuf, Error> {
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| anyhow!("Unable to get filename for tempfile {}", path.display()))?;
+
+    let mut temp_file_name = OsString::from(".temp");
+    temp_file_name.push(file_name);
+
+    Ok(path.with_file_name(temp_file_name))
+}
+
+/// Records that a write to the file finished successfully. The lock is only held to copy a timestamp, so a poisoned
+/// lock still holds a valid value.
+fn record_write(last_written: &Mutex<Option<SystemTime>>) {
+    *last_written.lock().unwrap_or_else(PoisonError::into_inner) = Some(SystemTime::now());
+}
+
+/// Counts the file names generated by [`FileLinked::new_in`] so names generated at the same time are still unique.
+static GENERATED_FILES: AtomicUsize = AtomicUsize::new(0);
+
+/// Generates a file name from the current time, the id of the process and the number of names generated so far.
+fn generate_file_name() -> Result<String, Error> {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .with_context(|| "Unable to get the current time to generate a filename".to_string())?;
+
+    Ok(format!(
+        "{}-{}-{}",
+        timestamp.as_millis(),
+        process::id(),
+        GENERATED_FILES.fetch_add(1, Ordering::Relaxed)
+    ))
 }
 
 impl<T> Drop for FileLinked<T>
@@ -31,10 +137,10 @@ where
     T: Serialize,
 {
     fn drop(&mut self) {
-        if let Some(file_thread) = self.file_thread.take() {
-            file_thread
-                .join()
-                .expect("Error cleaning up file thread for file_linked object");
+        // The wait blocks on purpose: callers commonly drop the object and open the file again straight away, which a
+        // detached join would turn into a race with the write. Failures can only be logged here, see `close`.
+        if let Err(e) = self.join_file_thread() {
+            error!("Error writing file_linked object to file: {}", e);
         }
     }
 }
@@ -120,59 +226,239 @@ where
     /// # }
     /// ```
     pub fn new(val: T, path: &Path) -> Result<FileLinked<T>, Error> {
-        let mut temp_file_path = path.to_path_buf();
-        temp_file_path.set_file_name(format!(
-            ".temp{}",
-            path.file_name()
-                .ok_or_else(|| anyhow!("Unable to get filename for tempfile {}", path.display()))?
-                .to_str()
-                .ok_or_else(|| anyhow!("Unable to get filename for tempfile {}", path.display()))?
-        ));
+        FileLinked::new_with_format(val, path, DataFormat::Bincode)
+    }
+
+    /// Creates a new [`FileLinked`] object of type `T` stored to a newly named file in the directory `dir`, returning the
+    /// object along with the path of the file. The directory is created if it doesn't exist, and the file name is
+    /// generated from the current time so each call gets its own file.
+    ///
+    /// # Examples
+    /// ```
+    /// # use file_linked::*;
+    /// # use file_linked::error::Error;
+    /// # use std::path::PathBuf;
+    /// #
+    /// # fn main() -> Result<(), Error> {
+    /// let dir = PathBuf::from("./temp_new_in");
+    ///
+    /// let (linked_test, path) = FileLinked::new_in(vec![1, 2, 3], &dir)?;
+    ///
+    /// assert_eq!(path.parent(), Some(dir.as_path()));
+    /// assert_eq!(linked_test.path(), Some(path.as_path()));
+    /// #
+    /// # drop(linked_test);
+    /// #
+    /// # std::fs::remove_dir_all(&dir)?;
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn new_in(val: T, dir: &Path) -> Result<(FileLinked<T>, PathBuf), Error> {
+        fs::create_dir_all(dir)
+            .with_context(|| format!("Unable to create directory {}", dir.display()))?;
+
+        // Names are skipped if another process already wrote a file with the same name
+        let path = loop {
+            let path = dir.join(generate_file_name()?);
+            if !path.exists() && !temp_file_path(&path)?.exists() {
+                break path;
+            }
+        };
+
+        Ok((FileLinked::new(val, &path)?, path))
+    }
+
+    /// Creates a new [`FileLinked`] object of type `T` stored to the file given by `path` in the given `format`.
+    ///
+    /// # Examples
+    /// ```
+    /// # use file_linked::*;
+    /// # use file_linked::error::Error;
+    /// # use std::path::PathBuf;
+    /// #
+    /// # #[cfg(feature = "json")]
+    /// # fn main() -> Result<(), Error> {
+    /// let linked_test = FileLinked::new_with_format(
+    ///     vec![1, 2, 3],
+    ///     &PathBuf::from("./temp_json"),
+    ///     DataFormat::Json { pretty: true },
+    /// )?;
+    /// linked_test.close()?;
+    ///
+    /// let linked_test = FileLinked::<Vec<i32>>::from_file_with_format(
+    ///     &PathBuf::from("./temp_json"),
+    ///     DataFormat::Json { pretty: false },
+    /// )?;
+    /// assert_eq!(*linked_test.readonly(), vec![1, 2, 3]);
+    /// #
+    /// # drop(linked_test);
+    /// #
+    /// # std::fs::remove_file("./temp_json").expect("Unable to remove file");
+    /// #
+    /// # Ok(())
+    /// # }
+    /// # #[cfg(not(feature = "json"))]
+    /// # fn main() {}
+    /// ```
+    pub fn new_with_format(
+        val: T,
+        path: &Path,
+        format: DataFormat,
+    ) -> Result<FileLinked<T>, Error> {
+        let temp_file_path = temp_file_path(path)?;
 
         let mut result = FileLinked {
             val,
-            path: path.to_path_buf(),
-            temp_file_path,
+            file: Some(LinkedFile {
+                path: path.to_path_buf(),
+                temp_file_path,
+                format,
+                journal: None,
+            }),
             file_thread: None,
+            last_written: Arc::default(),
         };
 
         result.write_data()?;
         Ok(result)
     }
 
-    fn write_data(&mut self) -> Result<(), Error> {
-        let thread_path = self.path.clone();
-        let thread_temp_path = self.temp_file_path.clone();
-        let thread_val = bincode::serialize(&self.val)
-            .with_context(|| "Unable to serialize object into bincode".to_string())?;
-
-        if let Some(file_thread) = self.file_thread.take() {
-            file_thread
-                .join()
-                .expect("Error cleaning up file thread for file_linked object");
+    /// Creates a new [`FileLinked`] object of type `T` that is only held in memory. Modifications behave the same as
+    /// with a file, except that nothing is written to disk.
+    ///
+    /// # Examples
+    /// ```
+    /// # use file_linked::*;
+    /// # use file_linked::error::Error;
+    /// # use serde::{Deserialize, Serialize};
+    /// #
+    /// # #[derive(Deserialize, Serialize)]
+    /// # struct Test {
+    /// #     pub a: u32,
+    /// #     pub b: String,
+    /// #     pub c: f64
+    /// # }
+    /// #
+    /// # fn main() -> Result<(), Error> {
+    /// let test = Test {
+    ///     a: 1,
+    ///     b: String::from("two"),
+    ///     c: 3.0
+    /// };
+    ///
+    /// let mut linked_test = FileLinked::in_memory(test);
+    ///
+    /// linked_test.mutate(|t| t.a = 2)?;
+    ///
+    /// assert_eq!(linked_test.readonly().a, 2);
+    /// assert!(linked_test.path().is_none());
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn in_memory(val: T) -> FileLinked<T> {
+        FileLinked {
+            val,
+            file: None,
+            file_thread: None,
+            last_written: Arc::default(),
         }
+    }
+
+    /// Returns the path of the file the object is written to, or `None` if the object is only held in memory.
+    pub fn path(&self) -> Option<&Path> {
+        self.file.as_ref().map(|f| f.path.as_path())
+    }
 
-        match File::open(&self.path) {
+    /// Returns when the object was last successfully written to its file, or `None` if it hasn't been written since
+    /// it was created or read. Writes happen in the background, so call [`flush`](FileLinked::flush) first to include
+    /// the latest modification.
+    ///
+    /// # Examples
+    /// ```
+    /// # use file_linked::*;
+    /// # use file_linked::error::Error;
+    /// # use std::path::PathBuf;
+    /// #
+    /// # fn main() -> Result<(), Error> {
+    /// let mut linked_test = FileLinked::new(1, &PathBuf::from("./temp_last_written"))?;
+    /// linked_test.flush()?;
+    /// let written = linked_test.last_written().unwrap();
+    ///
+    /// linked_test.mutate(|t| *t = 2)?;
+    /// linked_test.flush()?;
+    ///
+    /// assert!(linked_test.last_written().unwrap() >= written);
+    /// #
+    /// # drop(linked_test);
+    /// #
+    /// # std::fs::remove_file("./temp_last_written").expect("Unable to remove file");
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn last_written(&self) -> Option<SystemTime> {
+        *self
+            .last_written
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn write_data(&mut self) -> Result<(), Error> {
+        let file = match &self.file {
+            Some(file) => file,
+            None => return Ok(()),
+        };
+
+        let thread_path = file.path.clone();
+        let thread_temp_path = file.temp_file_path.clone();
+        // Journaled objects are written along with the number of events they include
+        let thread_val = match &file.journal {
+            Some(journal) => file.format.serialize(&(journal.sequence, &self.val))?,
+            None => file.format.serialize(&self.val)?,
+        };
+        let last_written = self.last_written.clone();
+
+        self.join_file_thread()?;
+
+        match File::open(&thread_path) {
             Ok(_) => {
                 let handle = thread::spawn(move || {
-                    copy(&thread_path, &thread_temp_path).expect("Unable to copy temp file");
+                    copy(&thread_path, &thread_temp_path).with_context(|| {
+                        format!("Unable to copy temp file {}", thread_temp_path.display())
+                    })?;
+
+                    let mut file = File::create(&thread_path).with_context(|| {
+                        format!("Error creating file handle {}", thread_path.display())
+                    })?;
 
-                    let mut file = File::create(&thread_path).expect("Error creating file handle");
+                    file.write_all(thread_val.as_slice()).with_context(|| {
+                        format!("Failed to write data to file {}", thread_path.display())
+                    })?;
 
-                    file.write_all(thread_val.as_slice())
-                        .expect("Failed to write data to file");
+                    remove_file(&thread_temp_path).with_context(|| {
+                        format!("Error removing temp file {}", thread_temp_path.display())
+                    })?;
 
-                    remove_file(&thread_temp_path).expect("Error removing temp file");
+                    record_write(&last_written);
+                    Ok(())
                 });
 
                 self.file_thread = Some(handle);
             }
             Err(error) if error.kind() == ErrorKind::NotFound => {
                 let handle = thread::spawn(move || {
-                    let mut file = File::create(&thread_path).expect("Error creating file handle");
+                    let mut file = File::create(&thread_path).with_context(|| {
+                        format!("Error creating file handle {}", thread_path.display())
+                    })?;
+
+                    file.write_all(thread_val.as_slice()).with_context(|| {
+                        format!("Failed to write data to file {}", thread_path.display())
+                    })?;
 
-                    file.write_all(thread_val.as_slice())
-                        .expect("Failed to write data to file");
+                    record_write(&last_written);
+                    Ok(())
                 });
 
                 self.file_thread = Some(handle);
@@ -183,6 +469,95 @@ where
         Ok(())
     }
 
+    fn write_data_streamed(&mut self) -> Result<(), Error> {
+        let (path, temp_file_path, format, sequence) = match &self.file {
+            Some(file) => (
+                file.path.clone(),
+                file.temp_file_path.clone(),
+                file.format,
+                file.journal.as_ref().map(|j| j.sequence),
+            ),
+            None => return Ok(()),
+        };
+
+        self.join_file_thread()?;
+
+        let existing = match File::open(&path) {
+            Ok(_) => true,
+            Err(error) if error.kind() == ErrorKind::NotFound => false,
+            Err(error) => return Err(Error::IO(error)),
+        };
+
+        if existing {
+            copy(&path, &temp_file_path).with_context(|| {
+                format!("Unable to copy temp file {}", temp_file_path.display())
+            })?;
+        }
+
+        let file = File::create(&path)
+            .with_context(|| format!("Error creating file handle {}", path.display()))?;
+        let mut writer = BufWriter::new(file);
+
+        match sequence {
+            Some(sequence) => format.serialize_into(&mut writer, &(sequence, &self.val)),
+            None => format.serialize_into(&mut writer, &self.val),
+        }
+        .with_context(|| format!("Unable to serialize object into file {}", path.display()))?;
+        writer
+            .flush()
+            .with_context(|| format!("Failed to write data to file {}", path.display()))?;
+
+        if existing {
+            remove_file(&temp_file_path).with_context(|| {
+                format!("Error removing temp file {}", temp_file_path.display())
+            })?;
+        }
+
+        record_write(&self.last_written);
+        Ok(())
+    }
+
+    fn join_file_thread(&mut self) -> Result<(), Error> {
+        match self.file_thread.take().map(|t| t.join()) {
+            Some(Ok(result)) => result,
+            Some(Err(_)) => Err(Error::Other(anyhow!(
+                "File thread for file_linked object panicked"
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    /// Waits for any pending write to the file to finish, returning an error if the write failed. Dropping a
+    /// [`FileLinked`] object also waits for the write, blocking the dropping thread so that the file is complete as
+    /// soon as the object is gone and can be opened again right away, but it can only log failures. Prefer calling
+    /// `close` in async contexts so the wait happens at a known point and errors aren't lost.
+    ///
+    /// # Examples
+    /// ```
+    /// # use file_linked::*;
+    /// # use file_linked::error::Error;
+    /// # use std::path::PathBuf;
+    /// #
+    /// # fn main() -> Result<(), Error> {
+    /// let linked_test = FileLinked::new(1, &PathBuf::from("./temp_close"))?;
+    ///
+    /// linked_test.close()?;
+    /// #
+    /// # std::fs::remove_file("./temp_close").expect("Unable to remove file");
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn close(mut self) -> Result<(), Error> {
+        self.join_file_thread()
+    }
+
+    /// Waits for any pending write to the file to finish in the same way as [`close`](FileLinked::close), but keeps the
+    /// object so it can be modified further.
+    pub fn flush(&mut self) -> Result<(), Error> {
+        self.join_file_thread()
+    }
+
     /// Modifies the data contained in a `FileLinked` object using a callback `op` that has a mutable reference to the
     /// underlying data. After the mutable operation is performed the data is written to a file to synchronize the state.
     ///
@@ -233,6 +608,99 @@ where
         Ok(result)
     }
 
+    /// Modifies the data contained in a `FileLinked` object in the same way as [`mutate`](FileLinked::mutate), but
+    /// serializes the data straight into the file instead of into an intermediate buffer. This avoids holding a second
+    /// copy of large values in memory, at the cost of blocking until the write has finished.
+    ///
+    /// # Examples
+    /// ```
+    /// # use file_linked::*;
+    /// # use file_linked::error::Error;
+    /// # use std::path::PathBuf;
+    /// #
+    /// # fn main() -> Result<(), Error> {
+    /// let mut linked_test = FileLinked::new(vec![0u8; 1024], &PathBuf::from("./temp_map_serialized"))?;
+    ///
+    /// linked_test.map_serialized(|v| v[0] = 1)?;
+    ///
+    /// assert_eq!(linked_test.readonly()[0], 1);
+    /// #
+    /// # drop(linked_test);
+    /// #
+    /// # std::fs::remove_file("./temp_map_serialized").expect("Unable to remove file");
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn map_serialized<U, F: FnOnce(&mut T) -> U>(&mut self, op: F) -> Result<U, Error> {
+        let result = op(&mut self.val);
+
+        self.write_data_streamed()?;
+
+        Ok(result)
+    }
+
+    /// Modifies the data contained in a `FileLinked` object using a callback `op` that returns a result along with a
+    /// flag reporting whether the data was changed. The data is only written to a file when the flag is `true`.
+    ///
+    /// The caller is responsible for honestly reporting whether the data changed, if `op` modifies the data and returns
+    /// `false` the file will be out of sync with the object until the next write.
+    ///
+    /// # Examples
+    /// ```
+    /// # use file_linked::*;
+    /// # use file_linked::error::Error;
+    /// # use serde::{Deserialize, Serialize};
+    /// # use std::fmt;
+    /// # use std::string::ToString;
+    /// # use std::path::PathBuf;
+    /// #
+    /// # #[derive(Deserialize, Serialize)]
+    /// # struct Test {
+    /// #     pub a: u32,
+    /// #     pub b: String,
+    /// #     pub c: f64
+    /// # }
+    /// #
+    /// # fn main() -> Result<(), Error> {
+    /// let test = Test {
+    ///     a: 1,
+    ///     b: String::from(""),
+    ///     c: 0.0
+    /// };
+    ///
+    /// let mut linked_test = FileLinked::new(test, &PathBuf::from("./temp"))
+    ///     .expect("Unable to create file linked object");
+    ///
+    /// let changed = linked_test.mutate_if(|t| {
+    ///     if t.a == 1 {
+    ///         t.a = 2;
+    ///         (true, true)
+    ///     } else {
+    ///         (false, false)
+    ///     }
+    /// })?;
+    ///
+    /// assert!(changed);
+    /// assert_eq!(linked_test.readonly().a, 2);
+    /// #
+    /// # drop(linked_test);
+    /// #
+    /// # std::fs::remove_file("./temp").expect("Unable to remove file");
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn mutate_if<U, F: FnOnce(&mut T) -> (U, bool)>(&mut self, op: F) -> Result<U, Error> {
+        let (result, changed) = op(&mut self.val);
+
+        if changed {
+            self.write_data()?;
+        }
+
+        Ok(result)
+    }
+
     /// Replaces the value held by the `FileLinked` object with `val`. After replacing the object will be written to a file.
     ///
     /// # Examples
@@ -289,7 +757,8 @@ impl<T> FileLinked<T>
 where
     T: Serialize + DeserializeOwned,
 {
-    /// Deserializes an object `T` from the file given by `path`
+    /// Deserializes an object `T` from the file given by `path`, falling back to the temp file left by an interrupted
+    /// write. Returns [`Error::NotFound`] when neither file exists and [`Error::Corrupt`] when they can't be read.
     ///
     /// # Examples
     /// ```
@@ -342,26 +811,44 @@ where
     /// # }
     /// ```
     pub fn from_file(path: &Path) -> Result<FileLinked<T>, Error> {
-        let mut temp_file_path = path.to_path_buf();
-        temp_file_path.set_file_name(format!(
-            ".temp{}",
-            path.file_name()
-                .ok_or_else(|| anyhow!("Unable to get filename for tempfile {}", path.display()))?
-                .to_str()
-                .ok_or_else(|| anyhow!("Unable to get filename for tempfile {}", path.display()))?
-        ));
-
-        match File::open(path).map_err(Error::from).and_then(|file| {
-            bincode::deserialize_from::<File, T>(file)
-                .with_context(|| format!("Unable to deserialize file {}", path.display()))
-                .map_err(Error::from)
-        }) {
-            Ok(val) => Ok(FileLinked {
-                val,
+        FileLinked::from_file_with_format(path, DataFormat::Bincode)
+    }
+
+    /// Deserializes an object `T` written in the given `format` from the file given by `path`, in the same way as
+    /// [`from_file`](FileLinked::from_file). Further writes use the same format.
+    pub fn from_file_with_format(path: &Path, format: DataFormat) -> Result<FileLinked<T>, Error> {
+        let temp_file_path = temp_file_path(path)?;
+        let val = FileLinked::read_file(path, &temp_file_path, format)?;
+
+        Ok(FileLinked {
+            val,
+            file: Some(LinkedFile {
                 path: path.to_path_buf(),
                 temp_file_path,
-                file_thread: None,
+                format,
+                journal: None,
             }),
+            file_thread: None,
+            last_written: Arc::default(),
+        })
+    }
+
+    /// Reads the object from `path`, falling back to the temp file and restoring it if `path` can't be read.
+    fn read_file(path: &Path, temp_file_path: &Path, format: DataFormat) -> Result<T, Error> {
+        let primary = File::open(path);
+        let primary_missing = matches!(&primary, Err(e) if e.kind() == ErrorKind::NotFound);
+
+        let val = match primary.map_err(Error::from).and_then(|file| {
+            format
+                .deserialize_from::<_, T>(BufReader::new(file))
+                .with_context(|| format!("Unable to deserialize file {}", path.display()))
+                .map_err(Error::from)
+        }) {
+            Ok(val) => val,
+            // Nothing was ever written, which callers usually want to treat as a fresh start
+            Err(_) if primary_missing && !temp_file_path.exists() => {
+                return Err(Error::NotFound(path.to_path_buf()));
+            }
             Err(err) => {
                 info!(
                     "Unable to read/deserialize file {} attempting to open temp file {}",
@@ -370,25 +857,62 @@ where
                 );
 
                 // Try to use temp file instead and see if that file exists and is serializable
-                let val = FileLinked::from_temp_file(&temp_file_path, path)
-                    .map_err(|_| err)
-                    .with_context(|| format!("Failed to read/deserialize the object from the file {} and temp file {}", path.display(), temp_file_path.display()))?;
-
-                Ok(FileLinked {
-                    val,
-                    path: path.to_path_buf(),
-                    temp_file_path,
-                    file_thread: None,
-                })
+                FileLinked::from_temp_file(temp_file_path, path, format).map_err(|temp_err| {
+                    Error::Corrupt {
+                        path: path.to_path_buf(),
+                        temp_file_path: temp_file_path.to_path_buf(),
+                        reason: format!("{}; {}", err, temp_err),
+                    }
+                })?
             }
-        }
+        };
+
+        Ok(val)
+    }
+
+    /// Deserializes an object `T` written in the given `format` from the file given by `path` without linking the object
+    /// to the file, so changes are only kept in memory like with [`in_memory`](FileLinked::in_memory). Falls back to
+    /// the temp file in the same way as [`from_file`](FileLinked::from_file) but never restores it, leaving both files
+    /// untouched.
+    pub fn from_file_unlinked(path: &Path, format: DataFormat) -> Result<FileLinked<T>, Error> {
+        let temp_file_path = temp_file_path(path)?;
+
+        let read = |p: &Path| -> Result<T, Error> {
+            let file = File::open(p)?;
+            Ok(format
+                .deserialize_from::<_, T>(BufReader::new(file))
+                .with_context(|| format!("Unable to deserialize file {}", p.display()))?)
+        };
+
+        let val = match read(path) {
+            Ok(val) => val,
+            Err(_) if !path.exists() && !temp_file_path.exists() => {
+                return Err(Error::NotFound(path.to_path_buf()));
+            }
+            Err(err) => read(&temp_file_path).map_err(|temp_err| Error::Corrupt {
+                path: path.to_path_buf(),
+                temp_file_path: temp_file_path.clone(),
+                reason: format!("{}; {}", err, temp_err),
+            })?,
+        };
+
+        Ok(FileLinked::in_memory(val))
+    }
+
+    fn from_temp_file(temp_file_path: &Path, path: &Path, format: DataFormat) -> Result<T, Error> {
+        FileLinked::from_temp_file_with(temp_file_path, path, format, |from, to| copy(from, to))
     }
 
-    fn from_temp_file(temp_file_path: &Path, path: &Path) -> Result<T, Error> {
-        let file = File::open(temp_file_path)
+    fn from_temp_file_with<F: FnOnce(&Path, &Path) -> std::io::Result<u64>>(
+        temp_file_path: &Path,
+        path: &Path,
+        format: DataFormat,
+        copy_file: F,
+    ) -> Result<T, Error> {
+        let data = fs::read(temp_file_path)
             .with_context(|| format!("Unable to open file {}", temp_file_path.display()))?;
 
-        let val = bincode::deserialize_from(file).with_context(|| {
+        let val = format.deserialize_from(data.as_slice()).with_context(|| {
             format!(
                 "Could not deserialize from temp file {}",
                 temp_file_path.display()
@@ -397,7 +921,19 @@ where
 
         info!("Successfully deserialized value from temp file");
 
-        copy(temp_file_path, path)?;
+        copy_file(temp_file_path, path)?;
+
+        // The temp file is the only good copy of the data until the copy is known to be complete
+        let copied = fs::read(path)
+            .with_context(|| format!("Unable to read restored file {}", path.display()))?;
+        if copied != data {
+            return Err(Error::Other(anyhow!(
+                "Restored file {} does not match temp file {}, keeping the temp file",
+                path.display(),
+                temp_file_path.display()
+            )));
+        }
+
         remove_file(temp_file_path)
             .with_context(|| format!("Unable to remove temp file {}", temp_file_path.display()))?;
 
@@ -408,7 +944,37 @@ where
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::{fs, fs::File};
+    use std::{
+        alloc::{GlobalAlloc, Layout, System},
+        cell::Cell,
+        fs,
+        fs::File,
+    };
+
+    /// Counts the bytes allocated by each thread so tests can check how much memory a write needs
+    struct CountingAllocator;
+
+    thread_local! {
+        static ALLOCATED: Cell<usize> = const { Cell::new(0) };
+    }
+
+    unsafe impl GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            let _ = ALLOCATED.try_with(|a| a.set(a.get() + layout.size()));
+            System.alloc(layout)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            System.dealloc(ptr, layout)
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+    fn allocated() -> usize {
+        ALLOCATED.with(|a| a.get())
+    }
 
     struct CleanUp {
         path: PathBuf,
@@ -441,7 +1007,7 @@ mod tests {
         cleanup.run(|p| {
             let val = vec!["one", "two", ""];
 
-            let linked_object = FileLinked::new(val.clone(), &p)?;
+            let linked_object = FileLinked::new(val.clone(), p)?;
             assert_eq!(*linked_object.readonly(), val);
 
             Ok(())
@@ -455,9 +1021,9 @@ mod tests {
         cleanup.run(|p| {
             let val = "test";
 
-            FileLinked::new(val, &p)?;
+            FileLinked::new(val, p)?;
 
-            let file = File::open(&p)?;
+            let file = File::open(p)?;
             let result: String =
                 bincode::deserialize_from(file).expect("Unable to deserialize from file");
             assert_eq!(result, val);
@@ -466,13 +1032,105 @@ mod tests {
         })
     }
 
+    #[test]
+    fn test_new_in() -> Result<(), Error> {
+        let dir = PathBuf::from("test_new_in");
+
+        let result = (|| {
+            let (first, first_path) = FileLinked::new_in(1, &dir)?;
+            let (second, second_path) = FileLinked::new_in(2, &dir)?;
+            assert_ne!(first_path, second_path);
+            drop((first, second));
+
+            assert_eq!(*FileLinked::<i32>::from_file(&first_path)?.readonly(), 1);
+            assert_eq!(*FileLinked::<i32>::from_file(&second_path)?.readonly(), 2);
+            assert_eq!(fs::read_dir(&dir)?.count(), 2);
+
+            Ok(())
+        })();
+
+        fs::remove_dir_all(&dir)?;
+        result
+    }
+
+    #[test]
+    fn test_in_memory() -> Result<(), Error> {
+        let mut test = FileLinked::in_memory(vec![1, 2, 3]);
+        assert!(test.path().is_none());
+
+        test.mutate(|v| v.push(4))?;
+        test.replace(vec![5])?;
+        assert_eq!(test.readonly(), &vec![5]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_close() -> Result<(), Error> {
+        let path = PathBuf::from("test_close");
+        let cleanup = CleanUp::new(&path);
+        cleanup.run(|p| {
+            FileLinked::new(vec![1, 2, 3], p)?.close()?;
+
+            let file = File::open(p)?;
+            let result: Vec<i32> =
+                bincode::deserialize_from(file).expect("Unable to deserialize from file");
+            assert_eq!(result, vec![1, 2, 3]);
+
+            Ok(())
+        })?;
+
+        // Writing into a directory that doesn't exist fails on the file thread
+        let missing = PathBuf::from("test_close_missing_dir").join("test_close");
+        assert!(FileLinked::new(vec![1, 2, 3], &missing)?.close().is_err());
+
+        // Dropping the object logs the same failure instead of panicking
+        drop(FileLinked::new(vec![1, 2, 3], &missing)?);
+        assert!(!missing.exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_last_written() -> Result<(), Error> {
+        assert!(FileLinked::in_memory(1).last_written().is_none());
+
+        let path = PathBuf::from("test_last_written");
+        let cleanup = CleanUp::new(&path);
+        cleanup.run(|p| {
+            let mut linked = FileLinked::new(vec![1, 2, 3], p)?;
+            linked.flush()?;
+            let created = linked
+                .last_written()
+                .expect("Creating the file wasn't recorded");
+
+            thread::sleep(std::time::Duration::from_millis(10));
+            linked.mutate(|v| v.push(4))?;
+            linked.flush()?;
+            let mutated = linked.last_written().expect("Mutating wasn't recorded");
+            assert!(mutated > created);
+
+            thread::sleep(std::time::Duration::from_millis(10));
+            linked.map_serialized(|v| v.push(5))?;
+            assert!(linked.last_written().unwrap() > mutated);
+            drop(linked);
+
+            // Reading the file back doesn't count as a write
+            assert!(FileLinked::<Vec<i32>>::from_file(p)?
+                .last_written()
+                .is_none());
+
+            Ok(())
+        })
+    }
+
     #[test]
     fn test_mutate() -> Result<(), Error> {
         let path = PathBuf::from("test_mutate");
         let cleanup = CleanUp::new(&path);
         cleanup.run(|p| {
             let list = vec![1, 2, 3, 4];
-            let mut file_linked_list = FileLinked::new(list, &p)?;
+            let mut file_linked_list = FileLinked::new(list, p)?;
             assert_eq!(*file_linked_list.readonly(), vec![1, 2, 3, 4]);
 
             file_linked_list.mutate(|v1| v1.push(5))?;
@@ -486,6 +1144,75 @@ mod tests {
         })
     }
 
+    #[test]
+    fn test_map_serialized() -> Result<(), Error> {
+        let path = PathBuf::from("test_map_serialized");
+        let cleanup = CleanUp::new(&path);
+        cleanup.run(|p| {
+            let size = 1 << 20;
+            let mut test = FileLinked::new(vec![0u64; size], p)?;
+
+            let before = allocated();
+            test.mutate(|v| v[0] = 1)?;
+            let buffered = allocated() - before;
+
+            let before = allocated();
+            test.map_serialized(|v| v[1] = 2)?;
+            let streamed = allocated() - before;
+
+            // Only the mutate call serializes the whole vector into a buffer first
+            assert!(buffered >= size * 8);
+            assert!(streamed < size * 8 / 100);
+
+            let file = File::open(p)?;
+            let result: Vec<u64> =
+                bincode::deserialize_from(file).expect("Unable to deserialize from file");
+            assert_eq!(result.len(), size);
+            assert_eq!(result[..3], [1, 2, 0]);
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_mutate_if() -> Result<(), Error> {
+        let path = PathBuf::from("test_mutate_if");
+        let cleanup = CleanUp::new(&path);
+        cleanup.run(|p| {
+            let mut file_linked_list = FileLinked::new(vec![1, 2, 3], p)?;
+
+            let result = file_linked_list.mutate_if(|v1| (v1.len(), false))?;
+            assert_eq!(result, 3);
+
+            // Changing the value but reporting no change should leave the file untouched
+            file_linked_list.mutate_if(|v1| {
+                v1.push(4);
+                ((), false)
+            })?;
+            assert_eq!(*file_linked_list.readonly(), vec![1, 2, 3, 4]);
+            drop(file_linked_list);
+
+            let file = File::open(p)?;
+            let result: Vec<i32> =
+                bincode::deserialize_from(file).expect("Unable to deserialize from file");
+            assert_eq!(result, vec![1, 2, 3]);
+
+            let mut file_linked_list: FileLinked<Vec<i32>> = FileLinked::from_file(p)?;
+            file_linked_list.mutate_if(|v1| {
+                v1.push(4);
+                ((), true)
+            })?;
+            drop(file_linked_list);
+
+            let file = File::open(p)?;
+            let result: Vec<i32> =
+                bincode::deserialize_from(file).expect("Unable to deserialize from file");
+            assert_eq!(result, vec![1, 2, 3, 4]);
+
+            Ok(())
+        })
+    }
+
     #[test]
     fn test_replace() -> Result<(), Error> {
         let path = PathBuf::from("test_replace");
@@ -493,7 +1220,7 @@ mod tests {
         cleanup.run(|p| {
             let val1 = String::from("val1");
             let val2 = String::from("val2");
-            let mut file_linked_list = FileLinked::new(val1.clone(), &p)?;
+            let mut file_linked_list = FileLinked::new(val1.clone(), p)?;
             assert_eq!(*file_linked_list.readonly(), val1);
 
             file_linked_list.replace(val2.clone())?;
@@ -510,15 +1237,182 @@ mod tests {
         let cleanup = CleanUp::new(&path);
         cleanup.run(|p| {
             let value: Vec<f64> = vec![2.0, 3.0, 5.0];
-            let file = File::create(&p)?;
+            let file = File::create(p)?;
+
+            bincode::serialize_into(&file, &value).expect("Unable to serialize into file");
+            drop(file);
+
+            let linked_object: FileLinked<Vec<f64>> = FileLinked::from_file(p)?;
+            assert_eq!(*linked_object.readonly(), value);
+
+            drop(linked_object);
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_from_file_missing() {
+        let result: Result<FileLinked<Vec<f64>>, Error> =
+            FileLinked::from_file(&PathBuf::from("test_from_file_missing"));
+
+        assert!(matches!(result, Err(Error::NotFound(_))));
+    }
+
+    #[test]
+    fn test_from_file_corrupt() -> Result<(), Error> {
+        let path = PathBuf::from("test_from_file_corrupt");
+        let temp_path = PathBuf::from(".temptest_from_file_corrupt");
+        let cleanup = CleanUp::new(&path);
+        let _temp_cleanup = CleanUp::new(&temp_path);
+        cleanup.run(|p| {
+            fs::write(p, [1])?;
+
+            let result: Result<FileLinked<Vec<f64>>, Error> = FileLinked::from_file(p);
+            assert!(matches!(result, Err(Error::Corrupt { .. })));
 
+            // A readable temp file is used to recover the value
+            let value: Vec<f64> = vec![2.0, 3.0, 5.0];
+            let file = File::create(&temp_path)?;
             bincode::serialize_into(&file, &value).expect("Unable to serialize into file");
             drop(file);
 
-            let linked_object: FileLinked<Vec<f64>> = FileLinked::from_file(&p)?;
+            let linked_object: FileLinked<Vec<f64>> = FileLinked::from_file(p)?;
             assert_eq!(*linked_object.readonly(), value);
+            assert!(!temp_path.exists());
 
+            // Only a corrupt temp file is left
             drop(linked_object);
+            fs::remove_file(p)?;
+            fs::write(&temp_path, [1])?;
+
+            let result: Result<FileLinked<Vec<f64>>, Error> = FileLinked::from_file(p);
+            assert!(matches!(result, Err(Error::Corrupt { .. })));
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_from_temp_file_short_copy() -> Result<(), Error> {
+        let path = PathBuf::from("test_from_temp_file_short_copy");
+        let temp_path = PathBuf::from(".temptest_from_temp_file_short_copy");
+        let cleanup = CleanUp::new(&path);
+        let _temp_cleanup = CleanUp::new(&temp_path);
+        cleanup.run(|p| {
+            let value: Vec<f64> = vec![2.0, 3.0, 5.0];
+            fs::write(
+                &temp_path,
+                bincode::serialize(&value).expect("Unable to serialize"),
+            )?;
+
+            // Only half of the temp file makes it to the restored file
+            let result = FileLinked::<Vec<f64>>::from_temp_file_with(
+                &temp_path,
+                p,
+                DataFormat::Bincode,
+                |from, to| {
+                    let data = fs::read(from)?;
+                    fs::write(to, &data[..data.len() / 2])?;
+                    Ok(data.len() as u64 / 2)
+                },
+            );
+            assert!(result.is_err());
+            assert!(temp_path.exists());
+
+            // The temp file is still usable afterwards
+            let linked_object: FileLinked<Vec<f64>> = FileLinked::from_file(p)?;
+            assert_eq!(*linked_object.readonly(), value);
+            assert!(!temp_path.exists());
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_from_file_unlinked() -> Result<(), Error> {
+        let path = PathBuf::from("test_from_file_unlinked");
+        let cleanup = CleanUp::new(&path);
+        cleanup.run(|p| {
+            FileLinked::new(vec![1, 2], p)?.close()?;
+            let written = fs::read(p)?;
+
+            let mut linked_object =
+                FileLinked::<Vec<i32>>::from_file_unlinked(p, DataFormat::Bincode)?;
+            assert_eq!(*linked_object.readonly(), vec![1, 2]);
+            assert!(linked_object.path().is_none());
+
+            linked_object.mutate(|v| v.push(3))?;
+            drop(linked_object);
+            assert_eq!(fs::read(p)?, written);
+
+            // A temp file left by an interrupted write is read from but not restored
+            let temp = temp_file_path(p)?;
+            fs::rename(p, &temp)?;
+            let result = FileLinked::<Vec<i32>>::from_file_unlinked(p, DataFormat::Bincode);
+            assert_eq!(*result?.readonly(), vec![1, 2]);
+            assert!(!p.exists());
+            fs::remove_file(&temp)?;
+
+            assert!(matches!(
+                FileLinked::<Vec<i32>>::from_file_unlinked(p, DataFormat::Bincode),
+                Err(Error::NotFound(_))
+            ));
+
+            Ok(())
+        })
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_non_utf8_path() -> Result<(), Error> {
+        use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+        let path = PathBuf::from(OsStr::from_bytes(b"test_non_utf8_path_\xff"));
+        assert!(path.to_str().is_none());
+
+        let cleanup = CleanUp::new(&path);
+        cleanup.run(|p| {
+            assert_eq!(
+                temp_file_path(p)?,
+                PathBuf::from(OsStr::from_bytes(b".temptest_non_utf8_path_\xff"))
+            );
+
+            let mut linked_object = FileLinked::new(vec![1, 2, 3], p)?;
+            linked_object.mutate(|v| v.push(4))?;
+            drop(linked_object);
+
+            let linked_object = FileLinked::<Vec<i32>>::from_file(p)?;
+            assert_eq!(*linked_object.readonly(), vec![1, 2, 3, 4]);
+
+            Ok(())
+        })
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_json() -> Result<(), Error> {
+        let path = PathBuf::from("test_json");
+        let cleanup = CleanUp::new(&path);
+        cleanup.run(|p| {
+            let value = vec![(1, "one".to_string()), (2, "two".to_string())];
+
+            for pretty in [true, false] {
+                let format = DataFormat::Json { pretty };
+                let mut linked_object = FileLinked::new_with_format(value.clone(), p, format)?;
+                linked_object.map_serialized(|v| v.push((3, "three".to_string())))?;
+                linked_object.mutate(|v| v.push((4, "four".to_string())))?;
+                linked_object.close()?;
+
+                let contents = fs::read_to_string(p)?;
+                assert_eq!(contents.contains('\n'), pretty);
+
+                // Files written either way are read back the same
+                let linked_object: FileLinked<Vec<(i32, String)>> =
+                    FileLinked::from_file_with_format(p, DataFormat::Json { pretty: !pretty })?;
+                assert_eq!(linked_object.readonly().len(), 4);
+                assert_eq!(linked_object.readonly()[..2], value[..]);
+            }
+
             Ok(())
         })
     }
@@ -0,0 +1,190 @@
+//! A [`FileLinked`] object that can be shared between threads and tasks
+
+use crate::{error::Error, FileLinked};
+use anyhow::anyhow;
+use serde::Serialize;
+use std::{
+    ops::Deref,
+    path::PathBuf,
+    sync::{Arc, PoisonError, RwLock, RwLockReadGuard},
+    time::SystemTime,
+};
+
+/// A [`FileLinked`] object behind a lock so it can be read and mutated through a shared reference. Clones refer to the
+/// same object, and only one clone can mutate the object at a time, so the file always holds the result of every
+/// mutation made before it.
+///
+/// # Examples
+/// ```
+/// # use file_linked::*;
+/// # use file_linked::error::Error;
+/// # use file_linked::shared::SharedFileLinked;
+/// # use std::path::PathBuf;
+/// # use std::thread;
+/// #
+/// # fn main() -> Result<(), Error> {
+/// let shared = SharedFileLinked::new(FileLinked::new(0, &PathBuf::from("./temp_shared"))?);
+///
+/// let handles = (0..4)
+///     .map(|_| {
+///         let shared = shared.clone();
+///         thread::spawn(move || shared.mutate(|v| *v += 1))
+///     })
+///     .collect::<Vec<_>>();
+///
+/// for handle in handles {
+///     handle.join().expect("Thread panicked")?;
+/// }
+///
+/// assert_eq!(*shared.readonly(), 4);
+/// #
+/// # drop(shared);
+/// #
+/// # std::fs::remove_file("./temp_shared").expect("Unable to remove file");
+/// #
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct SharedFileLinked<T>
+where
+    T: Serialize,
+{
+    inner: Arc<RwLock<FileLinked<T>>>,
+}
+
+impl<T> Clone for SharedFileLinked<T>
+where
+    T: Serialize,
+{
+    fn clone(&self) -> Self {
+        SharedFileLinked {
+            inner: Arc::clone(&self.inner),
+        }
+    }
+}
+
+/// A read only reference to the object held by a [`SharedFileLinked`]. Mutations wait until the reference is dropped.
+pub struct SharedReadGuard<'a, T>
+where
+    T: Serialize,
+{
+    guard: RwLockReadGuard<'a, FileLinked<T>>,
+}
+
+impl<T> Deref for SharedReadGuard<'_, T>
+where
+    T: Serialize,
+{
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.guard.readonly()
+    }
+}
+
+impl<T> SharedFileLinked<T>
+where
+    T: Serialize + Send + Sync + 'static,
+{
+    /// Wraps `linked` so it can be shared.
+    pub fn new(linked: FileLinked<T>) -> Self {
+        SharedFileLinked {
+            inner: Arc::new(RwLock::new(linked)),
+        }
+    }
+
+    /// Returns a read only reference to the underlying data, see [`FileLinked::readonly`].
+    pub fn readonly(&self) -> SharedReadGuard<'_, T> {
+        // Reading is still safe after a mutation panicked, the data just may not have been written
+        SharedReadGuard {
+            guard: self.inner.read().unwrap_or_else(PoisonError::into_inner),
+        }
+    }
+
+    /// Returns the path of the file the object is written to, see [`FileLinked::path`].
+    pub fn path(&self) -> Option<PathBuf> {
+        self.readonly().guard.path().map(|p| p.to_path_buf())
+    }
+
+    /// Returns when the object was last successfully written to its file, see [`FileLinked::last_written`].
+    pub fn last_written(&self) -> Option<SystemTime> {
+        self.readonly().guard.last_written()
+    }
+
+    /// Modifies the data while holding the lock and writes it to the file, see [`FileLinked::mutate`].
+    pub fn mutate<U, F: FnOnce(&mut T) -> U>(&self, op: F) -> Result<U, Error> {
+        let mut linked = self
+            .inner
+            .write()
+            .map_err(|_| anyhow!("Unable to mutate shared object, a previous mutation panicked"))?;
+
+        linked.mutate(op)
+    }
+
+    /// Replaces the data while holding the lock and writes it to the file, see [`FileLinked::replace`].
+    pub fn replace(&self, val: T) -> Result<(), Error> {
+        let mut linked = self.inner.write().map_err(|_| {
+            anyhow!("Unable to replace shared object, a previous mutation panicked")
+        })?;
+
+        linked.replace(val)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{fs, path::Path, thread};
+
+    #[test]
+    fn test_concurrent_mutate() -> Result<(), Error> {
+        let path = Path::new("test_concurrent_mutate");
+
+        let result = (|| {
+            let shared = SharedFileLinked::new(FileLinked::new(0u64, path)?);
+            assert_eq!(shared.path(), Some(path.to_path_buf()));
+
+            let handles = (0..8)
+                .map(|_| {
+                    let shared = shared.clone();
+                    thread::spawn(move || -> Result<(), Error> {
+                        for _ in 0..25 {
+                            shared.mutate(|v| *v += 1)?;
+                        }
+
+                        Ok(())
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            for handle in handles {
+                handle.join().expect("Thread panicked")?;
+            }
+
+            assert_eq!(*shared.readonly(), 200);
+            drop(shared);
+
+            assert_eq!(*FileLinked::<u64>::from_file(path)?.readonly(), 200);
+
+            Ok(())
+        })();
+
+        fs::remove_file(path)?;
+        result
+    }
+
+    #[test]
+    fn test_poisoned() -> Result<(), Error> {
+        let shared = SharedFileLinked::new(FileLinked::in_memory(vec![1]));
+
+        let panicking = shared.clone();
+        let _ = thread::spawn(move || panicking.mutate(|_| panic!("Mutation failed"))).join();
+
+        assert_eq!(*shared.readonly(), vec![1]);
+        assert!(shared.mutate(|v| v.push(2)).is_err());
+        assert!(shared.replace(vec![2]).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,88 @@
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use gemla::{
+    core::{
+        genetic_node::{GeneticNode, GeneticNodeContext},
+        Gemla, GemlaConfig,
+    },
+    error::Error,
+};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+struct BenchState {
+    pub population: Vec<u64>,
+}
+
+impl GeneticNode for BenchState {
+    fn initialize(_context: GeneticNodeContext) -> Result<Box<Self>, Error> {
+        Ok(Box::new(BenchState {
+            population: (0..100).collect(),
+        }))
+    }
+
+    fn simulate(&mut self, _context: GeneticNodeContext) -> Result<(), Error> {
+        self.population.iter_mut().for_each(|p| *p += 1);
+        Ok(())
+    }
+
+    fn mutate(&mut self, _context: GeneticNodeContext) -> Result<(), Error> {
+        self.population.reverse();
+        Ok(())
+    }
+
+    fn fitness(&self) -> f64 {
+        self.population.iter().sum::<u64>() as f64
+    }
+
+    fn population_size(&self) -> usize {
+        self.population.len()
+    }
+
+    fn merge(left: &BenchState, right: &BenchState) -> Result<Box<BenchState>, Error> {
+        Ok(Box::new(if left.fitness() > right.fitness() {
+            left.clone()
+        } else {
+            right.clone()
+        }))
+    }
+}
+
+const STEPS: u64 = 4;
+
+fn config() -> GemlaConfig {
+    GemlaConfig {
+        generations_per_node: 5,
+        overwrite: true,
+        ..GemlaConfig::default()
+    }
+}
+
+fn simulate(c: &mut Criterion) {
+    let mut group = c.benchmark_group("simulate");
+
+    group.bench_function("in_memory", |b| {
+        b.iter_batched(
+            || Gemla::<BenchState>::in_memory(config()).unwrap(),
+            |mut gemla| smol::block_on(gemla.simulate(STEPS)).unwrap(),
+            BatchSize::SmallInput,
+        )
+    });
+
+    // Only one simulation can be linked to the file at a time
+    let path = PathBuf::from("bench_simulate");
+    group.bench_function("file_linked", |b| {
+        b.iter_batched(
+            || Gemla::<BenchState>::new(&path, config()).unwrap(),
+            |mut gemla| smol::block_on(gemla.simulate(STEPS)).unwrap(),
+            BatchSize::PerIteration,
+        )
+    });
+
+    group.finish();
+
+    std::fs::remove_file(&path).expect("Unable to remove file");
+}
+
+criterion_group!(benches, simulate);
+criterion_main!(benches);
@@ -4,26 +4,66 @@ extern crate gemla;
 #[macro_use]
 extern crate log;
 
+#[cfg(not(feature = "init-tracing"))]
+mod logging;
 mod test_state;
 
 use anyhow::anyhow;
-use clap::App;
+use clap::{App, ArgMatches};
 use easy_parallel::Parallel;
+use file_linked::DataFormat;
 use gemla::{
-    constants::args::FILE,
-    core::{Gemla, GemlaConfig},
+    constants::args::{
+        CONFIG, DRY_RUN, EXPORT, FILE, FORMAT, INSPECT, LOG_FILE, LOG_LEVEL, MAX_HEIGHTS,
+        MAX_WALL_TIME, METRICS_ADDR, NODE, OUT, QUIET, RUN, STATUS, STATUS_INTERVAL, STEPS,
+        UNTIL_COMPLETE,
+    },
+    core::{
+        genetic_node::GeneticNodeWrapper, ExportFormat, Gemla, GemlaConfig, RunBudget,
+        SimulationReport,
+    },
     error::{log_error, Error},
+    tree::Tree,
 };
 use smol::{channel, channel::RecvError, future, Executor};
-use std::{path::PathBuf, time::Instant};
+use std::{
+    io::{self, IsTerminal, Write},
+    path::{Path, PathBuf},
+    thread,
+    time::{Duration, Instant},
+};
 use test_state::TestState;
 
 /// Runs a simluation of a genetic algorithm against a dataset.
 ///
+/// The `run` subcommand processes the simulation, while `status`, `export` and `inspect` only read the state file.
 /// Use the -h, --h, or --help flag to see usage syntax.
-/// TODO
 fn main() -> anyhow::Result<()> {
-    env_logger::init();
+    // Command line arguments are parsed with the clap crate. And this program uses
+    // the yaml method with clap.
+    let yaml = load_yaml!("../../cli.yml");
+    let matches = App::from_yaml(yaml).get_matches();
+
+    // Global arguments are only propagated down to the subcommand that was used
+    let (_, sub) = matches.subcommand();
+    let global = |name| {
+        sub.and_then(|m| m.value_of(name))
+            .or(matches.value_of(name))
+    };
+
+    #[cfg(feature = "init-tracing")]
+    {
+        // The tracing subscriber is only configured through RUST_LOG
+        if global(LOG_FILE).is_some() || global(LOG_LEVEL).is_some() {
+            return Err(anyhow!(
+                "--log-file and --log-level aren't supported with the init-tracing feature, set RUST_LOG instead"
+            ));
+        }
+
+        gemla::init_tracing();
+    }
+    #[cfg(not(feature = "init-tracing"))]
+    logging::init(global(LOG_FILE).map(Path::new), global(LOG_LEVEL))?;
     info!("Starting");
 
     let now = Instant::now();
@@ -42,26 +82,97 @@ fn main() -> anyhow::Result<()> {
             smol::block_on(async {
                 drop(signal);
 
-                // Command line arguments are parsed with the clap crate. And this program uses
-                // the yaml method with clap.
-                let yaml = load_yaml!("../../cli.yml");
-                let matches = App::from_yaml(yaml).get_matches();
+                match matches.subcommand() {
+                    (RUN, Some(m)) => {
+                        let file = required_path(m, FILE)?;
 
-                // Checking that the first argument <FILE> is a valid file
-                if let Some(file_path) = matches.value_of(FILE) {
-                    let mut gemla = log_error(Gemla::<TestState>::new(
-                        &PathBuf::from(file_path),
-                        GemlaConfig {
-                            generations_per_node: 3,
-                            overwrite: true,
-                        },
-                    ))?;
+                        // A config file, when given, replaces the compiled defaults entirely
+                        let config = match m.value_of(CONFIG) {
+                            Some(config_path) => {
+                                log_error(GemlaConfig::from_path(&PathBuf::from(config_path)))?
+                            }
+                            None => GemlaConfig {
+                                generations_per_node: 3,
+                                overwrite: true,
+                                ..GemlaConfig::default()
+                            },
+                        };
 
-                    log_error(gemla.simulate(3).await)?;
+                        let steps = value_t!(m, STEPS, u64).map_err(|e| {
+                            Error::Other(anyhow!("Invalid argument for STEPS: {}", e))
+                        })?;
 
-                    Ok(())
-                } else {
-                    Err(Error::Other(anyhow!("Invalid argument for FILE")))
+                        if m.is_present(DRY_RUN) {
+                            // Existing simulations are planned from rather than overwritten
+                            let gemla = log_error(Gemla::<TestState>::new(
+                                &file,
+                                GemlaConfig {
+                                    overwrite: false,
+                                    ..config
+                                },
+                            ))?;
+
+                            println!("{}", gemla.plan(steps));
+
+                            return Ok(());
+                        }
+
+                        let progress = if m.is_present(QUIET) {
+                            None
+                        } else {
+                            Some(ProgressDisplay::new(
+                                io::stdout().is_terminal(),
+                                parse_duration(m, STATUS_INTERVAL)?.unwrap_or_default(),
+                            ))
+                        };
+
+                        let summary = log_error(
+                            run(
+                                &file,
+                                config,
+                                steps,
+                                run_limits(m)?,
+                                progress,
+                                m.value_of(METRICS_ADDR),
+                            )
+                            .await,
+                        )?;
+                        println!("{}", summary);
+
+                        Ok(())
+                    }
+                    (STATUS, Some(m)) => {
+                        println!("{}", log_error(status(&required_path(m, FILE)?))?);
+
+                        Ok(())
+                    }
+                    (EXPORT, Some(m)) => {
+                        let format = match m.value_of(FORMAT) {
+                            Some("csv") => ExportFormat::Csv,
+                            Some("dot") => ExportFormat::Dot,
+                            _ => ExportFormat::Json,
+                        };
+
+                        log_error(export(
+                            &required_path(m, FILE)?,
+                            &required_path(m, OUT)?,
+                            format,
+                        ))
+                    }
+                    (INSPECT, Some(m)) => {
+                        let format = match m.value_of(FORMAT) {
+                            Some("json") => DataFormat::Json { pretty: false },
+                            _ => DataFormat::Bincode,
+                        };
+
+                        println!(
+                            "{}",
+                            log_error(inspect(&required_path(m, FILE)?, format, m.value_of(NODE)))?
+                        );
+
+                        Ok(())
+                    }
+                    _ => Err(Error::Other(anyhow!("Invalid subcommand"))),
                 }
             })
         });
@@ -72,3 +183,571 @@ fn main() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+fn required_path(matches: &ArgMatches, name: &str) -> Result<PathBuf, Error> {
+    matches
+        .value_of(name)
+        .map(PathBuf::from)
+        .ok_or_else(|| Error::Other(anyhow!("Invalid argument for {}", name)))
+}
+
+/// Limits on how long the run subcommand keeps growing and processing a simulation. Limits are checked between each
+/// time the tree is grown, and a run without any limits grows and processes the tree once.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct RunLimits {
+    /// Stops once this much time has passed, any nodes being processed are finished first
+    max_wall_time: Option<Duration>,
+    /// Stops once the tree reaches this height
+    max_heights: Option<u64>,
+    /// Stops once the tree reaches the [`GemlaConfig::max_height`] of the simulation and has been processed
+    until_complete: bool,
+}
+
+/// Parses an argument such as 2h30m into a [`Duration`].
+fn parse_duration(matches: &ArgMatches, name: &str) -> Result<Option<Duration>, Error> {
+    match matches.value_of(name) {
+        Some(t) => Ok(Some(humantime::parse_duration(t).map_err(|e| {
+            Error::Other(anyhow!("Invalid argument for {} {}: {}", name, t, e))
+        })?)),
+        None => Ok(None),
+    }
+}
+
+fn run_limits(matches: &ArgMatches) -> Result<RunLimits, Error> {
+    let max_wall_time = parse_duration(matches, MAX_WALL_TIME)?;
+
+    let max_heights = match matches.value_of(MAX_HEIGHTS) {
+        Some(_) => Some(
+            value_t!(matches, MAX_HEIGHTS, u64)
+                .map_err(|e| Error::Other(anyhow!("Invalid argument for MAX_HEIGHTS: {}", e)))?,
+        ),
+        None => None,
+    };
+
+    Ok(RunLimits {
+        max_wall_time,
+        max_heights,
+        until_complete: matches.is_present(UNTIL_COMPLETE),
+    })
+}
+
+/// Shows the progress of a run. On a terminal a single line is redrawn for every report, otherwise a line is printed
+/// at most once every `interval` so logs aren't flooded.
+struct ProgressDisplay {
+    terminal: bool,
+    interval: Duration,
+    last_printed: Option<Instant>,
+}
+
+impl ProgressDisplay {
+    fn new(terminal: bool, interval: Duration) -> ProgressDisplay {
+        ProgressDisplay {
+            terminal,
+            interval,
+            last_printed: None,
+        }
+    }
+
+    /// Returns the text to write for `report`, if any, when the run has been going for `elapsed`.
+    fn render(
+        &mut self,
+        report: &SimulationReport,
+        elapsed: Duration,
+        now: Instant,
+    ) -> Option<String> {
+        let line = progress_line(report, elapsed);
+
+        if self.terminal {
+            // Returns to the start of the line and clears whatever is left of the previous line
+            return Some(format!("\r{}\x1b[K", line));
+        }
+
+        match self.last_printed {
+            Some(last) if now.duration_since(last) < self.interval => None,
+            _ => {
+                self.last_printed = Some(now);
+                Some(format!("{}\n", line))
+            }
+        }
+    }
+}
+
+fn progress_line(report: &SimulationReport, elapsed: Duration) -> String {
+    format!(
+        "{}/{} nodes finished, height {}, best fitness {}, elapsed {}",
+        report.finished,
+        report.nodes,
+        report.height,
+        report
+            .best_fitness
+            .map(|b| b.to_string())
+            .unwrap_or_else(|| "-".to_string()),
+        humantime::format_duration(Duration::from_secs(elapsed.as_secs()))
+    )
+}
+
+/// Repeatedly grows the simulation in `file` by `steps` levels and processes it until one of the `limits` is reached,
+/// then shuts the simulation down and returns a summary of the run. Progress is shown on stdout with `progress` while
+/// the simulation runs, and its metrics are served at `metrics_addr` when one is given.
+async fn run(
+    file: &Path,
+    config: GemlaConfig,
+    steps: u64,
+    limits: RunLimits,
+    progress: Option<ProgressDisplay>,
+    metrics_addr: Option<&str>,
+) -> Result<String, Error> {
+    let start = Instant::now();
+    let mut gemla = Gemla::<TestState>::new(file, config)?;
+
+    if let Some(addr) = metrics_addr {
+        serve_metrics(&gemla, addr)?;
+    }
+
+    // Progress is written from its own thread since processing nodes doesn't yield to other tasks
+    let display = progress.map(|mut display| {
+        let receiver = gemla.subscribe_progress();
+
+        thread::spawn(move || {
+            let mut stdout = io::stdout();
+            while let Ok(report) = smol::block_on(receiver.recv()) {
+                if let Some(output) = display.render(&report, start.elapsed(), Instant::now()) {
+                    let _ = write!(stdout, "{}", output);
+                    let _ = stdout.flush();
+                }
+            }
+
+            if display.terminal {
+                println!();
+            }
+        })
+    });
+
+    if limits.until_complete && gemla.effective_config().max_height.is_none() {
+        return Err(Error::Other(anyhow!(
+            "Running until complete requires max_height to be set in the config"
+        )));
+    }
+
+    let reason = loop {
+        let height = gemla.report().height as u64;
+
+        let mut levels = steps;
+        if let Some(max) = limits.max_heights {
+            if height >= max {
+                break format!("the tree reached a height of {}", max);
+            }
+            levels = levels.min(max - height);
+        }
+
+        gemla.grow(levels)?;
+
+        // Processing stops scheduling nodes once the wall time runs out
+        let budget = match limits.max_wall_time {
+            Some(limit) => RunBudget::Duration(limit.saturating_sub(start.elapsed())),
+            None => RunBudget::Unbounded,
+        };
+        gemla.run(budget).await?;
+
+        let report = gemla.report();
+        if let Some(limit) = limits.max_wall_time.filter(|l| start.elapsed() >= *l) {
+            break format!("the max wall time of {:?} ran out", limit);
+        }
+        // The tree stops growing once it reaches its max height, at which point it has been completed
+        if limits.until_complete && report.completed && report.height as u64 == height {
+            break "the tree was completed".to_string();
+        }
+        if limits == RunLimits::default() {
+            break "the tree was processed".to_string();
+        }
+    };
+
+    let report = gemla.report();
+    gemla.shutdown().await?;
+
+    if let Some(display) = display {
+        if display.join().is_err() {
+            warn!("Progress display panicked");
+        }
+    }
+
+    Ok(format!(
+        "Stopped after {:?} because {}: height {}, {}/{} nodes finished, {} failed",
+        start.elapsed(),
+        reason,
+        report.height,
+        report.finished,
+        report.nodes,
+        report.failed
+    ))
+}
+
+#[cfg(feature = "metrics")]
+fn serve_metrics(gemla: &Gemla<TestState>, addr: &str) -> Result<(), Error> {
+    gemla.serve_metrics(addr).map(|_| ())
+}
+
+#[cfg(not(feature = "metrics"))]
+fn serve_metrics(_gemla: &Gemla<TestState>, addr: &str) -> Result<(), Error> {
+    Err(Error::Other(anyhow!(
+        "Unable to serve metrics at {}, gemla was built without the metrics feature",
+        addr
+    )))
+}
+
+/// Summarizes the simulation in `file` and outlines its tree, without modifying the file.
+fn status(file: &Path) -> Result<String, Error> {
+    Ok(summary(&Gemla::<TestState>::open_read_only(file)?))
+}
+
+fn summary(gemla: &Gemla<TestState>) -> String {
+    let mut summary = gemla.report().to_string();
+    let tree = gemla.print_tree();
+    if !tree.is_empty() {
+        summary.push('\n');
+        summary.push_str(&tree);
+    }
+
+    summary
+}
+
+/// Writes every node of the simulation in `file` to `out`, without modifying `file`.
+fn export(file: &Path, out: &Path, format: ExportFormat) -> Result<(), Error> {
+    Gemla::<TestState>::open_read_only(file)?.export(out, format)
+}
+
+/// Describes the node of the simulation in `file` whose id starts with `prefix`, or summarizes the whole simulation in
+/// the same way as [`status`] without a prefix. The file is read in the given `format` and isn't modified. The prefix
+/// has to match exactly one node.
+fn inspect(file: &Path, format: DataFormat, prefix: Option<&str>) -> Result<String, Error> {
+    let gemla = Gemla::<TestState>::open_read_only_with_format(file, format)?;
+    let prefix = match prefix {
+        Some(prefix) => prefix,
+        None => return Ok(summary(&gemla)),
+    };
+
+    let mut nodes = vec![];
+    if let Some(t) = gemla.tree_ref() {
+        find_nodes(t, prefix, "root".to_string(), &mut nodes);
+    }
+
+    match nodes.as_slice() {
+        [(path, node)] => Ok(format!(
+            "id: {}\npath: {}\nstate: {:?}\ngeneration: {}/{}\nheight: {}\ngroup: {}\nfailure: {:?}\nnode: {:#?}",
+            node.id(),
+            path,
+            node.state(),
+            node.generation(),
+            node.max_generations(),
+            node.height(),
+            node.is_group(),
+            node.failure(),
+            node.as_ref()
+        )),
+        [] => Err(Error::Other(anyhow!(
+            "No node in {} has an id starting with {}",
+            file.display(),
+            prefix
+        ))),
+        _ => Err(Error::Other(anyhow!(
+            "{} nodes have an id starting with {}, a longer prefix is needed",
+            nodes.len(),
+            prefix
+        ))),
+    }
+}
+
+fn find_nodes<'t>(
+    tree: &'t Tree<GeneticNodeWrapper<TestState>>,
+    prefix: &str,
+    path: String,
+    nodes: &mut Vec<(String, &'t GeneticNodeWrapper<TestState>)>,
+) {
+    if tree.val.id().to_string().starts_with(prefix) {
+        nodes.push((path.clone(), &tree.val));
+    }
+
+    if let Some(l) = &tree.left {
+        find_nodes(l, prefix, format!("{}/L", path), nodes);
+    }
+    if let Some(r) = &tree.right {
+        find_nodes(r, prefix, format!("{}/R", path), nodes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use file_linked::FileLinked;
+    use std::fs;
+
+    struct CleanUp {
+        path: PathBuf,
+    }
+
+    impl CleanUp {
+        fn new(path: &Path) -> CleanUp {
+            CleanUp {
+                path: path.to_path_buf(),
+            }
+        }
+
+        pub fn run<F: FnOnce(&Path) -> Result<(), Error>>(&self, op: F) -> Result<(), Error> {
+            op(&self.path)
+        }
+    }
+
+    impl Drop for CleanUp {
+        fn drop(&mut self) {
+            if self.path.exists() {
+                fs::remove_file(&self.path).expect("Unable to remove file");
+            }
+        }
+    }
+
+    fn config() -> GemlaConfig {
+        GemlaConfig {
+            overwrite: true,
+            max_height: Some(2),
+            ..GemlaConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_run() -> Result<(), Error> {
+        let path = PathBuf::from("test_bin_run");
+        CleanUp::new(&path).run(|p| {
+            smol::block_on(run(p, config(), 1, RunLimits::default(), None, None))?;
+            assert_eq!(Gemla::<TestState>::open_read_only(p)?.report().height, 1);
+
+            // The tree is grown one level at a time until it reaches its max height
+            let until_complete = RunLimits {
+                until_complete: true,
+                ..RunLimits::default()
+            };
+            let summary = smol::block_on(run(p, config(), 1, until_complete, None, None))?;
+            assert!(
+                summary.contains("because the tree was completed: height 2, 3/3 nodes finished")
+            );
+
+            let result = smol::block_on(run(
+                p,
+                GemlaConfig {
+                    max_height: None,
+                    ..config()
+                },
+                1,
+                until_complete,
+                None,
+                None,
+            ));
+            assert!(result.is_err());
+
+            let max_heights = RunLimits {
+                max_heights: Some(3),
+                ..RunLimits::default()
+            };
+            let summary = smol::block_on(run(
+                p,
+                GemlaConfig {
+                    max_height: None,
+                    ..config()
+                },
+                2,
+                max_heights,
+                None,
+                None,
+            ))?;
+            assert!(summary.contains("because the tree reached a height of 3"));
+            assert_eq!(Gemla::<TestState>::open_read_only(p)?.report().height, 3);
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_run_max_wall_time() -> Result<(), Error> {
+        let path = PathBuf::from("test_bin_run_max_wall_time");
+        CleanUp::new(&path).run(|p| {
+            let limits = RunLimits {
+                max_wall_time: Some(Duration::from_secs(1)),
+                ..RunLimits::default()
+            };
+
+            let start = Instant::now();
+            let summary = smol::block_on(run(
+                p,
+                GemlaConfig {
+                    max_height: None,
+                    ..config()
+                },
+                1,
+                limits,
+                None,
+                None,
+            ))?;
+            assert!(start.elapsed() < Duration::from_secs(30));
+            assert!(summary.contains("because the max wall time of 1s ran out"));
+
+            // The state file was flushed and no interrupted write was left behind
+            assert!(Gemla::<TestState>::open_read_only(p)?.report().height > 1);
+            assert!(!PathBuf::from(".temptest_bin_run_max_wall_time").exists());
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_progress_display() {
+        let report = SimulationReport {
+            height: 3,
+            nodes: 7,
+            finished: 2,
+            failed: 0,
+            completed: false,
+            best_fitness: Some(41.5),
+        };
+        let elapsed = Duration::from_millis(61_500);
+        let line = "2/7 nodes finished, height 3, best fitness 41.5, elapsed 1m 1s";
+        assert_eq!(progress_line(&report, elapsed), line);
+
+        let empty = SimulationReport {
+            best_fitness: None,
+            ..report.clone()
+        };
+        assert!(progress_line(&empty, elapsed).contains("best fitness -"));
+
+        // Without a terminal lines are only printed once the interval has passed
+        let start = Instant::now();
+        let mut display = ProgressDisplay::new(false, Duration::from_secs(30));
+        assert_eq!(
+            display.render(&report, elapsed, start),
+            Some(format!("{}\n", line))
+        );
+        assert_eq!(
+            display.render(&report, elapsed, start + Duration::from_secs(10)),
+            None
+        );
+        assert!(display
+            .render(&report, elapsed, start + Duration::from_secs(30))
+            .is_some());
+
+        let mut display = ProgressDisplay::new(true, Duration::from_secs(30));
+        for _ in 0..2 {
+            assert_eq!(
+                display.render(&report, elapsed, start),
+                Some(format!("\r{}\x1b[K", line))
+            );
+        }
+    }
+
+    #[test]
+    fn test_run_limits() -> Result<(), Error> {
+        let yaml = load_yaml!("../../cli.yml");
+        let matches = App::from_yaml(yaml).get_matches_from(vec![
+            "gemla",
+            "run",
+            "--file",
+            "test",
+            "--max-wall-time",
+            "2h30m",
+            "--max-heights",
+            "4",
+            "--exit-when-complete",
+        ]);
+
+        assert_eq!(
+            run_limits(matches.subcommand_matches(RUN).unwrap())?,
+            RunLimits {
+                max_wall_time: Some(Duration::from_secs(9000)),
+                max_heights: Some(4),
+                until_complete: true,
+            }
+        );
+
+        let matches = App::from_yaml(yaml).get_matches_from_safe(vec![
+            "gemla",
+            "run",
+            "--file",
+            "test",
+            "--max-wall-time",
+            "soon",
+        ]);
+        assert!(run_limits(matches.unwrap().subcommand_matches(RUN).unwrap()).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_only_subcommands() -> Result<(), Error> {
+        let path = PathBuf::from("test_bin_read_only");
+        CleanUp::new(&path).run(|p| {
+            smol::block_on(run(p, config(), 2, RunLimits::default(), None, None))?;
+            let written = fs::read(p)?;
+
+            let output = status(p)?;
+            assert!(
+                output.starts_with("Height: 2\nNodes: 3/3 finished\nFailed: 0\nCompleted: true\n")
+            );
+            assert_eq!(output.lines().count(), 8);
+
+            for (name, format) in [
+                ("test_bin_export.json", ExportFormat::Json),
+                ("test_bin_export.csv", ExportFormat::Csv),
+                ("test_bin_export.dot", ExportFormat::Dot),
+            ] {
+                CleanUp::new(&PathBuf::from(name)).run(|out| {
+                    export(p, out, format)?;
+                    assert!(!fs::read_to_string(out)?.is_empty());
+
+                    Ok(())
+                })?;
+            }
+
+            let gemla = Gemla::<TestState>::open_read_only(p)?;
+            let left = gemla.tree_ref().unwrap().left.as_ref().unwrap();
+            let id = left.val.id().to_string();
+
+            let output = inspect(p, DataFormat::Bincode, Some(&id[..8]))?;
+            assert!(output.starts_with(&format!("id: {}\npath: root/L\nstate: Finish\n", id)));
+            assert!(output.contains("population"));
+
+            // Prefixes must match exactly one node
+            assert!(inspect(p, DataFormat::Bincode, Some("")).is_err());
+            assert!(inspect(p, DataFormat::Bincode, Some("not an id")).is_err());
+
+            // None of the subcommands write to the state file
+            assert_eq!(fs::read(p)?, written);
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_inspect_formats() -> Result<(), Error> {
+        let path = PathBuf::from("test_bin_inspect");
+        let json = PathBuf::from("test_bin_inspect.json");
+        CleanUp::new(&path).run(|p| {
+            smol::block_on(run(p, config(), 2, RunLimits::default(), None, None))?;
+
+            let output = inspect(p, DataFormat::Bincode, None)?;
+            assert_eq!(output, status(p)?);
+            assert!(output.starts_with("Height: 2\nNodes: 3/3 finished\n"));
+            assert_eq!(output.matches("Finish 1/1").count(), 3);
+
+            // The same checkpoint written as JSON gives the same summary
+            CleanUp::new(&json).run(|j| {
+                let data = Gemla::<TestState>::open_read_only(p)?
+                    .data
+                    .readonly()
+                    .clone();
+                FileLinked::new_with_format(data, j, DataFormat::Json { pretty: true })?.close()?;
+
+                assert_eq!(inspect(j, DataFormat::Json { pretty: true }, None)?, output);
+                assert!(inspect(j, DataFormat::Bincode, None).is_err());
+
+                Ok(())
+            })
+        })
+    }
+}
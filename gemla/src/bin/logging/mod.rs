@@ -0,0 +1,210 @@
+use anyhow::anyhow;
+use gemla::error::Error;
+use log::LevelFilter;
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    panic,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, PoisonError},
+    time::SystemTime,
+};
+
+/// The size a log file can grow to before it is rotated.
+const MAX_LOG_SIZE: u64 = 20 * 1024 * 1024;
+/// The number of log files kept, including the one being written to.
+const MAX_LOG_FILES: usize = 5;
+
+/// Sets up logging for the binary. Records are written to stderr, and to `log_file` as well when one is given, which
+/// is rotated once it reaches [`MAX_LOG_SIZE`]. Panic messages are also written to `log_file`. `level` overrides the
+/// default level set with the `RUST_LOG` environment variable.
+pub fn init(log_file: Option<&Path>, level: Option<&str>) -> Result<(), Error> {
+    let mut builder = env_logger::Builder::from_default_env();
+
+    if let Some(level) = level {
+        let level = level.parse::<LevelFilter>().map_err(|e| {
+            Error::Other(anyhow!("Invalid argument for LOG_LEVEL {}: {}", level, e))
+        })?;
+        builder.filter_level(level);
+    }
+
+    if let Some(log_file) = log_file {
+        let file = Arc::new(Mutex::new(RotatingFile::new(
+            log_file,
+            MAX_LOG_SIZE,
+            MAX_LOG_FILES,
+        )?));
+
+        builder.target(env_logger::Target::Pipe(Box::new(LogWriter {
+            file: Arc::clone(&file),
+        })));
+
+        // The default hook still prints the message to stderr afterwards
+        let default_hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            let mut file = file.lock().unwrap_or_else(PoisonError::into_inner);
+            let _ = writeln!(
+                file,
+                "[{}] {}",
+                humantime::format_rfc3339_seconds(SystemTime::now()),
+                info
+            );
+            let _ = file.flush();
+
+            default_hook(info);
+        }));
+    }
+
+    builder
+        .try_init()
+        .map_err(|e| Error::Other(anyhow!("Unable to initialize logging: {}", e)))
+}
+
+/// Writes everything to both stderr and a log file.
+struct LogWriter {
+    file: Arc<Mutex<RotatingFile>>,
+}
+
+impl Write for LogWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        io::stderr().write_all(buf)?;
+        self.file
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .write_all(buf)?;
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        io::stderr().flush()?;
+        self.file
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .flush()
+    }
+}
+
+/// A file that is rotated once it reaches `max_size` bytes. The previous files are renamed `<path>.1`, `<path>.2` and
+/// so on, with the oldest removed so at most `max_files` files are kept.
+struct RotatingFile {
+    path: PathBuf,
+    max_size: u64,
+    max_files: usize,
+    file: File,
+    size: u64,
+}
+
+impl RotatingFile {
+    fn new(path: &Path, max_size: u64, max_files: usize) -> Result<RotatingFile, Error> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| {
+                Error::Other(anyhow!("Unable to open log file {}: {}", path.display(), e))
+            })?;
+        let size = file.metadata()?.len();
+
+        Ok(RotatingFile {
+            path: path.to_path_buf(),
+            max_size,
+            max_files: max_files.max(1),
+            file,
+            size,
+        })
+    }
+
+    fn rotated_path(&self, index: usize) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{}", index));
+        PathBuf::from(path)
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+
+        for index in (1..self.max_files).rev() {
+            let from = if index == 1 {
+                self.path.clone()
+            } else {
+                self.rotated_path(index - 1)
+            };
+
+            if from.exists() {
+                fs::rename(from, self.rotated_path(index))?;
+            }
+        }
+
+        self.file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(&self.path)?;
+        self.size = 0;
+
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // Records are never split between files, so a record larger than max_size gets a file to itself
+        if self.size > 0 && self.size + buf.len() as u64 > self.max_size {
+            self.rotate()?;
+        }
+
+        let written = self.file.write(buf)?;
+        self.size += written as u64;
+
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rotating_file() -> Result<(), Error> {
+        let dir = PathBuf::from("test_rotating_file");
+        fs::create_dir_all(&dir)?;
+
+        let result = (|| {
+            let path = dir.join("gemla.log");
+            let mut file = RotatingFile::new(&path, 100, 3)?;
+
+            // 50 lines of 20 bytes fill ten files, only the last three are kept
+            for i in 0..50 {
+                file.write_all(format!("synthetic line {:04}\n", i).as_bytes())?;
+            }
+            file.flush()?;
+
+            assert_eq!(fs::read_dir(&dir)?.count(), 3);
+            assert!(file.rotated_path(2).exists());
+            assert!(!file.rotated_path(3).exists());
+            for p in [path.clone(), file.rotated_path(1), file.rotated_path(2)] {
+                assert_eq!(fs::metadata(p)?.len(), 100);
+            }
+            assert!(fs::read_to_string(&path)?.ends_with("synthetic line 0049\n"));
+            assert!(fs::read_to_string(file.rotated_path(2))?.starts_with("synthetic line 0035\n"));
+
+            // Reopening appends to the current file, which is full, so the next line starts a new one
+            drop(file);
+            let mut file = RotatingFile::new(&path, 100, 3)?;
+            file.write_all(b"reopened\n")?;
+            file.flush()?;
+            assert_eq!(fs::read_to_string(&path)?, "reopened\n");
+            assert!(fs::read_to_string(file.rotated_path(1))?.ends_with("synthetic line 0049\n"));
+
+            Ok(())
+        })();
+
+        fs::remove_dir_all(&dir)?;
+        result
+    }
+}
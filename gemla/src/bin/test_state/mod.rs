@@ -1,4 +1,8 @@
-use gemla::{core::genetic_node::GeneticNode, error::Error};
+use anyhow::anyhow;
+use gemla::{
+    core::genetic_node::{GeneticNode, GeneticNodeContext, MergeContext},
+    error::Error,
+};
 use rand::prelude::*;
 use serde::{Deserialize, Serialize};
 
@@ -10,30 +14,10 @@ pub struct TestState {
     pub population: Vec<i64>,
 }
 
-impl GeneticNode for TestState {
-    fn initialize() -> Result<Box<Self>, Error> {
-        let mut population: Vec<i64> = vec![];
-
-        for _ in 0..POPULATION_SIZE {
-            population.push(thread_rng().gen_range(0..100))
-        }
-
-        Ok(Box::new(TestState { population }))
-    }
-
-    fn simulate(&mut self) -> Result<(), Error> {
-        let mut rng = thread_rng();
-
-        self.population = self
-            .population
-            .iter()
-            .map(|p| p.saturating_add(rng.gen_range(-1..2)))
-            .collect();
-
-        Ok(())
-    }
-
-    fn mutate(&mut self) -> Result<(), Error> {
+impl TestState {
+    /// Keeps the best members of the population and breeds new members from them until the population has `size`
+    /// members.
+    fn repopulate(&mut self, size: usize) {
         let mut rng = thread_rng();
 
         let mut v = self.population.clone();
@@ -44,7 +28,7 @@ impl GeneticNode for TestState {
         self.population = v[0..(POPULATION_REDUCTION_SIZE as usize)].to_vec();
 
         loop {
-            if self.population.len() as u64 >= POPULATION_SIZE {
+            if self.population.len() >= size {
                 break;
             }
 
@@ -67,35 +51,111 @@ impl GeneticNode for TestState {
 
             self.population.push(new_individual);
         }
+    }
+}
+
+impl GeneticNode for TestState {
+    fn initialize(_context: GeneticNodeContext) -> Result<Box<Self>, Error> {
+        let mut population: Vec<i64> = vec![];
+
+        for _ in 0..POPULATION_SIZE {
+            population.push(thread_rng().gen_range(0..100))
+        }
+
+        Ok(Box::new(TestState { population }))
+    }
+
+    fn simulate(&mut self, _context: GeneticNodeContext) -> Result<(), Error> {
+        let mut rng = thread_rng();
+
+        self.population = self
+            .population
+            .iter()
+            .map(|p| p.saturating_add(rng.gen_range(-1..2)))
+            .collect();
 
         Ok(())
     }
 
-    fn merge(left: &TestState, right: &TestState) -> Result<Box<TestState>, Error> {
-        let mut v = left.population.clone();
-        v.append(&mut right.population.clone());
+    fn mutate(&mut self, _context: GeneticNodeContext) -> Result<(), Error> {
+        // Populations grown by a merge keep their size
+        self.repopulate(self.population.len().max(POPULATION_SIZE as usize));
 
-        v.sort_by(|a, b| a.partial_cmp(b).unwrap());
-        v.reverse();
+        Ok(())
+    }
 
-        v = v[..(POPULATION_REDUCTION_SIZE as usize)].to_vec();
+    fn fitness(&self) -> f64 {
+        self.population.iter().max().copied().unwrap_or(0) as f64
+    }
+
+    fn population_size(&self) -> usize {
+        self.population.len()
+    }
+
+    fn merge(left: &TestState, right: &TestState) -> Result<Box<TestState>, Error> {
+        TestState::merge_with_context(
+            &[left, right],
+            MergeContext {
+                target_population_hint: None,
+                height: 0,
+                id: Default::default(),
+            },
+        )
+    }
 
-        let mut result = TestState { population: v };
+    fn merge_with_context(
+        nodes: &[&TestState],
+        context: MergeContext,
+    ) -> Result<Box<TestState>, Error> {
+        let size = context
+            .target_population_hint
+            .unwrap_or(POPULATION_SIZE as usize);
+
+        let mut result = TestState {
+            population: nodes
+                .iter()
+                .flat_map(|n| n.population.iter().copied())
+                .collect(),
+        };
 
-        result.mutate()?;
+        result.repopulate(size);
 
         Ok(Box::new(result))
     }
+
+    fn validate(&self) -> Result<(), Error> {
+        // Merges can grow the population, but breeding needs at least the members kept from the last generation
+        if (self.population.len() as u64) < POPULATION_REDUCTION_SIZE {
+            return Err(Error::Other(anyhow!(
+                "Population has {} members, expected at least {}",
+                self.population.len(),
+                POPULATION_REDUCTION_SIZE
+            )));
+        }
+
+        Ok(())
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use gemla::core::genetic_node::GeneticNode;
+    use gemla::core::genetic_node::{GeneticNode, UserData};
+    use uuid::Uuid;
+
+    fn context() -> GeneticNodeContext {
+        GeneticNodeContext {
+            generation: 1,
+            max_generations: 1,
+            id: Uuid::new_v4(),
+            height: 1,
+            user_data: UserData::default(),
+        }
+    }
 
     #[test]
     fn test_initialize() {
-        let state = TestState::initialize().unwrap();
+        let state = TestState::initialize(context()).unwrap();
 
         assert_eq!(state.population.len(), POPULATION_SIZE as usize);
     }
@@ -108,14 +168,14 @@ mod tests {
 
         let original_population = state.population.clone();
 
-        state.simulate().unwrap();
+        state.simulate(context()).unwrap();
         assert!(original_population
             .iter()
             .zip(state.population.iter())
             .all(|(&a, &b)| b >= a - 1 && b <= a + 2));
 
-        state.simulate().unwrap();
-        state.simulate().unwrap();
+        state.simulate(context()).unwrap();
+        state.simulate(context()).unwrap();
         assert!(original_population
             .iter()
             .zip(state.population.iter())
@@ -128,11 +188,31 @@ mod tests {
             population: vec![4, 3, 3],
         };
 
-        state.mutate().unwrap();
+        state.mutate(context()).unwrap();
 
         assert_eq!(state.population.len(), POPULATION_SIZE as usize);
     }
 
+    #[test]
+    fn test_fitness() {
+        let state = TestState {
+            population: vec![4, 9, 3],
+        };
+
+        assert_eq!(state.fitness(), 9.0);
+    }
+
+    #[test]
+    fn test_validate() {
+        let state = TestState::initialize(context()).unwrap();
+        assert!(state.validate().is_ok());
+
+        let state = TestState {
+            population: vec![4, 3],
+        };
+        assert!(state.validate().is_err());
+    }
+
     #[test]
     fn test_merge() {
         let state1 = TestState {
@@ -146,8 +226,32 @@ mod tests {
         let merged_state = TestState::merge(&state1, &state2).unwrap();
 
         assert_eq!(merged_state.population.len(), POPULATION_SIZE as usize);
-        assert!(merged_state.population.iter().any(|&x| x == 7));
-        assert!(merged_state.population.iter().any(|&x| x == 5));
-        assert!(merged_state.population.iter().any(|&x| x == 4));
+        assert!(merged_state.population.contains(&7));
+        assert!(merged_state.population.contains(&5));
+        assert!(merged_state.population.contains(&4));
+    }
+
+    #[test]
+    fn test_merge_with_context() {
+        let state1 = TestState {
+            population: vec![1, 2, 4, 5, 6],
+        };
+
+        let state2 = TestState {
+            population: vec![0, 1, 3, 7, 2],
+        };
+
+        let context = MergeContext {
+            target_population_hint: Some(8),
+            height: 2,
+            id: Uuid::new_v4(),
+        };
+        let merged_state = TestState::merge_with_context(&[&state1, &state2], context).unwrap();
+
+        assert_eq!(merged_state.population.len(), 8);
+        assert!(merged_state.population.contains(&7));
+        assert!(merged_state.population.contains(&6));
+        assert!(merged_state.population.contains(&5));
+        assert!(merged_state.validate().is_ok());
     }
 }
@@ -1,2 +1,39 @@
 /// Corresponds to the FILE command line argument used in accordance with the clap crate.
 pub const FILE: &str = "FILE";
+/// Corresponds to the CONFIG command line argument used in accordance with the clap crate.
+pub const CONFIG: &str = "CONFIG";
+/// Corresponds to the DRY_RUN command line argument used in accordance with the clap crate.
+pub const DRY_RUN: &str = "DRY_RUN";
+/// Corresponds to the STEPS command line argument used in accordance with the clap crate.
+pub const STEPS: &str = "STEPS";
+/// Corresponds to the UNTIL_COMPLETE command line argument used in accordance with the clap crate.
+pub const UNTIL_COMPLETE: &str = "UNTIL_COMPLETE";
+/// Corresponds to the MAX_WALL_TIME command line argument used in accordance with the clap crate.
+pub const MAX_WALL_TIME: &str = "MAX_WALL_TIME";
+/// Corresponds to the MAX_HEIGHTS command line argument used in accordance with the clap crate.
+pub const MAX_HEIGHTS: &str = "MAX_HEIGHTS";
+/// Corresponds to the QUIET command line argument used in accordance with the clap crate.
+pub const QUIET: &str = "QUIET";
+/// Corresponds to the STATUS_INTERVAL command line argument used in accordance with the clap crate.
+pub const STATUS_INTERVAL: &str = "STATUS_INTERVAL";
+/// Corresponds to the METRICS_ADDR command line argument used in accordance with the clap crate.
+pub const METRICS_ADDR: &str = "METRICS_ADDR";
+/// Corresponds to the OUT command line argument used in accordance with the clap crate.
+pub const OUT: &str = "OUT";
+/// Corresponds to the FORMAT command line argument used in accordance with the clap crate.
+pub const FORMAT: &str = "FORMAT";
+/// Corresponds to the NODE command line argument used in accordance with the clap crate.
+pub const NODE: &str = "NODE";
+/// Corresponds to the LOG_FILE command line argument used in accordance with the clap crate.
+pub const LOG_FILE: &str = "LOG_FILE";
+/// Corresponds to the LOG_LEVEL command line argument used in accordance with the clap crate.
+pub const LOG_LEVEL: &str = "LOG_LEVEL";
+
+/// Corresponds to the run subcommand used in accordance with the clap crate.
+pub const RUN: &str = "run";
+/// Corresponds to the status subcommand used in accordance with the clap crate.
+pub const STATUS: &str = "status";
+/// Corresponds to the export subcommand used in accordance with the clap crate.
+pub const EXPORT: &str = "export";
+/// Corresponds to the inspect subcommand used in accordance with the clap crate.
+pub const INSPECT: &str = "inspect";
@@ -1,17 +1,23 @@
-//! A trait used to interact with the internal state of nodes within the [`Bracket`]
+//! A trait used to interact with the internal state of nodes within the [`Gemla`] simulation tree
 //!
-//! [`Bracket`]: crate::bracket::Bracket
+//! [`Gemla`]: crate::core::Gemla
 
-use crate::error::Error;
+use crate::error::{Error, SerializableError};
 
-use anyhow::Context;
-use serde::{Deserialize, Serialize};
-use std::fmt::Debug;
+use anyhow::{anyhow, Context};
+use serde::{de::DeserializeOwned, Deserialize, Serialize, Serializer};
+use std::{
+    collections::BTreeMap,
+    fmt::Debug,
+    fs::File,
+    io::{BufReader, BufWriter, Write},
+    mem,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, PoisonError},
+};
 use uuid::Uuid;
 
 /// An enum used to control the state of a [`GeneticNode`]
-///
-/// [`GeneticNode`]: crate::bracket::genetic_node
 #[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Copy)]
 pub enum GeneticState {
     /// The node and it's data have not finished initializing
@@ -22,38 +28,335 @@ pub enum GeneticState {
     Mutate,
     /// The node has finished processing for a given number of iterations
     Finish,
+    /// The node is scoring the members of it's population after a simulation, between [`GeneticState::Simulate`] and
+    /// [`GeneticState::Mutate`]. Declared last so state files written before it was added can still be read.
+    Score,
+    /// A state defined by the node itself, entered through [`GeneticNode::next_state`] and processed by
+    /// [`GeneticNode::custom_step`]. Declared after [`GeneticState::Score`] for the same reason.
+    Custom(u8),
+}
+
+/// Information about where a node sits in the simulation, given to each [`GeneticNode`] method that processes it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GeneticNodeContext {
+    pub generation: u64,
+    pub max_generations: u64,
+    pub id: Uuid,
+    /// The level of the bracket the node is on, where leaf nodes have a height of 1
+    pub height: u64,
+    /// Values kept with the node between calls, see [`UserData`]
+    pub user_data: UserData,
 }
 
-/// A trait used to interact with the internal state of nodes within the [`Bracket`]
+/// Information about the node finished nodes are merged into, given to [`GeneticNode::merge_with_context`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MergeContext {
+    /// The population size the merged node should have, taken from [`GemlaConfig::target_population`]. When unset,
+    /// the merged node is expected to keep the population size of the nodes it's merged from.
+    ///
+    /// [`GemlaConfig::target_population`]: crate::core::GemlaConfig::target_population
+    pub target_population_hint: Option<usize>,
+    /// The level of the bracket the merged node is on
+    pub height: u64,
+    pub id: Uuid,
+}
+
+/// Values a [`GeneticNode`] keeps with its wrapper between calls without storing them in the node itself, reached
+/// through [`GeneticNodeContext::user_data`]. Values written while a node is processed are kept for every later
+/// generation of the same node, and are saved in the state file as JSON so they also survive a restart. Anything that
+/// can't be serialized, like an open file, has to be recreated from a value stored here, such as its path.
 ///
-/// [`Bracket`]: crate::bracket::Bracket
+/// Clones refer to the same values, but only writes made before the [`GeneticNode`] method returns are kept. Values
+/// are cleared when the node is reset, and nodes created by merging start without any.
+#[derive(Debug, Clone, Default)]
+pub struct UserData {
+    values: Arc<Mutex<BTreeMap<String, String>>>,
+}
+
+impl UserData {
+    fn new(values: BTreeMap<String, String>) -> Self {
+        UserData {
+            values: Arc::new(Mutex::new(values)),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BTreeMap<String, String>> {
+        self.values.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Returns the value stored under `key`, or `None` if nothing is stored there.
+    pub fn get<V: DeserializeOwned>(&self, key: &str) -> Result<Option<V>, Error> {
+        self.lock()
+            .get(key)
+            .map(|v| {
+                serde_json::from_str(v)
+                    .with_context(|| format!("Unable to deserialize user data {}", key))
+            })
+            .transpose()
+            .map_err(Error::from)
+    }
+
+    /// Stores `value` under `key`, replacing any value already stored there.
+    pub fn insert<V: Serialize>(&self, key: &str, value: &V) -> Result<(), Error> {
+        let value = serde_json::to_string(value)
+            .with_context(|| format!("Unable to serialize user data {}", key))?;
+        self.lock().insert(key.to_string(), value);
+
+        Ok(())
+    }
+
+    /// Removes the value stored under `key`, returning whether there was one.
+    pub fn remove(&self, key: &str) -> bool {
+        self.lock().remove(key).is_some()
+    }
+
+    fn take(&self) -> BTreeMap<String, String> {
+        mem::take(&mut *self.lock())
+    }
+}
+
+impl PartialEq for UserData {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.values, &other.values) || *self.lock() == *other.lock()
+    }
+}
+
+/// How to handle nodes being merged into a node that already holds data, returned by
+/// [`GeneticNode::on_merge_conflict`].
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum MergeConflict {
+    /// Keep the existing data and skip the merge
+    Reuse,
+    /// Discard the existing data and replace it with the merged node
+    Replace,
+}
+
+/// A node rebuilt from the artifacts it stored outside of the simulation tree, returned by
+/// [`GeneticNode::recover_from_disk`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecoveredNode<T> {
+    pub id: Uuid,
+    /// The generation the node continues from, its data is simulated again for this generation
+    pub generation: u64,
+    pub node: T,
+}
+
+/// A trait used to interact with the internal state of nodes within the [`Gemla`] simulation tree
+///
+/// [`Gemla`]: crate::core::Gemla
 pub trait GeneticNode {
     /// Initializes a new instance of a [`GeneticState`].
     ///
     /// # Examples
     /// TODO
-    fn initialize() -> Result<Box<Self>, Error>;
+    fn initialize(context: GeneticNodeContext) -> Result<Box<Self>, Error>;
 
-    fn simulate(&mut self) -> Result<(), Error>;
+    fn simulate(&mut self, context: GeneticNodeContext) -> Result<(), Error>;
+
+    /// Scores the members of a population after they have been simulated, for example to rank them by fitness and
+    /// prune the weakest before they are mutated. Called after every simulation, including the last one. The default
+    /// implementation does nothing.
+    ///
+    /// # Examples
+    /// TODO
+    fn score(&mut self, _context: GeneticNodeContext) -> Result<(), Error> {
+        Ok(())
+    }
 
     /// Mutates members in a population and/or crossbreeds them to produce new offspring.
     ///
     /// # Examples
     /// TODO
-    fn mutate(&mut self) -> Result<(), Error>;
+    fn mutate(&mut self, context: GeneticNodeContext) -> Result<(), Error>;
+
+    /// Returns the state the node moves on to after being processed in the `current` state, which lets a node add its
+    /// own [`GeneticState::Custom`] states to each generation. The generation moves on once the node leaves
+    /// [`GeneticState::Mutate`], and [`GeneticState::Initialize`] can't be returned. The default implementation
+    /// simulates and scores each generation, then mutates until the node has run all of its generations. Custom states
+    /// continue in the same way as [`GeneticState::Score`].
+    ///
+    /// # Examples
+    /// TODO
+    fn next_state(&self, current: GeneticState, context: &GeneticNodeContext) -> GeneticState {
+        match current {
+            GeneticState::Initialize | GeneticState::Mutate => GeneticState::Simulate,
+            GeneticState::Simulate => GeneticState::Score,
+            GeneticState::Score | GeneticState::Custom(_)
+                if context.generation >= context.max_generations =>
+            {
+                GeneticState::Finish
+            }
+            GeneticState::Score | GeneticState::Custom(_) => GeneticState::Mutate,
+            GeneticState::Finish => GeneticState::Finish,
+        }
+    }
+
+    /// Processes the node in the custom state `step`, see [`GeneticNode::next_state`]. The default implementation
+    /// returns an error, since a node only enters custom states it defines itself.
+    ///
+    /// # Examples
+    /// TODO
+    fn custom_step(&mut self, step: u8, _context: GeneticNodeContext) -> Result<(), Error> {
+        Err(Error::Other(anyhow!(
+            "Custom state {} isn't handled by the node",
+            step
+        )))
+    }
+
+    /// Returns a score for how well the node performed in its most recent simulation, higher is better.
+    ///
+    /// # Examples
+    /// TODO
+    fn fitness(&self) -> f64;
+
+    /// Returns the number of members in the node's population. Used to check that merging nodes doesn't change the
+    /// size of the population.
+    ///
+    /// # Examples
+    /// TODO
+    fn population_size(&self) -> usize;
 
     fn merge(left: &Self, right: &Self) -> Result<Box<Self>, Error>;
+
+    /// Checks that the node's data is consistent. Called after the node is initialized, simulated, mutated or merged,
+    /// an error stops the node from being processed any further. The default implementation accepts every node.
+    ///
+    /// # Examples
+    /// TODO
+    fn validate(&self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Called on the data already held by a node when its children are about to be merged into it, such as data left
+    /// behind by a run that was interrupted. Implementations that store artifacts keyed by the node's id can clean them
+    /// up here. An error stops the merge. The default implementation replaces the existing data.
+    ///
+    /// # Examples
+    /// TODO
+    fn on_merge_conflict(&self, _context: GeneticNodeContext) -> Result<MergeConflict, Error> {
+        Ok(MergeConflict::Replace)
+    }
+
+    /// Rebuilds the nodes that stored artifacts in `dir` during earlier runs, returning the latest generation found for
+    /// each node. Used by [`Gemla::rebuild_index`] to recover a simulation whose state file was lost. The default
+    /// implementation recovers nothing.
+    ///
+    /// [`Gemla::rebuild_index`]: crate::core::Gemla::rebuild_index
+    ///
+    /// # Examples
+    /// TODO
+    fn recover_from_disk(_dir: &Path) -> Result<Vec<RecoveredNode<Self>>, Error>
+    where
+        Self: Sized,
+    {
+        Ok(vec![])
+    }
+
+    /// Copies the node to start the new right branch with the given `id` when the bracket grows with
+    /// [`RightBranchMode::CloneChampion`]. Implementations can reset anything that shouldn't carry over to the copy,
+    /// such as artifacts keyed by the node's id. The default implementation clones the node.
+    ///
+    /// [`RightBranchMode::CloneChampion`]: crate::core::RightBranchMode::CloneChampion
+    ///
+    /// # Examples
+    /// TODO
+    fn clone_for_branch(&self, _id: Uuid) -> Box<Self>
+    where
+        Self: Sized + Clone,
+    {
+        Box::new(self.clone())
+    }
+
+    /// Merges any number of nodes into a single node. Used when a bracket has a fan in greater than two. The default
+    /// implementation folds the nodes together pairwise using [`GeneticNode::merge`].
+    ///
+    /// # Examples
+    /// TODO
+    fn merge_many(nodes: &[&Self]) -> Result<Box<Self>, Error> {
+        match nodes {
+            [first, second, rest @ ..] => {
+                let mut merged = Self::merge(first, second)?;
+
+                for node in rest {
+                    merged = Self::merge(&merged, node)?;
+                }
+
+                Ok(merged)
+            }
+            _ => Err(Error::Other(anyhow!(
+                "Unable to merge {} node(s), at least two are required",
+                nodes.len()
+            ))),
+        }
+    }
+
+    /// Merges any number of nodes into the node described by `context`. This is what [`Gemla`] calls when merging
+    /// nodes, so implementations can size the merged population from the hint in the context. The default
+    /// implementation ignores the context and uses [`GeneticNode::merge_many`].
+    ///
+    /// [`Gemla`]: crate::core::Gemla
+    ///
+    /// # Examples
+    /// TODO
+    fn merge_with_context(nodes: &[&Self], _context: MergeContext) -> Result<Box<Self>, Error> {
+        Self::merge_many(nodes)
+    }
 }
 
 /// Used externally to wrap a node implementing the [`GeneticNode`] trait. Processes state transitions for the given node as
 /// well as signal recovery. Transition states are given by [`GeneticState`]
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[derive(Debug, Deserialize, Clone, PartialEq)]
 pub struct GeneticNodeWrapper<T> {
     node: Option<T>,
     state: GeneticState,
     generation: u64,
     max_generations: u64,
     id: Uuid,
+    group: bool,
+    height: u64,
+    #[serde(default)]
+    failure: Option<SerializableError>,
+    /// The values of [`UserData`], serialized as JSON
+    #[serde(default)]
+    user_data: BTreeMap<String, String>,
+    /// The sidecar file the node's data was stored in, see [`GeneticNodeWrapper::store_payload`]
+    #[serde(default)]
+    payload: Option<PathBuf>,
+}
+
+/// The fields of a [`GeneticNodeWrapper`] in the order they are serialized in. Data that is stored in a sidecar file
+/// is left out so only the reference to the file is written.
+#[derive(Serialize)]
+#[serde(rename = "GeneticNodeWrapper")]
+struct SerializedWrapper<'a, T> {
+    node: Option<&'a T>,
+    state: GeneticState,
+    generation: u64,
+    max_generations: u64,
+    id: Uuid,
+    group: bool,
+    height: u64,
+    failure: &'a Option<SerializableError>,
+    user_data: &'a BTreeMap<String, String>,
+    payload: &'a Option<PathBuf>,
+}
+
+impl<T: Serialize> Serialize for GeneticNodeWrapper<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        SerializedWrapper {
+            node: self.node.as_ref().filter(|_| self.payload.is_none()),
+            state: self.state,
+            generation: self.generation,
+            max_generations: self.max_generations,
+            id: self.id,
+            group: self.group,
+            height: self.height,
+            failure: &self.failure,
+            user_data: &self.user_data,
+            payload: &self.payload,
+        }
+        .serialize(serializer)
+    }
 }
 
 impl<T> Default for GeneticNodeWrapper<T> {
@@ -64,6 +367,11 @@ impl<T> Default for GeneticNodeWrapper<T> {
             generation: 1,
             max_generations: 1,
             id: Uuid::new_v4(),
+            group: false,
+            height: 1,
+            failure: None,
+            user_data: BTreeMap::new(),
+            payload: None,
         }
     }
 }
@@ -86,17 +394,70 @@ where
             generation: 1,
             max_generations,
             id,
+            group: false,
+            height: 1,
+            failure: None,
+            user_data: BTreeMap::new(),
+            payload: None,
+        }
+    }
+
+    /// Sets the level of the bracket the node is on, where leaf nodes have a height of 1.
+    pub fn with_height(mut self, height: u64) -> Self {
+        self.height = height;
+        self
+    }
+
+    /// Sets the generation the node is on, used to continue a node from an earlier run.
+    pub fn with_generation(mut self, generation: u64) -> Self {
+        self.generation = generation;
+        self
+    }
+
+    /// Sets the id of the node, which is random by default.
+    pub fn with_id(mut self, id: Uuid) -> Self {
+        self.id = id;
+        self
+    }
+
+    /// Creates a placeholder node that groups its children together so they can be merged as a single bracket level.
+    /// Group nodes are never processed themselves.
+    pub fn group() -> Self {
+        GeneticNodeWrapper::<T> {
+            group: true,
+            ..Default::default()
         }
     }
 
+    /// Returns the node's data if it's loaded. Data stored in a sidecar file isn't loaded when the tree is read back,
+    /// see [`GeneticNodeWrapper::load_payload`].
     pub fn as_ref(&self) -> Option<&T> {
         self.node.as_ref()
     }
 
+    /// Returns whether the node holds data, either loaded or stored in a sidecar file.
+    pub fn has_data(&self) -> bool {
+        self.node.is_some() || self.payload.is_some()
+    }
+
+    /// Returns the sidecar file the node's data is stored in, if it was stored separately from the tree.
+    pub fn payload_path(&self) -> Option<&Path> {
+        self.payload.as_deref()
+    }
+
+    /// Moves the node's data out of the wrapper, leaving it empty.
+    pub(crate) fn take_node(&mut self) -> Option<T> {
+        self.node.take()
+    }
+
     pub fn id(&self) -> Uuid {
         self.id
     }
 
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
     pub fn max_generations(&self) -> u64 {
         self.max_generations
     }
@@ -105,37 +466,205 @@ where
         self.state
     }
 
+    pub fn is_group(&self) -> bool {
+        self.group
+    }
+
+    pub fn height(&self) -> u64 {
+        self.height
+    }
+
+    /// Returns why the node failed the last time it was processed, if it did. Cleared once the node is processed
+    /// successfully.
+    pub fn failure(&self) -> Option<&SerializableError> {
+        self.failure.as_ref()
+    }
+
+    pub(crate) fn set_failure(&mut self, failure: SerializableError) {
+        self.failure = Some(failure);
+    }
+
+    pub(crate) fn context(&self) -> GeneticNodeContext {
+        GeneticNodeContext {
+            generation: self.generation,
+            max_generations: self.max_generations,
+            id: self.id,
+            height: self.height,
+            user_data: UserData::new(self.user_data.clone()),
+        }
+    }
+
+    /// Increases the maximum number of generations for the node by `additional`. If the node has finished it moves
+    /// back to [`GeneticState::Mutate`] so that it continues on to the next generation.
+    pub fn extend_generations(&mut self, additional: u64) {
+        self.max_generations += additional;
+
+        if additional > 0 && self.state == GeneticState::Finish {
+            self.state = GeneticState::Mutate;
+        }
+    }
+
+    /// Discards the node's data and [`UserData`] and sets it back to [`GeneticState::Initialize`], keeping its id and
+    /// max generations.
+    pub fn reset(&mut self) {
+        self.node = None;
+        self.payload = None;
+        self.user_data.clear();
+        self.state = GeneticState::Initialize;
+        self.generation = 1;
+    }
+
+    /// Moves the node on to its next [`GeneticState`], returning the new state. Errors are returned as [`Error::Node`]
+    /// so they identify the node and the state it failed in.
     pub fn process_node(&mut self) -> Result<GeneticState, Error> {
-        match (self.state, &mut self.node) {
+        let (state, generation) = (self.state, self.generation);
+
+        match self.transition() {
+            Ok(state) => {
+                self.failure = None;
+                Ok(state)
+            }
+            Err(e) => {
+                let error = Error::Node {
+                    id: self.id,
+                    state,
+                    generation,
+                    source: Box::new(e),
+                };
+                self.failure = Some(SerializableError::from(&error));
+                Err(error)
+            }
+        }
+    }
+
+    fn transition(&mut self) -> Result<GeneticState, Error> {
+        // The data is about to change, so any sidecar file no longer holds it
+        if self.node.is_some() {
+            self.payload = None;
+        }
+
+        let context = self.context();
+        let user_data = context.user_data.clone();
+
+        let state = self.transition_with(context);
+        // Values written before an error are kept as well, the node may rely on them when it is processed again
+        self.user_data = user_data.take();
+
+        state
+    }
+
+    fn transition_with(&mut self, context: GeneticNodeContext) -> Result<GeneticState, Error> {
+        let current = self.state;
+
+        match (current, &mut self.node) {
             (GeneticState::Initialize, _) => {
-                self.node = Some(*T::initialize()?);
-                self.state = GeneticState::Simulate;
+                self.node = Some(*T::initialize(context.clone())?);
             }
             (GeneticState::Simulate, Some(n)) => {
-                n.simulate()
+                n.simulate(context.clone())
                     .with_context(|| format!("Error simulating node: {:?}", self))?;
-
-                self.state = if self.generation >= self.max_generations {
-                    GeneticState::Finish
-                } else {
-                    GeneticState::Mutate
-                };
+            }
+            (GeneticState::Score, Some(n)) => {
+                n.score(context.clone())
+                    .with_context(|| format!("Error scoring node: {:?}", self))?;
             }
             (GeneticState::Mutate, Some(n)) => {
-                n.mutate()
+                n.mutate(context.clone())
                     .with_context(|| format!("Error mutating node: {:?}", self))?;
+            }
+            (GeneticState::Custom(step), Some(n)) => {
+                n.custom_step(step, context.clone()).with_context(|| {
+                    format!("Error processing custom state {} of node: {:?}", step, self)
+                })?;
+            }
+            (GeneticState::Finish, Some(_)) => return Ok(self.state),
+            _ => panic!("Error processing node {:?}", self.node),
+        }
 
+        if let Some(n) = &self.node {
+            let next = n.next_state(current, &context);
+            if next == GeneticState::Initialize {
+                return Err(Error::Other(anyhow!(
+                    "Node {} can't move from {:?} back to {:?}",
+                    self.id,
+                    current,
+                    next
+                )));
+            }
+
+            if current == GeneticState::Mutate {
                 self.generation += 1;
-                self.state = GeneticState::Simulate;
             }
-            (GeneticState::Finish, Some(_)) => (),
-            _ => panic!("Error processing node {:?}", self.node),
+            self.state = next;
+
+            n.validate()
+                .with_context(|| format!("Error validating node: {:?}", self))?;
         }
 
         Ok(self.state)
     }
 }
 
+impl<T> GeneticNodeWrapper<T>
+where
+    T: Serialize + DeserializeOwned,
+{
+    /// Writes the node's data to a sidecar file in `dir` named after the node's id, generation and state, so that
+    /// serializing the wrapper only writes a reference to the file. The data stays loaded. Nothing is written if the
+    /// node has no data loaded or it's already stored for its current generation and state.
+    pub fn store_payload(&mut self, dir: &Path) -> Result<(), Error> {
+        let node = match &self.node {
+            Some(node) => node,
+            None => return Ok(()),
+        };
+
+        let path = dir.join(format!("{}-{}-{:?}", self.id, self.generation, self.state));
+        if self.payload.as_ref() == Some(&path) {
+            return Ok(());
+        }
+
+        let mut writer = BufWriter::new(
+            File::create(&path)
+                .with_context(|| format!("Unable to create sidecar file {}", path.display()))?,
+        );
+        bincode::serialize_into(&mut writer, node)
+            .with_context(|| format!("Unable to write node to sidecar file {}", path.display()))?;
+        writer.flush()?;
+
+        self.payload = Some(path);
+
+        Ok(())
+    }
+
+    /// Reads the node's data back from its sidecar file if it isn't loaded, returning the data.
+    pub fn load_payload(&mut self) -> Result<Option<&T>, Error> {
+        if let (None, Some(path)) = (&self.node, &self.payload) {
+            let file = File::open(path)
+                .with_context(|| format!("Unable to open sidecar file {}", path.display()))?;
+            let node = bincode::deserialize_from(BufReader::new(file)).with_context(|| {
+                format!("Unable to read node from sidecar file {}", path.display())
+            })?;
+
+            self.node = Some(node);
+        }
+
+        Ok(self.node.as_ref())
+    }
+
+    /// Drops the node's data from memory if it's stored in a sidecar file, returning whether it was dropped.
+    pub fn unload_payload(&mut self) -> bool {
+        self.payload.is_some() && self.node.take().is_some()
+    }
+
+    /// Loads the node's data and forgets its sidecar file, so the data is serialized with the wrapper again.
+    pub(crate) fn inline_payload(&mut self) -> Result<(), Error> {
+        self.load_payload()?;
+        self.payload = None;
+
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -148,19 +677,27 @@ mod tests {
     }
 
     impl GeneticNode for TestState {
-        fn simulate(&mut self) -> Result<(), Error> {
+        fn simulate(&mut self, _context: GeneticNodeContext) -> Result<(), Error> {
             self.score += 1.0;
             Ok(())
         }
 
-        fn mutate(&mut self) -> Result<(), Error> {
+        fn mutate(&mut self, _context: GeneticNodeContext) -> Result<(), Error> {
             Ok(())
         }
 
-        fn initialize() -> Result<Box<TestState>, Error> {
+        fn initialize(_context: GeneticNodeContext) -> Result<Box<TestState>, Error> {
             Ok(Box::new(TestState { score: 0.0 }))
         }
 
+        fn fitness(&self) -> f64 {
+            self.score
+        }
+
+        fn population_size(&self) -> usize {
+            1
+        }
+
         fn merge(_l: &TestState, _r: &TestState) -> Result<Box<TestState>, Error> {
             Err(Error::Other(anyhow!("Unable to merge")))
         }
@@ -176,6 +713,11 @@ mod tests {
             generation: 1,
             max_generations: 10,
             id: genetic_node.id(),
+            group: false,
+            height: 1,
+            failure: None,
+            user_data: BTreeMap::new(),
+            payload: None,
         };
 
         assert_eq!(genetic_node, other_genetic_node);
@@ -195,6 +737,11 @@ mod tests {
             generation: 1,
             max_generations: 10,
             id: genetic_node.id(),
+            group: false,
+            height: 1,
+            failure: None,
+            user_data: BTreeMap::new(),
+            payload: None,
         };
 
         assert_eq!(genetic_node, other_genetic_node);
@@ -241,6 +788,57 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_generation() -> Result<(), Error> {
+        let mut genetic_node = GeneticNodeWrapper::<TestState>::new(2);
+
+        assert_eq!(genetic_node.generation(), 1);
+        for _ in 0..4 {
+            genetic_node.process_node()?;
+        }
+        assert_eq!(genetic_node.generation(), 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_extend_generations() -> Result<(), Error> {
+        let mut genetic_node = GeneticNodeWrapper::<TestState>::new(1);
+
+        genetic_node.process_node()?;
+        genetic_node.process_node()?;
+        assert_eq!(genetic_node.process_node()?, GeneticState::Finish);
+
+        genetic_node.extend_generations(0);
+        assert_eq!(genetic_node.state(), GeneticState::Finish);
+
+        genetic_node.extend_generations(1);
+        assert_eq!(genetic_node.max_generations(), 2);
+        assert_eq!(genetic_node.state(), GeneticState::Mutate);
+        assert_eq!(genetic_node.process_node()?, GeneticState::Simulate);
+        assert_eq!(genetic_node.process_node()?, GeneticState::Score);
+        assert_eq!(genetic_node.process_node()?, GeneticState::Finish);
+        assert_eq!(genetic_node.generation(), 2);
+        assert_eq!(genetic_node.as_ref().unwrap().score, 2.0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_reset() -> Result<(), Error> {
+        let uuid = Uuid::new_v4();
+        let mut genetic_node = GeneticNodeWrapper::from(TestState { score: 3.0 }, 10, uuid);
+
+        genetic_node.reset();
+
+        assert_eq!(genetic_node.state(), GeneticState::Initialize);
+        assert_eq!(genetic_node.as_ref(), None);
+        assert_eq!(genetic_node.id(), uuid);
+        assert_eq!(genetic_node.max_generations(), 10);
+
+        Ok(())
+    }
+
     #[test]
     fn test_state() -> Result<(), Error> {
         let val = TestState { score: 3.0 };
@@ -260,11 +858,425 @@ mod tests {
 
         assert_eq!(genetic_node.state(), GeneticState::Initialize);
         assert_eq!(genetic_node.process_node()?, GeneticState::Simulate);
+        assert_eq!(genetic_node.process_node()?, GeneticState::Score);
         assert_eq!(genetic_node.process_node()?, GeneticState::Mutate);
         assert_eq!(genetic_node.process_node()?, GeneticState::Simulate);
+        assert_eq!(genetic_node.process_node()?, GeneticState::Score);
         assert_eq!(genetic_node.process_node()?, GeneticState::Finish);
         assert_eq!(genetic_node.process_node()?, GeneticState::Finish);
 
         Ok(())
     }
+
+    #[test]
+    fn test_height() -> Result<(), Error> {
+        let genetic_node = GeneticNodeWrapper::<TestState>::new(10);
+        assert_eq!(genetic_node.height(), 1);
+
+        let genetic_node = genetic_node.with_height(3);
+        assert_eq!(genetic_node.height(), 3);
+        assert_eq!(genetic_node.context().height, 3);
+
+        Ok(())
+    }
+
+    #[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+    struct LimitedState {
+        pub score: f64,
+    }
+
+    impl GeneticNode for LimitedState {
+        fn simulate(&mut self, _context: GeneticNodeContext) -> Result<(), Error> {
+            self.score += 1.0;
+            Ok(())
+        }
+
+        fn mutate(&mut self, _context: GeneticNodeContext) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn initialize(_context: GeneticNodeContext) -> Result<Box<LimitedState>, Error> {
+            Ok(Box::new(LimitedState { score: 0.0 }))
+        }
+
+        fn fitness(&self) -> f64 {
+            self.score
+        }
+
+        fn population_size(&self) -> usize {
+            1
+        }
+
+        fn merge(_l: &LimitedState, _r: &LimitedState) -> Result<Box<LimitedState>, Error> {
+            Err(Error::Other(anyhow!("Unable to merge")))
+        }
+
+        fn validate(&self) -> Result<(), Error> {
+            if self.score > 1.0 {
+                Err(Error::Other(anyhow!(
+                    "Score {} is over the limit",
+                    self.score
+                )))
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    #[test]
+    fn test_validate() -> Result<(), Error> {
+        let mut genetic_node = GeneticNodeWrapper::<LimitedState>::new(3);
+
+        assert_eq!(genetic_node.process_node()?, GeneticState::Simulate);
+        assert_eq!(genetic_node.process_node()?, GeneticState::Score);
+        assert_eq!(genetic_node.process_node()?, GeneticState::Mutate);
+        assert_eq!(genetic_node.process_node()?, GeneticState::Simulate);
+        match genetic_node.process_node() {
+            Err(Error::Node {
+                id,
+                state,
+                generation,
+                source,
+            }) => {
+                assert_eq!(id, genetic_node.id());
+                assert_eq!(state, GeneticState::Simulate);
+                assert_eq!(generation, 2);
+                assert!(anyhow::Error::from(*source)
+                    .chain()
+                    .any(|e| e.to_string().contains("over the limit")));
+            }
+            _ => panic!("Expected the node to fail validation"),
+        }
+        assert_eq!(genetic_node.failure().unwrap().kind, "Node");
+
+        Ok(())
+    }
+
+    #[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+    struct ScoredState {
+        pub population: Vec<f64>,
+    }
+
+    impl GeneticNode for ScoredState {
+        fn simulate(&mut self, _context: GeneticNodeContext) -> Result<(), Error> {
+            self.population.push(self.population.len() as f64);
+            Ok(())
+        }
+
+        fn score(&mut self, _context: GeneticNodeContext) -> Result<(), Error> {
+            // Only the best member survives to be mutated
+            let best = self.fitness();
+            self.population.retain(|p| *p == best);
+            Ok(())
+        }
+
+        fn mutate(&mut self, _context: GeneticNodeContext) -> Result<(), Error> {
+            self.population.push(0.0);
+            Ok(())
+        }
+
+        fn initialize(_context: GeneticNodeContext) -> Result<Box<ScoredState>, Error> {
+            Ok(Box::new(ScoredState {
+                population: vec![0.0],
+            }))
+        }
+
+        fn fitness(&self) -> f64 {
+            self.population.iter().cloned().fold(0.0, f64::max)
+        }
+
+        fn population_size(&self) -> usize {
+            self.population.len()
+        }
+
+        fn merge(_l: &ScoredState, _r: &ScoredState) -> Result<Box<ScoredState>, Error> {
+            Err(Error::Other(anyhow!("Unable to merge")))
+        }
+    }
+
+    #[test]
+    fn test_score() -> Result<(), Error> {
+        let mut genetic_node = GeneticNodeWrapper::<ScoredState>::new(2);
+
+        genetic_node.process_node()?;
+        assert_eq!(genetic_node.process_node()?, GeneticState::Score);
+        assert_eq!(genetic_node.as_ref().unwrap().population, vec![0.0, 1.0]);
+
+        assert_eq!(genetic_node.process_node()?, GeneticState::Mutate);
+        assert_eq!(genetic_node.as_ref().unwrap().population, vec![1.0]);
+
+        genetic_node.process_node()?;
+        genetic_node.process_node()?;
+        assert_eq!(genetic_node.process_node()?, GeneticState::Finish);
+        assert_eq!(genetic_node.as_ref().unwrap().population, vec![2.0]);
+
+        Ok(())
+    }
+
+    #[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+    struct CachedState {
+        pub seeds: Vec<u64>,
+    }
+
+    impl GeneticNode for CachedState {
+        fn simulate(&mut self, context: GeneticNodeContext) -> Result<(), Error> {
+            let seed = context
+                .user_data
+                .get::<u64>("seed")?
+                .ok_or_else(|| anyhow!("Seed was not kept"))?;
+            self.seeds.push(seed);
+            context.user_data.insert("seed", &(seed + 1))?;
+            Ok(())
+        }
+
+        fn mutate(&mut self, _context: GeneticNodeContext) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn initialize(context: GeneticNodeContext) -> Result<Box<CachedState>, Error> {
+            context.user_data.insert("seed", &42u64)?;
+            Ok(Box::new(CachedState { seeds: vec![] }))
+        }
+
+        fn fitness(&self) -> f64 {
+            0.0
+        }
+
+        fn population_size(&self) -> usize {
+            1
+        }
+
+        fn merge(_l: &CachedState, _r: &CachedState) -> Result<Box<CachedState>, Error> {
+            Err(Error::Other(anyhow!("Unable to merge")))
+        }
+    }
+
+    #[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+    struct EvaluatedState {
+        pub rollouts: u64,
+        pub score: f64,
+    }
+
+    impl GeneticNode for EvaluatedState {
+        fn simulate(&mut self, _context: GeneticNodeContext) -> Result<(), Error> {
+            self.rollouts += 1;
+            Ok(())
+        }
+
+        fn mutate(&mut self, _context: GeneticNodeContext) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn next_state(&self, current: GeneticState, context: &GeneticNodeContext) -> GeneticState {
+            // Rollouts are evaluated in their own state instead of being scored
+            match current {
+                GeneticState::Simulate => GeneticState::Custom(1),
+                GeneticState::Custom(_) if context.generation >= context.max_generations => {
+                    GeneticState::Finish
+                }
+                GeneticState::Custom(_) => GeneticState::Mutate,
+                _ => GeneticState::Simulate,
+            }
+        }
+
+        fn custom_step(&mut self, step: u8, _context: GeneticNodeContext) -> Result<(), Error> {
+            match step {
+                1 => {
+                    self.score = self.rollouts as f64 * 10.0;
+                    Ok(())
+                }
+                _ => Err(Error::Other(anyhow!("Unknown step {}", step))),
+            }
+        }
+
+        fn initialize(_context: GeneticNodeContext) -> Result<Box<EvaluatedState>, Error> {
+            Ok(Box::new(EvaluatedState {
+                rollouts: 0,
+                score: 0.0,
+            }))
+        }
+
+        fn fitness(&self) -> f64 {
+            self.score
+        }
+
+        fn population_size(&self) -> usize {
+            1
+        }
+
+        fn merge(_l: &EvaluatedState, _r: &EvaluatedState) -> Result<Box<EvaluatedState>, Error> {
+            Err(Error::Other(anyhow!("Unable to merge")))
+        }
+    }
+
+    #[test]
+    fn test_custom_state() -> Result<(), Error> {
+        let mut node = GeneticNodeWrapper::<EvaluatedState>::new(2);
+        let mut states = vec![node.state()];
+        while node.state() != GeneticState::Finish {
+            states.push(node.process_node()?);
+        }
+
+        assert_eq!(
+            states,
+            vec![
+                GeneticState::Initialize,
+                GeneticState::Simulate,
+                GeneticState::Custom(1),
+                GeneticState::Mutate,
+                GeneticState::Simulate,
+                GeneticState::Custom(1),
+                GeneticState::Finish,
+            ]
+        );
+        assert_eq!(node.generation(), 2);
+        assert_eq!(node.as_ref().unwrap().score, 20.0);
+
+        // Custom states are written to state files like any other
+        let mut node = GeneticNodeWrapper::<EvaluatedState>::new(2);
+        node.process_node()?;
+        node.process_node()?;
+        let read: GeneticNodeWrapper<EvaluatedState> =
+            bincode::deserialize(&bincode::serialize(&node).map_err(anyhow::Error::from)?)
+                .map_err(anyhow::Error::from)?;
+        assert_eq!(read.state(), GeneticState::Custom(1));
+
+        // Nodes without custom states refuse to process them
+        let mut node = GeneticNodeWrapper::from(TestState { score: 0.0 }, 1, Uuid::new_v4());
+        node.state = GeneticState::Custom(3);
+        assert!(node.process_node().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_user_data() -> Result<(), Error> {
+        let mut genetic_node = GeneticNodeWrapper::<CachedState>::new(3);
+
+        // Initialize, then simulate, score and mutate for the first generation
+        for _ in 0..4 {
+            genetic_node.process_node()?;
+        }
+        assert_eq!(genetic_node.as_ref().unwrap().seeds, vec![42]);
+        assert_eq!(
+            genetic_node.context().user_data.get::<u64>("seed")?,
+            Some(43)
+        );
+
+        // Values are kept when the node is saved and loaded again
+        let mut genetic_node: GeneticNodeWrapper<CachedState> = serde_json::from_str(
+            &serde_json::to_string(&genetic_node).map_err(anyhow::Error::from)?,
+        )
+        .map_err(anyhow::Error::from)?;
+        while genetic_node.process_node()? != GeneticState::Finish {}
+        assert_eq!(genetic_node.as_ref().unwrap().seeds, vec![42, 43, 44]);
+
+        // Values of the wrong type are reported rather than ignored
+        let context = genetic_node.context();
+        assert!(context.user_data.get::<String>("seed").is_err());
+        assert!(context.user_data.get::<u64>("missing")?.is_none());
+        assert!(context.user_data.remove("seed"));
+        assert!(!context.user_data.remove("seed"));
+
+        genetic_node.reset();
+        assert_eq!(genetic_node.context().user_data.get::<u64>("seed")?, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_group() -> Result<(), Error> {
+        let group = GeneticNodeWrapper::<TestState>::group();
+
+        assert!(group.is_group());
+        assert!(group.as_ref().is_none());
+        assert!(!GeneticNodeWrapper::<TestState>::new(10).is_group());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_payload() -> Result<(), Error> {
+        let dir = std::env::temp_dir().join(format!("test_payload_{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir)?;
+
+        let result = (|| {
+            let mut node = GeneticNodeWrapper::from(TestState { score: 1.0 }, 10, Uuid::new_v4());
+            node.store_payload(&dir)?;
+            assert!(node.payload_path().unwrap().exists());
+
+            // Only the reference to the sidecar file is serialized
+            let serialized = bincode::serialize(&node).map_err(anyhow::Error::from)?;
+            let mut read: GeneticNodeWrapper<TestState> =
+                bincode::deserialize(&serialized).map_err(anyhow::Error::from)?;
+            assert!(read.as_ref().is_none());
+            assert!(read.has_data());
+            assert_eq!(read.load_payload()?, Some(&TestState { score: 1.0 }));
+
+            // Processing the node changes its data, so it has to be stored again
+            node.process_node()?;
+            assert!(node.payload_path().is_none());
+            node.store_payload(&dir)?;
+            assert!(node.unload_payload());
+            assert_eq!(node.load_payload()?, Some(&TestState { score: 2.0 }));
+
+            Ok(())
+        })();
+
+        std::fs::remove_dir_all(&dir)?;
+        result
+    }
+
+    /// Merges by appending the digits of the right node to the left one, so the result shows the order of the merges
+    #[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+    struct DigitState {
+        pub digits: u64,
+    }
+
+    impl GeneticNode for DigitState {
+        fn simulate(&mut self, _context: GeneticNodeContext) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn mutate(&mut self, _context: GeneticNodeContext) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn initialize(_context: GeneticNodeContext) -> Result<Box<DigitState>, Error> {
+            Ok(Box::new(DigitState { digits: 0 }))
+        }
+
+        fn fitness(&self) -> f64 {
+            self.digits as f64
+        }
+
+        fn population_size(&self) -> usize {
+            1
+        }
+
+        fn merge(l: &DigitState, r: &DigitState) -> Result<Box<DigitState>, Error> {
+            Ok(Box::new(DigitState {
+                digits: l.digits * 10 + r.digits,
+            }))
+        }
+    }
+
+    #[test]
+    fn test_merge_many() -> Result<(), Error> {
+        let nodes = [
+            DigitState { digits: 1 },
+            DigitState { digits: 2 },
+            DigitState { digits: 3 },
+        ];
+        let refs = nodes.iter().collect::<Vec<&DigitState>>();
+
+        assert!(DigitState::merge_many(&[]).is_err());
+        assert!(DigitState::merge_many(&refs[..1]).is_err());
+
+        // The nodes are folded together from left to right
+        assert_eq!(DigitState::merge_many(&refs[..2])?.digits, 12);
+        assert_eq!(DigitState::merge_many(&refs)?.digits, 123);
+
+        Ok(())
+    }
 }
@@ -0,0 +1,210 @@
+//! An append-only record of what happened during a simulation, written next to the state file when
+//! [`GemlaConfig::event_journal`] is enabled so runs can be looked into after they finished.
+//!
+//! [`GemlaConfig::event_journal`]: super::GemlaConfig::event_journal
+
+use super::genetic_node::{GeneticNode, GeneticNodeWrapper, GeneticState};
+use crate::error::Error;
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::{
+    ffi::OsString,
+    fmt::Debug,
+    fs::{self, File, OpenOptions},
+    io::{BufRead, BufReader, ErrorKind, Write},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+use tracing::warn;
+use uuid::Uuid;
+
+/// What happened to the node an event was recorded for.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum JournalEventKind {
+    /// The node was processed and moved on to the given state
+    StateChange(GeneticState),
+    /// The children of the node were merged into it
+    Merge,
+    /// The tree was grown and the node is its new root
+    HeightIncrease,
+}
+
+/// A single line of the journal.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct JournalEvent {
+    pub timestamp: SystemTime,
+    pub kind: JournalEventKind,
+    pub id: Uuid,
+    /// The position of the node in the tree when the event was recorded, such as `root/L/R`
+    pub path: String,
+    pub generation: u64,
+    /// The level of the bracket the node is on, so the number of levels the tree has grown to for
+    /// [`JournalEventKind::HeightIncrease`]
+    pub height: u64,
+}
+
+impl JournalEvent {
+    pub(crate) fn new<T: GeneticNode + Debug>(
+        kind: JournalEventKind,
+        node: &GeneticNodeWrapper<T>,
+        path: String,
+    ) -> Self {
+        JournalEvent {
+            timestamp: SystemTime::now(),
+            kind,
+            id: node.id(),
+            path,
+            generation: node.generation(),
+            height: node.height(),
+        }
+    }
+}
+
+/// Appends `extension` to the full file name of `path`, so state files that only differ in their extension don't
+/// share a journal.
+fn sibling_path(path: &Path, extension: &str) -> PathBuf {
+    let mut file_name = path.file_name().map(OsString::from).unwrap_or_default();
+    file_name.push(extension);
+
+    path.with_file_name(file_name)
+}
+
+/// The journal kept for the state file at `path`.
+pub(crate) fn journal_path(path: &Path) -> PathBuf {
+    sibling_path(path, ".journal")
+}
+
+/// The file the journal is moved to once it grows too large.
+fn rotated_path(path: &Path) -> PathBuf {
+    sibling_path(path, ".journal.1")
+}
+
+/// Appends `events` to the journal of the state file at `path`, one JSON object per line. Once the journal has reached
+/// `max_bytes` it's moved aside first, replacing the journal moved aside before it.
+pub(crate) fn append(
+    path: &Path,
+    max_bytes: Option<u64>,
+    events: &[JournalEvent],
+) -> Result<(), Error> {
+    if events.is_empty() {
+        return Ok(());
+    }
+
+    let journal = journal_path(path);
+    if let Some(max) = max_bytes {
+        match fs::metadata(&journal) {
+            Ok(metadata) if metadata.len() >= max => {
+                fs::rename(&journal, rotated_path(path))
+                    .with_context(|| format!("Unable to rotate journal {}", journal.display()))?;
+            }
+            Ok(_) => (),
+            Err(e) if e.kind() == ErrorKind::NotFound => (),
+            Err(e) => return Err(Error::IO(e)),
+        }
+    }
+
+    let mut lines = vec![];
+    for event in events {
+        serde_json::to_writer(&mut lines, event)
+            .with_context(|| format!("Unable to serialize journal event {:?}", event))?;
+        lines.push(b'\n');
+    }
+
+    // The events are written at once so an interrupted write can only cut off the last line
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&journal)
+        .and_then(|mut file| file.write_all(&lines))
+        .with_context(|| format!("Unable to append to journal {}", journal.display()))?;
+
+    Ok(())
+}
+
+/// Reads every event from the journal of the state file at `path`, including the journal moved aside by the last
+/// rotation, oldest first. Lines that can't be read, such as one cut off by an interrupted write, are skipped.
+pub(crate) fn read(path: &Path) -> Result<Vec<JournalEvent>, Error> {
+    let mut events = vec![];
+
+    for journal in [rotated_path(path), journal_path(path)].iter() {
+        let file = match File::open(journal) {
+            Ok(file) => file,
+            Err(e) if e.kind() == ErrorKind::NotFound => continue,
+            Err(e) => return Err(Error::IO(e)),
+        };
+
+        for (i, line) in BufReader::new(file).lines().enumerate() {
+            match serde_json::from_str(&line?) {
+                Ok(event) => events.push(event),
+                Err(e) => warn!(
+                    "Skipping line {} of journal {}: {}",
+                    i + 1,
+                    journal.display(),
+                    e
+                ),
+            }
+        }
+    }
+
+    Ok(events)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rotation() -> Result<(), Error> {
+        let path = PathBuf::from("test_journal_rotation");
+        let event = |kind| JournalEvent {
+            timestamp: SystemTime::now(),
+            kind,
+            id: Uuid::new_v4(),
+            path: "root".to_string(),
+            generation: 0,
+            height: 1,
+        };
+
+        let result = (|| {
+            append(&path, Some(1), &[event(JournalEventKind::HeightIncrease)])?;
+            append(&path, Some(1), &[event(JournalEventKind::Merge)])?;
+
+            // The first event was moved aside, and a cut off line is skipped
+            assert!(rotated_path(&path).exists());
+            let mut journal = OpenOptions::new().append(true).open(journal_path(&path))?;
+            journal.write_all(b"{\"timestamp\":")?;
+
+            let kinds = read(&path)?
+                .into_iter()
+                .map(|e| e.kind)
+                .collect::<Vec<JournalEventKind>>();
+            assert_eq!(
+                kinds,
+                vec![JournalEventKind::HeightIncrease, JournalEventKind::Merge]
+            );
+
+            Ok(())
+        })();
+
+        fs::remove_file(journal_path(&path))?;
+        fs::remove_file(rotated_path(&path))?;
+        result
+    }
+
+    #[test]
+    fn test_journal_path() {
+        assert_eq!(
+            journal_path(Path::new("runs/run.a")),
+            PathBuf::from("runs/run.a.journal")
+        );
+        assert_eq!(
+            rotated_path(Path::new("runs/run.b")),
+            PathBuf::from("runs/run.b.journal.1")
+        );
+        // A state file that already has the journal's extension isn't written to
+        assert_eq!(
+            journal_path(Path::new("x.journal")),
+            PathBuf::from("x.journal.journal")
+        );
+    }
+}
@@ -0,0 +1,297 @@
+//! Collects metrics about a running simulation, which can be served in the Prometheus text format with the `metrics`
+//! feature.
+
+use super::genetic_node::GeneticState;
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    sync::{Mutex, PoisonError},
+    time::Duration,
+};
+
+/// The upper bounds, in seconds, of the buckets durations are counted in.
+const BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.05, 0.1, 0.5, 1.0, 5.0, 10.0, 60.0, 300.0, 1800.0,
+];
+
+/// Every built in state a node can be in, in the order they are rendered. Custom states are rendered after them.
+const STATES: [GeneticState; 5] = [
+    GeneticState::Initialize,
+    GeneticState::Simulate,
+    GeneticState::Score,
+    GeneticState::Mutate,
+    GeneticState::Finish,
+];
+
+/// Metrics about a simulation, updated by [`Gemla`] as it grows and processes its tree.
+///
+/// [`Gemla`]: super::Gemla
+#[derive(Debug, Default)]
+pub struct Metrics {
+    state: Mutex<MetricsState>,
+}
+
+#[derive(Debug, Default)]
+struct MetricsState {
+    nodes: [u64; STATES.len()],
+    /// The number of nodes in each custom state, by step
+    custom_nodes: BTreeMap<u8, u64>,
+    generations_completed: u64,
+    node_processing: Histogram,
+    file_write: Histogram,
+    best_fitness: Option<f64>,
+}
+
+#[derive(Debug, Default)]
+struct Histogram {
+    buckets: [u64; BUCKETS.len()],
+    count: u64,
+    sum: f64,
+}
+
+impl Histogram {
+    fn observe(&mut self, duration: Duration) {
+        let seconds = duration.as_secs_f64();
+
+        for (bucket, bound) in self.buckets.iter_mut().zip(BUCKETS) {
+            if seconds <= bound {
+                *bucket += 1;
+            }
+        }
+        self.count += 1;
+        self.sum += seconds;
+    }
+
+    fn render(&self, out: &mut String, name: &str, help: &str) {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} histogram", name);
+        for (bucket, bound) in self.buckets.iter().zip(BUCKETS) {
+            let _ = writeln!(out, "{}_bucket{{le=\"{}\"}} {}", name, bound, bucket);
+        }
+        let _ = writeln!(out, "{}_bucket{{le=\"+Inf\"}} {}", name, self.count);
+        let _ = writeln!(out, "{}_sum {}", name, self.sum);
+        let _ = writeln!(out, "{}_count {}", name, self.count);
+    }
+}
+
+impl Metrics {
+    fn lock(&self) -> std::sync::MutexGuard<'_, MetricsState> {
+        // Metrics are only ever incremented, so they're still usable after a panic
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Records a node that took `duration` to be processed from `from`, moving on to `to` unless processing failed. A
+    /// generation is completed once a node leaves [`GeneticState::Mutate`] for the next generation or reaches
+    /// [`GeneticState::Finish`], whichever states it went through in between.
+    pub(crate) fn record_node(
+        &self,
+        from: GeneticState,
+        to: Option<GeneticState>,
+        duration: Duration,
+    ) {
+        let mut metrics = self.lock();
+
+        metrics.node_processing.observe(duration);
+        let completed = match (from, to) {
+            (_, None) | (GeneticState::Finish, _) => false,
+            (GeneticState::Mutate, Some(_)) | (_, Some(GeneticState::Finish)) => true,
+            _ => false,
+        };
+        if completed {
+            metrics.generations_completed += 1;
+        }
+    }
+
+    /// Records writing the state file, which took `duration`.
+    pub(crate) fn record_file_write(&self, duration: Duration) {
+        self.lock().file_write.observe(duration);
+    }
+
+    /// Replaces the number of nodes in each state and the best fitness with those of the current tree.
+    pub(crate) fn record_tree(&self, states: &[GeneticState], best_fitness: Option<f64>) {
+        let mut metrics = self.lock();
+
+        metrics.nodes = [0; STATES.len()];
+        metrics.custom_nodes.clear();
+        for state in states {
+            match state {
+                GeneticState::Custom(step) => *metrics.custom_nodes.entry(*step).or_default() += 1,
+                _ => {
+                    if let Some(i) = STATES.iter().position(|s| s == state) {
+                        metrics.nodes[i] += 1;
+                    }
+                }
+            }
+        }
+        metrics.best_fitness = best_fitness;
+    }
+
+    /// Renders the metrics in the Prometheus text format.
+    pub fn render(&self) -> String {
+        let metrics = self.lock();
+        let mut out = String::new();
+
+        let _ = writeln!(
+            out,
+            "# HELP gemla_nodes The number of nodes in the simulation tree in each state."
+        );
+        let _ = writeln!(out, "# TYPE gemla_nodes gauge");
+        let custom = metrics
+            .custom_nodes
+            .iter()
+            .map(|(step, count)| (GeneticState::Custom(*step), *count));
+        for (state, count) in STATES.iter().copied().zip(metrics.nodes).chain(custom) {
+            let _ = writeln!(out, "gemla_nodes{{state=\"{:?}\"}} {}", state, count);
+        }
+
+        let _ = writeln!(
+            out,
+            "# HELP gemla_generations_completed_total The number of generations completed by all nodes."
+        );
+        let _ = writeln!(out, "# TYPE gemla_generations_completed_total counter");
+        let _ = writeln!(
+            out,
+            "gemla_generations_completed_total {}",
+            metrics.generations_completed
+        );
+
+        metrics.node_processing.render(
+            &mut out,
+            "gemla_node_processing_seconds",
+            "How long processing a node for a single state took.",
+        );
+        metrics.file_write.render(
+            &mut out,
+            "gemla_file_write_seconds",
+            "How long updating the state file took.",
+        );
+
+        // A gauge without a value is left out rather than reported as 0
+        if let Some(best_fitness) = metrics.best_fitness {
+            let _ = writeln!(
+                out,
+                "# HELP gemla_best_fitness The highest fitness of any node in the tree."
+            );
+            let _ = writeln!(out, "# TYPE gemla_best_fitness gauge");
+            let _ = writeln!(out, "gemla_best_fitness {}", best_fitness);
+        }
+
+        out
+    }
+}
+
+#[cfg(feature = "metrics")]
+pub(crate) use server::serve;
+
+#[cfg(feature = "metrics")]
+mod server {
+    use super::Metrics;
+    use crate::error::Error;
+    use std::{
+        io::{self, BufRead, BufReader, Write},
+        net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+        sync::Arc,
+        thread,
+        time::Duration,
+    };
+    use tracing::{info, warn};
+
+    /// Serves `metrics` over HTTP at `addr` from a background thread, returning the address that was bound.
+    pub(crate) fn serve(
+        metrics: Arc<Metrics>,
+        addr: impl ToSocketAddrs,
+    ) -> Result<SocketAddr, Error> {
+        let listener = TcpListener::bind(addr)?;
+        let local = listener.local_addr()?;
+
+        thread::Builder::new()
+            .name("gemla-metrics".to_string())
+            .spawn(move || {
+                for stream in listener.incoming() {
+                    if let Err(e) = stream.and_then(|s| respond(s, &metrics)) {
+                        warn!("Unable to serve metrics: {}", e);
+                    }
+                }
+            })?;
+
+        info!("Serving metrics at http://{}", local);
+
+        Ok(local)
+    }
+
+    fn respond(mut stream: TcpStream, metrics: &Metrics) -> io::Result<()> {
+        // Every request is answered with the metrics, so the request only needs to be read up to the blank line
+        stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+        let mut reader = BufReader::new(&stream);
+        let mut line = String::new();
+        while reader.read_line(&mut line)? > 0 && line != "\r\n" && line != "\n" {
+            line.clear();
+        }
+
+        let body = metrics.render();
+        write!(
+            stream,
+            "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        )?;
+
+        stream.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let metrics = Metrics::default();
+        assert!(!metrics.render().contains("gemla_best_fitness"));
+
+        metrics.record_node(
+            GeneticState::Simulate,
+            Some(GeneticState::Score),
+            Duration::from_millis(20),
+        );
+        metrics.record_node(
+            GeneticState::Mutate,
+            Some(GeneticState::Simulate),
+            Duration::from_secs(2),
+        );
+        metrics.record_node(GeneticState::Mutate, None, Duration::from_secs(2));
+        metrics.record_file_write(Duration::from_millis(1));
+        metrics.record_tree(
+            &[
+                GeneticState::Finish,
+                GeneticState::Finish,
+                GeneticState::Initialize,
+                GeneticState::Custom(3),
+            ],
+            Some(4.5),
+        );
+
+        let rendered = metrics.render();
+        for line in [
+            "gemla_nodes{state=\"Initialize\"} 1",
+            "gemla_nodes{state=\"Simulate\"} 0",
+            "gemla_nodes{state=\"Finish\"} 2",
+            "gemla_nodes{state=\"Custom(3)\"} 1",
+            "gemla_generations_completed_total 1",
+            "gemla_node_processing_seconds_bucket{le=\"0.05\"} 1",
+            "gemla_node_processing_seconds_bucket{le=\"5\"} 3",
+            "gemla_node_processing_seconds_bucket{le=\"+Inf\"} 3",
+            "gemla_node_processing_seconds_count 3",
+            "gemla_file_write_seconds_bucket{le=\"0.005\"} 1",
+            "gemla_file_write_seconds_count 1",
+            "gemla_best_fitness 4.5",
+        ] {
+            assert!(
+                rendered.lines().any(|l| l == line),
+                "{} missing from\n{}",
+                line,
+                rendered
+            );
+        }
+    }
+}
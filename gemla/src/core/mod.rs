@@ -1,45 +1,98 @@
 //! Simulates a genetic algorithm on a population in order to improve the fit score and performance. The simulations
 //! are performed in a tournament bracket configuration so that populations can compete against each other.
+//!
+//! The futures returned here only rely on the `futures` crate and on smol's timers and channels, which run their own
+//! reactor, so they can be driven by any executor such as `smol::block_on`, `futures::executor::block_on`, async-std
+//! or tokio.
 
 pub mod genetic_node;
+pub mod journal;
+pub mod metrics;
 
-use crate::{error::Error, tree::Tree};
-use file_linked::FileLinked;
-use futures::{future, future::BoxFuture};
-use genetic_node::{GeneticNode, GeneticNodeWrapper, GeneticState};
-use log::{info, trace, warn};
-use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use crate::{
+    error::{Error, SerializableError},
+    tree::Tree,
+};
+use anyhow::{anyhow, Context};
+use file_linked::{DataFormat, FileLinked};
+use futures::{
+    future::{self, BoxFuture, Either},
+    stream::{FuturesUnordered, StreamExt},
+};
+use genetic_node::{
+    GeneticNode, GeneticNodeWrapper, GeneticState, MergeConflict, MergeContext, RecoveredNode,
+};
+use journal::{JournalEvent, JournalEventKind};
+use metrics::Metrics;
+use serde::{de::DeserializeOwned, Deserialize, Deserializer, Serialize, Serializer};
+use smol::{
+    channel::{self, Receiver, Sender},
+    Timer,
+};
 use std::{
-    collections::HashMap, fmt::Debug, fs::File, io::ErrorKind, marker::Send, mem, path::Path,
-    time::Instant,
+    cmp::max,
+    collections::{BTreeMap, HashMap, HashSet},
+    ffi::OsString,
+    fmt,
+    fmt::Debug,
+    fs::{self, File},
+    io::{BufWriter, Write},
+    marker::Send,
+    mem,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant},
 };
+use tracing::{info, info_span, instrument, trace, warn};
 use uuid::Uuid;
 
 type SimulationTree<T> = Box<Tree<GeneticNodeWrapper<T>>>;
+type SimulationData<T> = (
+    Option<SimulationTree<T>>,
+    GemlaConfig,
+    Vec<GenerationRecord>,
+);
+type NodeThread<'a, T> = (Uuid, BoxFuture<'a, Result<GeneticNodeWrapper<T>, Error>>);
 
 /// Provides configuration options for managing a [`Gemla`] object as it executes.
-/// 
+///
 /// # Examples
 /// ```
+/// # use gemla::core::{
+/// #     genetic_node::{GeneticNode, GeneticNodeContext},
+/// #     Gemla, GemlaConfig, MergeStrategy, RightBranchMode, SchedulingOrder,
+/// # };
+/// # use gemla::error::Error;
+/// # use serde::{Deserialize, Serialize};
+/// # use std::path::PathBuf;
+/// #
 /// #[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
 /// struct TestState {
 ///     pub score: f64,
 /// }
-/// 
-/// impl genetic_node::GeneticNode for TestState {
-///     fn simulate(&mut self) -> Result<(), Error> {
+///
+/// impl GeneticNode for TestState {
+///     fn simulate(&mut self, _context: GeneticNodeContext) -> Result<(), Error> {
 ///         self.score += 1.0;
 ///         Ok(())
 ///     }
-/// 
-///     fn mutate(&mut self) -> Result<(), Error> {
+///
+///     fn mutate(&mut self, _context: GeneticNodeContext) -> Result<(), Error> {
 ///         Ok(())
 ///     }
-/// 
-///     fn initialize() -> Result<Box<TestState>, Error> {
+///
+///     fn initialize(_context: GeneticNodeContext) -> Result<Box<TestState>, Error> {
 ///         Ok(Box::new(TestState { score: 0.0 }))
 ///     }
-/// 
+///
+///     fn fitness(&self) -> f64 {
+///         self.score
+///     }
+///
+///     fn population_size(&self) -> usize {
+///         1
+///     }
+///
 ///     fn merge(left: &TestState, right: &TestState) -> Result<Box<TestState>, Error> {
 ///         Ok(Box::new(if left.score > right.score {
 ///             left.clone()
@@ -48,407 +101,5855 @@ type SimulationTree<T> = Box<Tree<GeneticNodeWrapper<T>>>;
 ///         }))
 ///     }
 /// }
-/// 
-/// fn main() {
-///     
-/// }
+///
+/// # fn main() -> Result<(), Error> {
+/// let config = GemlaConfig {
+///     generations_per_node: 2,
+///     overwrite: true,
+///     fan_in: 2,
+///     repair: false,
+///     record_history: false,
+///     merge_strategy: MergeStrategy::Standard,
+///     target_population: None,
+///     max_height: None,
+///     join_deadline: None,
+///     worker_pool_size: None,
+///     iteration_scaling: None,
+///     id_seed: None,
+///     sidecar_storage: false,
+///     scheduling_order: SchedulingOrder::DepthFirst,
+///     right_branch_mode: RightBranchMode::Fresh,
+///     event_journal: false,
+///     journal_max_bytes: None,
+/// };
+///
+/// let mut gemla = Gemla::<TestState>::new(&PathBuf::from("./temp_gemla_config"), config)?;
+/// smol::block_on(gemla.simulate(2))?;
+///
+/// assert_eq!(gemla.tree_ref().unwrap().val.as_ref().unwrap().score, 4.0);
+/// #
+/// # drop(gemla);
+/// #
+/// # std::fs::remove_file("./temp_gemla_config").expect("Unable to remove file");
+/// #
+/// # Ok(())
+/// # }
 /// ```
-#[derive(Serialize, Deserialize, Copy, Clone)]
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq)]
+#[serde(default)]
 pub struct GemlaConfig {
     pub generations_per_node: u64,
     pub overwrite: bool,
+    /// The number of populations merged together at each level of the bracket. Must be at least 2.
+    pub fan_in: usize,
+    /// Whether inconsistent nodes found when loading a tree from a file should be reset to
+    /// [`GeneticState::Initialize`] so they are processed again.
+    pub repair: bool,
+    /// Whether the fitness of each node should be recorded every time it completes a generation.
+    pub record_history: bool,
+    /// How the nodes at each level of the bracket are combined once they have finished.
+    pub merge_strategy: MergeStrategy,
+    /// The population size every merged node is expected to report through [`GeneticNode::population_size`]. When
+    /// unset, merged nodes are expected to match the size of the nodes they were merged from.
+    pub target_population: Option<usize>,
+    /// The number of levels the bracket is allowed to grow to. Once the tree reaches this height [`Gemla::grow`] and
+    /// [`Gemla::simulate`] stop adding levels, so a run finishes once the capped tree has been processed.
+    pub max_height: Option<u64>,
+    /// How long joining the nodes being processed may take before giving up on the nodes that haven't finished. The
+    /// results of the finished nodes are kept and the rest are joined again later. Nodes are only checked against the
+    /// deadline between each other, a node that blocks while processing can't be interrupted.
+    pub join_deadline: Option<Duration>,
+    /// The number of nodes [`Gemla::run`] schedules before joining them, which bounds the memory used by pending nodes
+    /// regardless of how wide the tree is. Defaults to the number of CPUs when unset.
+    pub worker_pool_size: Option<usize>,
+    /// How many generations each node runs depending on its height. When unset, nodes scale linearly with
+    /// `generations_per_node`.
+    pub iteration_scaling: Option<IterationScaling>,
+    /// When set, the id of each node is a name based UUID (version 5) derived from this seed and the position the node
+    /// was created at instead of being random, so two runs with the same seed and configuration produce the same ids.
+    pub id_seed: Option<u64>,
+    /// Whether the data of each node is written to its own sidecar file instead of the state file, which then only
+    /// references it. Writing the state file stays fast when nodes hold a lot of data, and a resumed simulation only
+    /// loads the data of the nodes it processes or merges, see [`GeneticNodeWrapper::load_payload`]. Sidecar files are
+    /// kept in a `sidecars` directory next to the state file. Has no effect on simulations that are only held in memory.
+    pub sidecar_storage: bool,
+    /// Which node is processed next when more than one node can be processed.
+    pub scheduling_order: SchedulingOrder,
+    /// How the new right branch is built each time the tree grows by a level.
+    pub right_branch_mode: RightBranchMode,
+    /// Whether node state changes, merges and height increases are appended to a journal next to the state file, named
+    /// after it with `.journal` appended, so a finished run can be looked into with [`Gemla::read_journal`]. Failing
+    /// to write the journal doesn't stop the simulation. Has no effect on simulations that are only held in memory.
+    pub event_journal: bool,
+    /// The size in bytes the journal can reach before it's moved aside, named after the state file with `.journal.1`
+    /// appended, replacing the journal moved aside before it. The journal grows without limit when this is `None`.
+    pub journal_max_bytes: Option<u64>,
 }
 
-/// Creates a tournament style bracket for simulating and evaluating nodes of type `T` implementing [`GeneticNode`].
-/// These nodes are built upwards as a balanced binary tree starting from the bottom. This results in `Bracket` building
-/// a separate tree of the same height then merging trees together. Evaluating populations between nodes and taking the strongest
-/// individuals.
-///
-/// [`GeneticNode`]: genetic_node::GeneticNode
-pub struct Gemla<'a, T>
-where
-    T: Serialize + Clone,
-{
-    pub data: FileLinked<(Option<SimulationTree<T>>, GemlaConfig)>,
-    threads: HashMap<Uuid, BoxFuture<'a, Result<GeneticNodeWrapper<T>, Error>>>,
-}
-
-impl<'a, T: 'a> Gemla<'a, T>
-where
-    T: GeneticNode + Serialize + DeserializeOwned + Debug + Clone + Send,
-{
-    pub fn new(path: &Path, config: GemlaConfig) -> Result<Self, Error> {
-        match File::open(path) {
-            // If the file exists we either want to overwrite the file or read from the file 
-            // based on the configuration provided
-            Ok(_) => Ok(Gemla {
-                data: if config.overwrite {
-                    FileLinked::new((None, config), path)?
-                } else {
-                    FileLinked::from_file(path)?
-                },
-                threads: HashMap::new(),
-            }),
-            // If the file doesn't exist we must create it
-            Err(error) if error.kind() == ErrorKind::NotFound => Ok(Gemla {
-                data: FileLinked::new((None, config), path)?,
-                threads: HashMap::new(),
-            }),
-            Err(error) => Err(Error::IO(error)),
+impl Default for GemlaConfig {
+    fn default() -> Self {
+        GemlaConfig {
+            generations_per_node: 1,
+            overwrite: false,
+            fan_in: 2,
+            repair: false,
+            record_history: false,
+            merge_strategy: MergeStrategy::Standard,
+            target_population: None,
+            max_height: None,
+            join_deadline: None,
+            worker_pool_size: None,
+            iteration_scaling: None,
+            id_seed: None,
+            sidecar_storage: false,
+            scheduling_order: SchedulingOrder::DepthFirst,
+            right_branch_mode: RightBranchMode::Fresh,
+            event_journal: false,
+            journal_max_bytes: None,
         }
     }
+}
 
-    pub fn tree_ref(&self) -> Option<&SimulationTree<T>> {
-        self.data.readonly().0.as_ref()
-    }
-
-    pub async fn simulate(&mut self, steps: u64) -> Result<(), Error> {
-        // Before we can process nodes we must create blank nodes in their place to keep track of which nodes have been processed
-        // in the tree and which nodes have not.
-        self.data.mutate(|(d, c)| {
-            let mut tree: Option<SimulationTree<T>> = Gemla::increase_height(d.take(), c, steps);
-            mem::swap(d, &mut tree);
-        })?;
-
-        info!(
-            "Height of simulation tree increased to {}",
-            self.tree_ref()
-                .map(|t| format!("{}", t.height()))
-                .unwrap_or_else(|| "Tree is not defined".to_string())
-        );
-
-        loop {
-            // We need to keep simulating until the tree has been completely processed.
-            if self
-                .tree_ref()
-                .map(|t| Gemla::is_completed(t))
-                .unwrap_or(false)
-            {
-                self.join_threads().await?;
+impl GemlaConfig {
+    /// Loads a configuration from a TOML or JSON file, chosen by the file's extension. Fields
+    /// missing from the file take their values from [`GemlaConfig::default`], and keys that don't
+    /// correspond to any field are logged as a warning.
+    ///
+    /// # Examples
+    /// ```
+    /// # use gemla::core::GemlaConfig;
+    /// # use gemla::error::Error;
+    /// # use std::path::PathBuf;
+    /// #
+    /// # fn main() -> Result<(), Error> {
+    /// # std::fs::write("./temp_gemla_config.toml", "generations_per_node = 5\nfan_in = 3\n")?;
+    /// let config = GemlaConfig::from_path(&PathBuf::from("./temp_gemla_config.toml"))?;
+    ///
+    /// assert_eq!(config.generations_per_node, 5);
+    /// assert_eq!(config.fan_in, 3);
+    /// assert_eq!(config.overwrite, GemlaConfig::default().overwrite);
+    /// #
+    /// # std::fs::remove_file("./temp_gemla_config.toml")?;
+    /// #
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn from_path(path: &Path) -> Result<GemlaConfig, Error> {
+        let contents = fs::read_to_string(path)?;
+        let mut unknown = vec![];
 
-                info!("Processed tree");
-                break;
+        let config: GemlaConfig = match path.extension().and_then(|e| e.to_str()) {
+            Some("toml") => {
+                let deserializer = &mut toml::Deserializer::new(&contents);
+                serde_ignored::deserialize(deserializer, |key| unknown.push(key.to_string()))
+                    .map_err(|e| anyhow!("Unable to parse config file {}: {}", path.display(), e))?
+            }
+            Some("json") => {
+                let deserializer = &mut serde_json::Deserializer::from_str(&contents);
+                serde_ignored::deserialize(deserializer, |key| unknown.push(key.to_string()))
+                    .map_err(|e| anyhow!("Unable to parse config file {}: {}", path.display(), e))?
+            }
+            _ => {
+                return Err(Error::Other(anyhow!(
+                    "Unsupported config file {}, expected a .toml or .json extension",
+                    path.display()
+                )))
             }
+        };
 
-            if let Some(node) = self
-                .tree_ref()
-                .and_then(|t| self.get_unprocessed_node(t))
-            {
-                trace!("Adding node to process list {}", node.id());
+        if !unknown.is_empty() {
+            warn!(
+                "Ignoring unknown keys in config file {}: {}",
+                path.display(),
+                unknown.join(", ")
+            );
+        }
 
-                self.threads
-                    .insert(node.id(), Box::pin(Gemla::process_node(node)));
-            } else {
-                trace!("No node found to process, joining threads");
+        config.validate()?;
 
-                self.join_threads().await?;
-            }
+        Ok(config)
+    }
+
+    /// Combines this runtime configuration with one persisted alongside an existing tree. Runtime values take
+    /// precedence, except for `fan_in` which determines the shape of the tree that has already been built.
+    fn resume_from(&self, persisted: &GemlaConfig) -> GemlaConfig {
+        GemlaConfig {
+            fan_in: persisted.fan_in,
+            ..*self
         }
+    }
 
-        Ok(())
+    fn differing_fields(&self, other: &GemlaConfig) -> Vec<&'static str> {
+        let fields = [
+            (
+                "generations_per_node",
+                self.generations_per_node != other.generations_per_node,
+            ),
+            ("overwrite", self.overwrite != other.overwrite),
+            ("fan_in", self.fan_in != other.fan_in),
+            ("repair", self.repair != other.repair),
+            (
+                "record_history",
+                self.record_history != other.record_history,
+            ),
+            (
+                "merge_strategy",
+                self.merge_strategy != other.merge_strategy,
+            ),
+            (
+                "target_population",
+                self.target_population != other.target_population,
+            ),
+            ("max_height", self.max_height != other.max_height),
+            ("join_deadline", self.join_deadline != other.join_deadline),
+            (
+                "worker_pool_size",
+                self.worker_pool_size != other.worker_pool_size,
+            ),
+            (
+                "iteration_scaling",
+                self.iteration_scaling != other.iteration_scaling,
+            ),
+            ("id_seed", self.id_seed != other.id_seed),
+            (
+                "sidecar_storage",
+                self.sidecar_storage != other.sidecar_storage,
+            ),
+            (
+                "scheduling_order",
+                self.scheduling_order != other.scheduling_order,
+            ),
+            (
+                "right_branch_mode",
+                self.right_branch_mode != other.right_branch_mode,
+            ),
+            ("event_journal", self.event_journal != other.event_journal),
+            (
+                "journal_max_bytes",
+                self.journal_max_bytes != other.journal_max_bytes,
+            ),
+        ];
+
+        fields
+            .iter()
+            .filter(|(_, differs)| *differs)
+            .map(|(name, _)| *name)
+            .collect()
     }
 
-    async fn join_threads(&mut self) -> Result<(), Error> {
-        if !self.threads.is_empty() {
-            trace!("Joining threads for nodes {:?}", self.threads.keys());
+    /// Checks that the configuration can be used to run a simulation.
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.generations_per_node < 1 {
+            return Err(Error::Other(anyhow!(
+                "Invalid generations_per_node {}, each node must run at least one generation",
+                self.generations_per_node
+            )));
+        }
 
-            let results = future::join_all(self.threads.values_mut()).await;
-            // Converting a list of results into a result wrapping the list
-            let reduced_results: Result<Vec<GeneticNodeWrapper<T>>, Error> =
-                results.into_iter().collect();
-            self.threads.clear();
+        if self.fan_in < 2 {
+            return Err(Error::Other(anyhow!(
+                "Invalid fan_in {}, at least two nodes must be merged at each level",
+                self.fan_in
+            )));
+        }
 
-            // We need to retrieve the processed nodes from the resulting list and replace them in the original list
-            reduced_results.and_then(|r| {
-                self.data.mutate(|(d, _)| {
-                    if let Some(t) = d {
-                        let failed_nodes = Gemla::replace_nodes(t, r);
-                        // We receive a list of nodes that were unable to be found in the original tree
-                        if !failed_nodes.is_empty() {
-                            warn!(
-                                "Unable to find {:?} to replace in tree",
-                                failed_nodes.iter().map(|n| n.id())
-                            )
-                        }
+        if let Some(IterationScaling::Linear(0) | IterationScaling::Constant(0)) =
+            self.iteration_scaling
+        {
+            return Err(Error::Other(anyhow!(
+                "Invalid iteration_scaling {:?}, each node must run at least one generation",
+                self.iteration_scaling
+            )));
+        }
 
-                        // Once the nodes are replaced we need to find nodes that can be merged from the completed children nodes
-                        Gemla::merge_completed_nodes(t)
-                    } else {
-                        warn!("Unable to replce nodes {:?} in empty tree", r);
-                        Ok(())
-                    }
-                })?
-            })?;
+        if self.worker_pool_size == Some(0) {
+            return Err(Error::Other(anyhow!(
+                "Invalid worker_pool_size 0, at least one node must be processed at a time"
+            )));
         }
 
         Ok(())
     }
 
-    fn merge_completed_nodes(tree: &mut SimulationTree<T>) -> Result<(), Error> {
-        if tree.val.state() == GeneticState::Initialize {
-            match (&mut tree.left, &mut tree.right) {
-                // If the current node has been initialized, and has children nodes that are completed, then we need
-                // to merge the children nodes together into the parent node
-                (Some(l), Some(r))
-                    if l.val.state() == GeneticState::Finish
-                        && r.val.state() == GeneticState::Finish =>
-                {
-                    info!("Merging nodes {} and {}", l.val.id(), r.val.id());
-                    if let (Some(left_node), Some(right_node)) = (l.val.as_ref(), r.val.as_ref()) {
-                        let merged_node = GeneticNode::merge(left_node, right_node)?;
-                        tree.val = GeneticNodeWrapper::from(
-                            *merged_node,
-                            tree.val.max_generations(),
-                            tree.val.id(),
-                        );
-                    }
-                }
-                (Some(l), Some(r)) => {
-                    Gemla::merge_completed_nodes(l)?;
-                    Gemla::merge_completed_nodes(r)?;
-                }
-                // If there is only one child node that's completed then we want to copy it to the parent node
-                (Some(l), None) if l.val.state() == GeneticState::Finish => {
-                    trace!("Copying node {}", l.val.id());
-
-                    if let Some(left_node) = l.val.as_ref() {
-                        GeneticNodeWrapper::from(
-                            left_node.clone(),
-                            tree.val.max_generations(),
-                            tree.val.id(),
-                        );
-                    }
-                }
-                (Some(l), None) => Gemla::merge_completed_nodes(l)?,
-                (None, Some(r)) if r.val.state() == GeneticState::Finish => {
-                    trace!("Copying node {}", r.val.id());
+    /// The number of generations given to a new node standing in for `levels` levels of the bracket.
+    fn generations(&self, levels: u64) -> u64 {
+        self.iteration_scaling
+            .unwrap_or(IterationScaling::Linear(self.generations_per_node))
+            .generations(levels)
+    }
 
-                    if let Some(right_node) = r.val.as_ref() {
-                        tree.val = GeneticNodeWrapper::from(
-                            right_node.clone(),
-                            tree.val.max_generations(),
-                            tree.val.id(),
-                        );
-                    }
-                }
-                (None, Some(r)) => Gemla::merge_completed_nodes(r)?,
-                (_, _) => (),
+    /// The id of a node created at `position`, which has to be unique among every node created during a run. Random
+    /// unless [`GemlaConfig::id_seed`] is set.
+    fn node_id(&self, position: &str) -> Uuid {
+        match self.id_seed {
+            Some(seed) => {
+                // Name based ids within a namespace of their own for each seed
+                let namespace = Uuid::new_v5(&Uuid::NAMESPACE_OID, &seed.to_le_bytes());
+                Uuid::new_v5(&namespace, position.as_bytes())
             }
+            None => Uuid::new_v4(),
         }
-
-        Ok(())
     }
+}
 
-    fn get_unprocessed_node(&self, tree: &SimulationTree<T>) -> Option<GeneticNodeWrapper<T>> {
-        // If the current node has been processed or exists in the thread list then we want to stop recursing. Checking if it exists in the thread list 
-        // should be fine because we process the tree from bottom to top.
-        if tree.val.state() != GeneticState::Finish && !self.threads.contains_key(&tree.val.id()) {
-            match (&tree.left, &tree.right) {
-                // If the children are finished we can start processing the currrent node. The current node should be merged from the children already 
-                // during join_threads.
-                (Some(l), Some(r))
-                    if l.val.state() == GeneticState::Finish
-                        && r.val.state() == GeneticState::Finish => Some(tree.val.clone()),
-                (Some(l), Some(r)) => self
-                    .get_unprocessed_node(l)
-                    .or_else(|| self.get_unprocessed_node(r)),
-                (Some(l), None) => self.get_unprocessed_node(l),
-                (None, Some(r)) => self.get_unprocessed_node(r),
-                (None, None) => Some(tree.val.clone()),
-            }
-        } else {
-            None
+/// Determines how many generations a node runs depending on its height in the bracket, set with
+/// [`GemlaConfig::iteration_scaling`]. Config files use the same tags as the scaling of the earlier bracket module, for
+/// example `{"enumType": "Constant", "enumContent": 5}`, so existing files can still be read.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IterationScaling {
+    /// Nodes run this many generations for every level of the bracket they stand in for, so a leaf added next to a
+    /// branch several levels deep runs as many generations as that branch
+    Linear(u64),
+    /// Every node runs this many generations regardless of its height
+    Constant(u64),
+}
+
+impl IterationScaling {
+    /// The number of generations a node standing in for `levels` levels of the bracket runs.
+    pub fn generations(&self, levels: u64) -> u64 {
+        match self {
+            IterationScaling::Linear(n) => n * levels,
+            IterationScaling::Constant(n) => *n,
         }
     }
+}
 
-    fn replace_nodes(
-        tree: &mut SimulationTree<T>,
-        mut nodes: Vec<GeneticNodeWrapper<T>>,
-    ) -> Vec<GeneticNodeWrapper<T>> {
-        // Replacing nodes as we recurse through the tree
-        if let Some(i) = nodes.iter().position(|n| n.id() == tree.val.id()) {
-            tree.val = nodes.remove(i);
-        }
+/// The representation of [`IterationScaling`] in config files. Adjacently tagged enums can't be read by formats that
+/// aren't self describing, so the state file uses [`PlainIterationScaling`] instead.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "enumType", content = "enumContent")]
+enum TaggedIterationScaling {
+    Linear(u64),
+    Constant(u64),
+}
 
-        match (&mut tree.left, &mut tree.right) {
-            (Some(l), Some(r)) => Gemla::replace_nodes(r, Gemla::replace_nodes(l, nodes)),
-            (Some(l), None) => Gemla::replace_nodes(l, nodes),
-            (None, Some(r)) => Gemla::replace_nodes(r, nodes),
-            _ => nodes,
+#[derive(Serialize, Deserialize)]
+enum PlainIterationScaling {
+    Linear(u64),
+    Constant(u64),
+}
+
+impl Serialize for IterationScaling {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match (*self, serializer.is_human_readable()) {
+            (IterationScaling::Linear(n), true) => {
+                TaggedIterationScaling::Linear(n).serialize(serializer)
+            }
+            (IterationScaling::Constant(n), true) => {
+                TaggedIterationScaling::Constant(n).serialize(serializer)
+            }
+            (IterationScaling::Linear(n), false) => {
+                PlainIterationScaling::Linear(n).serialize(serializer)
+            }
+            (IterationScaling::Constant(n), false) => {
+                PlainIterationScaling::Constant(n).serialize(serializer)
+            }
         }
     }
+}
 
-    fn increase_height(
-        tree: Option<SimulationTree<T>>,
-        config: &GemlaConfig,
-        amount: u64,
-    ) -> Option<SimulationTree<T>> {
-        if amount == 0 {
-            tree
+impl<'de> Deserialize<'de> for IterationScaling {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if deserializer.is_human_readable() {
+            Ok(match TaggedIterationScaling::deserialize(deserializer)? {
+                TaggedIterationScaling::Linear(n) => IterationScaling::Linear(n),
+                TaggedIterationScaling::Constant(n) => IterationScaling::Constant(n),
+            })
         } else {
-            let left_branch_right =
-                tree.as_ref().map(|t| t.height() as u64).unwrap_or(0) + amount - 1;
-            
-            Some(Box::new(Tree::new(
-                GeneticNodeWrapper::new(config.generations_per_node),
-                Gemla::increase_height(tree, config, amount - 1),
-                // The right branch height has to equal the left branches total height
-                if left_branch_right > 0 {
-                    Some(Box::new(btree!(GeneticNodeWrapper::new(
-                        left_branch_right * config.generations_per_node
-                    ))))
-                } else {
-                    None
-                },
-            )))
+            Ok(match PlainIterationScaling::deserialize(deserializer)? {
+                PlainIterationScaling::Linear(n) => IterationScaling::Linear(n),
+                PlainIterationScaling::Constant(n) => IterationScaling::Constant(n),
+            })
         }
     }
+}
 
-    fn is_completed(tree: &SimulationTree<T>) -> bool {
-        // If the current node is finished, then by convention the children should all be finished as well
-        tree.val.state() == GeneticState::Finish 
-    }
-
-    async fn process_node(mut node: GeneticNodeWrapper<T>) -> Result<GeneticNodeWrapper<T>, Error> {
-        let node_state_time = Instant::now();
-        let node_state = node.state();
-
-        node.process_node()?;
-
-        trace!(
-            "{:?} completed in {:?} for {}",
-            node_state,
-            node_state_time.elapsed(),
-            node.id()
-        );
+/// The file formats the simulation tree can be exported to with [`Gemla::export`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    /// A JSON array with one object per node, including the node's data
+    Json,
+    /// A CSV file with one row per node, using [`GeneticNode::fitness`] in place of the node's data
+    Csv,
+    /// A Graphviz DOT graph with an edge from each node to its children, labelled with each node's path, state and
+    /// progress
+    Dot,
+}
 
-        if node.state() == GeneticState::Finish {
-            info!("Processed node {}", node.id());
-        }
+/// A single node of the simulation tree as written by [`Gemla::export`].
+#[derive(Serialize)]
+struct ExportRecord<'n, T> {
+    id: Uuid,
+    path: String,
+    state: GeneticState,
+    generation: u64,
+    max_generations: u64,
+    fitness: Option<f64>,
+    failure: Option<&'n SerializableError>,
+    node: Option<&'n T>,
+}
 
-        Ok(node)
-    }
+/// Determines how [`Gemla`] combines finished nodes into their parent node.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum MergeStrategy {
+    /// Merges the nodes with [`GeneticNode::merge_with_context`]
+    Standard,
+    /// Orders the nodes from highest to lowest [`GeneticNode::fitness`] before merging them with
+    /// [`GeneticNode::merge_with_context`], so the fittest nodes lead the merge
+    FitnessWeighted,
+    /// Copies the node with the highest [`GeneticNode::fitness`] into the parent without merging
+    TakeBest,
 }
 
-#[cfg(test)]
-mod tests {
-    use crate::core::*;
-    use serde::{Deserialize, Serialize};
-    use std::path::PathBuf;
-    use std::fs;
+/// Determines which node [`Gemla::run`] schedules next when more than one node can be processed.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum SchedulingOrder {
+    /// Schedules nodes in the order of a depth first traversal, visiting the left child of a node before the right one
+    DepthFirst,
+    /// Schedules the node with the highest [`GeneticNode::fitness`] first, so more promising branches make progress
+    /// before the rest. Nodes without data, such as leaves that haven't been initialized or nodes whose data is in a
+    /// sidecar file, are scheduled after the others in depth first order.
+    BestFirst,
+    /// Schedules the node with the most generations left to run first. The single node standing in for several levels
+    /// on the right of the bracket usually has the most generations, so it's started before the nodes on the left.
+    CriticalPathFirst,
+    /// Takes turns between the left and right subtrees of the root, scheduling the first node in depth first order
+    /// from the subtree after the one the previous node was scheduled from
+    RoundRobin,
+}
 
-    struct CleanUp {
-        path: PathBuf,
-    }
+/// Determines how the right branch is built when [`Gemla::grow`] adds a level above the existing tree.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum RightBranchMode {
+    /// Adds a single new node in place of the levels underneath the new root, given as many generations as those
+    /// levels together
+    Fresh,
+    /// Adds a single node in the same way as `Fresh`, but starts it from the data held by the root of the existing
+    /// tree using [`GeneticNode::clone_for_branch`], so the champion of the tree competes against a copy of itself.
+    /// Falls back to `Fresh` when the root doesn't hold any data.
+    CloneChampion,
+    /// Adds a full subtree of new nodes matching the height of the existing tree, so both branches are built from the
+    /// same number of populations
+    MirrorTree,
+}
 
-    impl CleanUp {
-        fn new(path: &Path) -> CleanUp {
-            CleanUp {
-                path: path.to_path_buf(),
-            }
-        }
+/// The fitness of a node after completing a generation, recorded when [`GemlaConfig::record_history`] is enabled.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct GenerationRecord {
+    pub id: Uuid,
+    pub generation: u64,
+    pub fitness: f64,
+}
 
-        pub fn run<F: FnOnce(&Path) -> Result<(), Error>>(&self, op: F) -> Result<(), Error> {
-            op(&self.path)
-        }
-    }
+/// The children combined into a node once they finished, see [`Gemla::merge_log`].
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct MergeRecord {
+    /// The node the children were combined into
+    pub parent: Uuid,
+    /// The finished children, in bracket order
+    pub children: Vec<Uuid>,
+    /// The children whose data the parent received. Every child contributes unless [`MergeStrategy::TakeBest`] picked
+    /// one of them, and a single child is copied into its parent as is.
+    pub contributors: Vec<Uuid>,
+}
 
-    impl Drop for CleanUp {
-        fn drop(&mut self) {
-            if self.path.exists() {
-                fs::remove_file(&self.path).expect("Unable to remove file");
+impl GenerationRecord {
+    fn from_node<T>(node: &GeneticNodeWrapper<T>) -> Option<GenerationRecord>
+    where
+        T: GeneticNode + Debug,
+    {
+        // A generation is recorded once a node reaches one of these states, which by default only happens after it has
+        // been scored. Nodes with custom states are recorded the same way, whichever states they went through before.
+        match node.state() {
+            GeneticState::Mutate | GeneticState::Finish => {
+                node.as_ref().map(|n| GenerationRecord {
+                    id: node.id(),
+                    generation: node.generation(),
+                    fitness: n.fitness(),
+                })
             }
+            _ => None,
         }
     }
+}
 
-    #[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
-    struct TestState {
-        pub score: f64,
-    }
+/// The shape of a simulation tree and the work needed to complete it, as returned by [`Gemla::plan`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimulationPlan {
+    /// The number of nodes at each level of the bracket, starting from the root
+    pub nodes_per_level: Vec<usize>,
+    /// Every node that will be processed, excluding group nodes
+    pub nodes: Vec<PlannedNode>,
+    /// The sum of the max generations of every node
+    pub total_generations: u64,
+}
 
-    impl genetic_node::GeneticNode for TestState {
-        fn simulate(&mut self) -> Result<(), Error> {
-            self.score += 1.0;
-            Ok(())
-        }
+/// A single node within a [`SimulationPlan`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlannedNode {
+    pub path: String,
+    pub max_generations: u64,
+}
 
-        fn mutate(&mut self) -> Result<(), Error> {
-            Ok(())
+impl fmt::Display for SimulationPlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Levels: {}", self.nodes_per_level.len())?;
+        for (level, count) in self.nodes_per_level.iter().enumerate() {
+            writeln!(f, "  Level {}: {} node(s)", level, count)?;
         }
 
-        fn initialize() -> Result<Box<TestState>, Error> {
-            Ok(Box::new(TestState { score: 0.0 }))
+        writeln!(f, "Nodes:")?;
+        for node in self.nodes.iter() {
+            writeln!(f, "  {}: {} generation(s)", node.path, node.max_generations)?;
         }
 
-        fn merge(left: &TestState, right: &TestState) -> Result<Box<TestState>, Error> {
-            Ok(Box::new(if left.score > right.score {
-                left.clone()
-            } else {
-                right.clone()
-            }))
-        }
+        write!(f, "Total generations: {}", self.total_generations)
     }
+}
 
-    #[test]
-    fn test_new() -> Result<(), Error> {
-        let path = PathBuf::from("test_new_non_existing");
-        CleanUp::new(&path).run(|p| {
-            assert!(!path.exists());
+/// A summary of the progress of a simulation, as returned by [`Gemla::report`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimulationReport {
+    /// The height of the simulation tree, or 0 if the tree hasn't been grown yet
+    pub height: usize,
+    /// The number of nodes in the tree, excluding group nodes
+    pub nodes: usize,
+    /// The number of nodes that have finished processing
+    pub finished: usize,
+    /// The number of nodes whose last processing step failed
+    pub failed: usize,
+    /// Whether the root of the tree has finished processing
+    pub completed: bool,
+    /// The highest [`GeneticNode::fitness`] of any node holding data, or `None` if no node holds data yet
+    pub best_fitness: Option<f64>,
+}
 
-            // Testing initial creation
-            let mut config = GemlaConfig {
-                generations_per_node: 1,
-                overwrite: true
-            };
-            let mut gemla = Gemla::<TestState>::new(&p, config)?;
+impl fmt::Display for SimulationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Height: {}", self.height)?;
+        writeln!(f, "Nodes: {}/{} finished", self.finished, self.nodes)?;
+        writeln!(f, "Failed: {}", self.failed)?;
+        writeln!(f, "Completed: {}", self.completed)?;
+        write!(
+            f,
+            "Best fitness: {}",
+            self.best_fitness
+                .map(|b| b.to_string())
+                .unwrap_or_else(|| "-".to_string())
+        )
+    }
+}
 
-            smol::block_on(gemla.simulate(2))?;
-            assert_eq!(gemla.data.readonly().0.as_ref().unwrap().height(), 2);
-            
-            drop(gemla);
-            assert!(path.exists());
+/// Limits how much work is done by a call to [`Gemla::run`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RunBudget {
+    /// Run until the tree is completed
+    Unbounded,
+    /// Stop once the given number of nodes have finished
+    Nodes(u64),
+    /// Stop scheduling new work once the given amount of time has passed
+    Duration(Duration),
+}
 
-            // Testing overwriting data
-            let mut gemla = Gemla::<TestState>::new(&p, config)?;
+/// An inconsistency found in a simulation tree by [`Gemla::validate_tree`]. Paths give the directions taken from the
+/// root to reach the node, for example `root/L/R`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TreeInvariantViolation {
+    /// More than one node in the tree uses the same id
+    DuplicateId { id: Uuid, path: String },
+    /// A node has started processing before all of its children have finished
+    ParentAheadOfChildren {
+        id: Uuid,
+        path: String,
+        state: GeneticState,
+    },
+    /// A node has advanced past its maximum number of generations
+    GenerationExceedsMax {
+        id: Uuid,
+        path: String,
+        generation: u64,
+        max_generations: u64,
+    },
+}
 
-            smol::block_on(gemla.simulate(2))?;
-            assert_eq!(gemla.data.readonly().0.as_ref().unwrap().height(), 2);
+impl fmt::Display for TreeInvariantViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TreeInvariantViolation::DuplicateId { id, path } => {
+                write!(f, "Duplicate id {} for node at {}", id, path)
+            }
+            TreeInvariantViolation::ParentAheadOfChildren { id, path, state } => write!(
+                f,
+                "Node {} at {} is in state {:?} before its children have finished",
+                id, path, state
+            ),
+            TreeInvariantViolation::GenerationExceedsMax {
+                id,
+                path,
+                generation,
+                max_generations,
+            } => write!(
+                f,
+                "Node {} at {} is on generation {} which exceeds its max generations {}",
+                id, path, generation, max_generations
+            ),
+        }
+    }
+}
+
+/// Creates a tournament style bracket for simulating and evaluating nodes of type `T` implementing [`GeneticNode`].
+/// These nodes are built upwards as a balanced binary tree starting from the bottom. This results in `Gemla` building
+/// a separate tree of the same height then merging trees together. Evaluating populations between nodes and taking the strongest
+/// individuals.
+///
+/// Node data is cloned when a node is scheduled to be processed, when a single child is copied into its parent and
+/// when [`MergeStrategy::TakeBest`] picks a node. Merging, planning and reusing existing data work on references or move
+/// the data instead, so types that are expensive to clone are only cloned once per processing step. A single child
+/// keeps its own copy rather than having its data moved into the parent, since a finished node without data is
+/// rejected as corrupt when the simulation is loaded, and exports and [`Gemla::finished_nodes`] still report the child.
+///
+/// Nodes are scheduled in the order of a depth first traversal that visits the left child of a node before the right
+/// one, and the results of processed nodes are applied to the tree in the same order no matter which node finished
+/// first. Together with [`GemlaConfig::id_seed`] this makes runs reproducible. Other [`SchedulingOrder`]s pick the
+/// node to process next in a different way, still in a reproducible order.
+///
+/// [`GeneticNode`]: genetic_node::GeneticNode
+pub struct Gemla<'a, T>
+where
+    T: Serialize + Clone,
+{
+    pub data: FileLinked<(
+        Option<SimulationTree<T>>,
+        GemlaConfig,
+        Vec<GenerationRecord>,
+    )>,
+    /// The nodes being processed, keyed by their path in the tree so they're joined in traversal order
+    threads: BTreeMap<String, NodeThread<'a, T>>,
+    progress: Vec<Sender<SimulationReport>>,
+    metrics: Arc<Metrics>,
+    merge_log: Vec<MergeRecord>,
+    /// The path of the node scheduled most recently, used by [`SchedulingOrder::RoundRobin`]
+    last_scheduled: Option<String>,
+}
+
+impl<'a, T: 'a> Gemla<'a, T>
+where
+    T: GeneticNode + Serialize + DeserializeOwned + Debug + Clone + Send,
+{
+    pub fn new(path: &Path, config: GemlaConfig) -> Result<Self, Error> {
+        config.validate()?;
+
+        if config.overwrite {
+            return Ok(Gemla {
+                data: FileLinked::new((None, config, vec![]), path)?,
+                threads: BTreeMap::new(),
+                progress: vec![],
+                metrics: Arc::default(),
+                merge_log: vec![],
+                last_scheduled: None,
+            });
+        }
+
+        let data = match FileLinked::from_file(path) {
+            Ok(data) => data,
+            // If the file doesn't exist we must create it
+            Err(file_linked::error::Error::NotFound(_)) => {
+                return Ok(Gemla {
+                    data: FileLinked::new((None, config, vec![]), path)?,
+                    threads: BTreeMap::new(),
+                    progress: vec![],
+                    metrics: Arc::default(),
+                    merge_log: vec![],
+                    last_scheduled: None,
+                })
+            }
+            // Files that exist but can't be read are never replaced, to avoid losing a previous run
+            Err(error) => return Err(error.into()),
+        };
+
+        let mut gemla = Gemla {
+            data,
+            threads: BTreeMap::new(),
+            progress: vec![],
+            metrics: Arc::default(),
+            merge_log: vec![],
+            last_scheduled: None,
+        };
+
+        // Trees that can't be processed at all are rejected rather than repaired
+        if let Some(reason) = gemla
+            .tree_ref()
+            .and_then(|t| Gemla::find_corruption(t, "root".to_string()))
+        {
+            return Err(Error::CorruptTree { reason });
+        }
+
+        gemla.resume_config(config)?;
+
+        // Trees written by previous runs may not be consistent, so they are checked before being used
+        gemla.check_tree(config.repair)?;
+
+        Ok(gemla)
+    }
+
+    /// Loads the simulation at `path` without linking it to the file, so the state file is never written to. The
+    /// configuration saved with the simulation is used and the tree is neither repaired nor grown. Used to inspect a
+    /// simulation, possibly while another process is running it.
+    pub fn open_read_only(path: &Path) -> Result<Self, Error> {
+        Gemla::open_read_only_with_format(path, DataFormat::Bincode)
+    }
+
+    /// Loads the simulation at `path` in the same way as [`Gemla::open_read_only`], from a file written in the given
+    /// `format`, such as a checkpoint converted to JSON.
+    pub fn open_read_only_with_format(path: &Path, format: DataFormat) -> Result<Self, Error> {
+        let data = FileLinked::<SimulationData<T>>::from_file_unlinked(path, format)?;
+
+        if let Some(reason) = data
+            .readonly()
+            .0
+            .as_ref()
+            .and_then(|t| Gemla::find_corruption(t, "root".to_string()))
+        {
+            return Err(Error::CorruptTree { reason });
+        }
+
+        Ok(Gemla {
+            data,
+            threads: BTreeMap::new(),
+            progress: vec![],
+            metrics: Arc::default(),
+            merge_log: vec![],
+            last_scheduled: None,
+        })
+    }
+
+    /// Reads the events recorded in the journal of the simulation stored at `path`, oldest first, see
+    /// [`GemlaConfig::event_journal`]. Returns no events if the simulation didn't keep a journal.
+    pub fn read_journal(path: &Path) -> Result<Vec<JournalEvent>, Error> {
+        journal::read(path)
+    }
+
+    /// Writes the current state of the simulation to a separate file at `path`, leaving the live file untouched. The
+    /// snapshot can be loaded later with [`Gemla::restore`] to roll back to this point. Nodes that are being processed
+    /// are saved as they were before processing started.
+    pub fn snapshot(&self, path: &Path) -> Result<(), Error> {
+        // Sidecar files are removed once the live tree stops referencing them, so snapshots hold the data themselves
+        let mut data = self.data.readonly().clone();
+        if let Some(t) = &mut data.0 {
+            Gemla::inline_payloads(t)?;
+        }
+
+        FileLinked::new(data, path)?.close()?;
+
+        info!("Saved snapshot of simulation to {}", path.display());
+
+        Ok(())
+    }
+
+    /// Loads a snapshot written by [`Gemla::snapshot`] and makes it the live state of the simulation at `path`,
+    /// replacing anything already written there. The configuration saved in the snapshot is used.
+    pub fn restore(snapshot: &Path, path: &Path) -> Result<Self, Error> {
+        let data = Gemla::load_snapshot(snapshot)?;
+
+        let repair = data.1.repair;
+        let mut gemla = Gemla {
+            data: FileLinked::new(data, path)?,
+            threads: BTreeMap::new(),
+            progress: vec![],
+            metrics: Arc::default(),
+            merge_log: vec![],
+            last_scheduled: None,
+        };
+        gemla.check_tree(repair)?;
+
+        info!("Restored simulation from snapshot {}", snapshot.display());
+
+        Ok(gemla)
+    }
+
+    fn load_snapshot(snapshot: &Path) -> Result<SimulationData<T>, Error> {
+        let data = FileLinked::<SimulationData<T>>::from_file(snapshot)?
+            .readonly()
+            .clone();
+
+        // The live file is only replaced once the snapshot is known to be usable
+        if let Some(reason) = data
+            .0
+            .as_ref()
+            .and_then(|t| Gemla::find_corruption(t, "root".to_string()))
+        {
+            return Err(Error::CorruptTree { reason });
+        }
+
+        Ok(data)
+    }
+
+    /// Saves the current state of the simulation as a checkpoint called `name`, which can be rolled back to with
+    /// [`Gemla::rollback_to`]. Checkpoints are kept in a `checkpoints` directory next to the live file. Characters
+    /// other than letters, digits, `-` and `_` are replaced with `_`, and names that are already taken are rejected.
+    pub fn tag_checkpoint(&mut self, name: &str) -> Result<(), Error> {
+        let path = self.checkpoint_path(name)?;
+        if path.exists() {
+            return Err(Error::Other(anyhow!(
+                "Checkpoint {} already exists at {}",
+                name,
+                path.display()
+            )));
+        }
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        self.snapshot(&path)
+    }
+
+    /// Returns the names of the checkpoints saved with [`Gemla::tag_checkpoint`], in alphabetical order.
+    pub fn list_checkpoints(&self) -> Result<Vec<String>, Error> {
+        let dir = self.checkpoint_dir()?;
+        if !dir.exists() {
+            return Ok(vec![]);
+        }
+
+        let mut names = fs::read_dir(dir)?
+            .map(|entry| entry.map(|e| e.file_name().to_string_lossy().into_owned()))
+            .collect::<Result<Vec<String>, _>>()?;
+        names.sort();
+
+        Ok(names)
+    }
+
+    /// Replaces the live state of the simulation with the checkpoint called `name` and persists it. Nodes can't be in
+    /// the middle of being processed.
+    pub fn rollback_to(&mut self, name: &str) -> Result<(), Error> {
+        if !self.is_idle() {
+            return Err(Error::Other(anyhow!(
+                "Unable to roll back to checkpoint {} while nodes {:?} are being processed",
+                name,
+                self.nodes_in_flight()
+            )));
+        }
+
+        let data = Gemla::load_snapshot(&self.checkpoint_path(name)?)?;
+        let repair = data.1.repair;
+        self.data.replace(data)?;
+        self.check_tree(repair)?;
+
+        info!("Rolled back simulation to checkpoint {}", name);
+
+        Ok(())
+    }
+
+    fn checkpoint_dir(&self) -> Result<PathBuf, Error> {
+        let path = self
+            .data
+            .path()
+            .ok_or_else(|| anyhow!("Checkpoints require a simulation linked to a file"))?;
+        let file_name = path
+            .file_name()
+            .ok_or_else(|| anyhow!("Unable to get filename for {}", path.display()))?;
+
+        Ok(path
+            .parent()
+            .unwrap_or_else(|| Path::new(""))
+            .join("checkpoints")
+            .join(file_name))
+    }
+
+    fn checkpoint_path(&self, name: &str) -> Result<PathBuf, Error> {
+        let sanitized = name
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect::<String>();
+
+        if sanitized.is_empty() {
+            return Err(Error::Other(anyhow!("Checkpoint names can't be empty")));
+        }
+
+        Ok(self.checkpoint_dir()?.join(sanitized))
+    }
+
+    fn sidecar_dir(&self) -> Result<Option<PathBuf>, Error> {
+        let path = match self.data.path() {
+            Some(path) if self.effective_config().sidecar_storage => path,
+            _ => return Ok(None),
+        };
+        let file_name = path
+            .file_name()
+            .ok_or_else(|| anyhow!("Unable to get filename for {}", path.display()))?;
+
+        Ok(Some(
+            path.parent()
+                .unwrap_or_else(|| Path::new(""))
+                .join("sidecars")
+                .join(file_name),
+        ))
+    }
+
+    /// Appends `events` to the journal when the simulation keeps one. The journal is only for looking into a run
+    /// afterwards, so failing to write it is logged rather than stopping the simulation.
+    fn write_journal(&self, events: &[JournalEvent]) {
+        let config = self.effective_config();
+        let path = match self.data.path() {
+            Some(path) if config.event_journal => path,
+            _ => return,
+        };
+
+        if let Err(e) = journal::append(path, config.journal_max_bytes, events) {
+            warn!("Unable to write to journal: {}", e);
+        }
+    }
+
+    /// Modifies the simulation with `op` and writes it to the state file. With sidecar storage the data of new and
+    /// processed nodes is written to sidecar files first, so the state file only references it, and sidecar files the
+    /// tree no longer references are removed once the state file has been written.
+    fn mutate_data<U>(&mut self, op: impl FnOnce(&mut SimulationData<T>) -> U) -> Result<U, Error> {
+        let dir = self.sidecar_dir()?;
+        if let Some(dir) = &dir {
+            fs::create_dir_all(dir)?;
+        }
+
+        let (result, stored) = self.data.mutate(|data| {
+            let result = op(data);
+            let stored = match (&dir, &mut data.0) {
+                (Some(dir), Some(t)) => Gemla::store_payloads(t, dir),
+                _ => Ok(()),
+            };
+
+            (result, stored)
+        })?;
+        stored?;
+
+        if let Some(dir) = dir {
+            // The previous sidecar files are needed until the state file referencing the new ones is in place
+            self.data.flush()?;
+            self.remove_stale_payloads(&dir)?;
+        }
+
+        Ok(result)
+    }
+
+    fn store_payloads(tree: &mut SimulationTree<T>, dir: &Path) -> Result<(), Error> {
+        tree.val.store_payload(dir)?;
+
+        if let Some(l) = &mut tree.left {
+            Gemla::store_payloads(l, dir)?;
+        }
+        if let Some(r) = &mut tree.right {
+            Gemla::store_payloads(r, dir)?;
+        }
+
+        Ok(())
+    }
+
+    fn inline_payloads(tree: &mut SimulationTree<T>) -> Result<(), Error> {
+        tree.val.inline_payload()?;
+
+        if let Some(l) = &mut tree.left {
+            Gemla::inline_payloads(l)?;
+        }
+        if let Some(r) = &mut tree.right {
+            Gemla::inline_payloads(r)?;
+        }
+
+        Ok(())
+    }
+
+    fn remove_stale_payloads(&self, dir: &Path) -> Result<(), Error> {
+        let mut referenced = HashSet::new();
+        if let Some(t) = self.tree_ref() {
+            Gemla::collect_payloads(t, &mut referenced);
+        }
+
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            if !referenced.contains(&entry.file_name()) {
+                trace!("Removing stale sidecar file {}", entry.path().display());
+                fs::remove_file(entry.path())?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn collect_payloads(tree: &SimulationTree<T>, referenced: &mut HashSet<OsString>) {
+        if let Some(name) = tree.val.payload_path().and_then(|p| p.file_name()) {
+            referenced.insert(name.to_os_string());
+        }
+
+        if let Some(l) = &tree.left {
+            Gemla::collect_payloads(l, referenced);
+        }
+        if let Some(r) = &tree.right {
+            Gemla::collect_payloads(r, referenced);
+        }
+    }
+
+    /// Returns the configuration the simulation is running with. When resuming from a file this is the configuration
+    /// passed to [`Gemla::new`], except for fields that must keep their persisted values to stay consistent with the
+    /// existing tree.
+    pub fn effective_config(&self) -> GemlaConfig {
+        self.data.readonly().1
+    }
+
+    fn resume_config(&mut self, runtime: GemlaConfig) -> Result<(), Error> {
+        let persisted = self.effective_config();
+        let effective = runtime.resume_from(&persisted);
+
+        let changed = persisted.differing_fields(&effective);
+        if !changed.is_empty() {
+            warn!(
+                "Runtime config overrides persisted config for fields: {}",
+                changed.join(", ")
+            );
+        }
+
+        let kept = runtime.differing_fields(&effective);
+        if !kept.is_empty() {
+            warn!(
+                "Keeping persisted values to stay consistent with the existing tree for fields: {}",
+                kept.join(", ")
+            );
+        }
+
+        self.data.mutate(|(_, c, _)| *c = effective)?;
+
+        Ok(())
+    }
+
+    /// Writes every node in the simulation tree to the file at `path` for analysis outside of gemla. The state file is
+    /// only read from.
+    pub fn export(&self, path: &Path, format: ExportFormat) -> Result<(), Error> {
+        let mut records = vec![];
+        if let Some(t) = self.tree_ref() {
+            Gemla::export_records(t, "root".to_string(), &mut records);
+        }
+
+        let mut writer = BufWriter::new(File::create(path)?);
+
+        match format {
+            ExportFormat::Json => serde_json::to_writer_pretty(&mut writer, &records)
+                .map_err(|e| anyhow!("Unable to export tree to {}: {}", path.display(), e))?,
+            ExportFormat::Csv => {
+                writeln!(
+                    writer,
+                    "id,path,state,generation,max_generations,fitness,failure"
+                )?;
+
+                for r in records.iter() {
+                    writeln!(
+                        writer,
+                        "{},{},{:?},{},{},{},{}",
+                        r.id,
+                        r.path,
+                        r.state,
+                        r.generation,
+                        r.max_generations,
+                        r.fitness.map(|f| f.to_string()).unwrap_or_default(),
+                        // Failure messages are quoted since they can contain commas
+                        r.failure
+                            .map(|f| format!("\"{}\"", f.message.replace('"', "\"\"")))
+                            .unwrap_or_default()
+                    )?;
+                }
+            }
+            ExportFormat::Dot => {
+                writeln!(writer, "digraph gemla {{")?;
+
+                let ids = records
+                    .iter()
+                    .map(|r| (r.path.as_str(), r.id))
+                    .collect::<HashMap<&str, Uuid>>();
+
+                for r in records.iter() {
+                    writeln!(
+                        writer,
+                        "    \"{}\" [label=\"{}\\n{:?} {}/{}\"];",
+                        r.id, r.path, r.state, r.generation, r.max_generations
+                    )?;
+
+                    // The parent of each node is found from the directions taken to reach it
+                    if let Some(parent) = r.path.rsplit_once('/').and_then(|(p, _)| ids.get(p)) {
+                        writeln!(writer, "    \"{}\" -> \"{}\";", parent, r.id)?;
+                    }
+                }
+
+                writeln!(writer, "}}")?;
+            }
+        }
+
+        writer.flush()?;
+
+        info!("Exported {} nodes to {}", records.len(), path.display());
+
+        Ok(())
+    }
+
+    fn export_records<'n>(
+        tree: &'n SimulationTree<T>,
+        path: String,
+        records: &mut Vec<ExportRecord<'n, T>>,
+    ) {
+        let node = &tree.val;
+
+        records.push(ExportRecord {
+            id: node.id(),
+            path: path.clone(),
+            state: node.state(),
+            generation: node.generation(),
+            max_generations: node.max_generations(),
+            fitness: node.as_ref().map(|n| n.fitness()),
+            failure: node.failure(),
+            node: node.as_ref(),
+        });
+
+        if let Some(l) = &tree.left {
+            Gemla::export_records(l, format!("{}/L", path), records);
+        }
+        if let Some(r) = &tree.right {
+            Gemla::export_records(r, format!("{}/R", path), records);
+        }
+    }
+
+    /// Creates a new simulation that is only held in memory, without reading from or writing to a file. Useful for tests
+    /// and benchmarks where the state doesn't need to be kept.
+    pub fn in_memory(config: GemlaConfig) -> Result<Self, Error> {
+        config.validate()?;
+
+        Ok(Gemla {
+            data: FileLinked::in_memory((None, config, vec![])),
+            threads: BTreeMap::new(),
+            progress: vec![],
+            metrics: Arc::default(),
+            merge_log: vec![],
+            last_scheduled: None,
+        })
+    }
+
+    /// Renders the simulation tree as an indented outline with one line per node, showing the start of each node's id,
+    /// its state and its progress through its generations.
+    pub fn print_tree(&self) -> String {
+        let mut lines = vec![];
+
+        if let Some(t) = self.tree_ref() {
+            Gemla::print_node(t, "", 0, &mut lines);
+        }
+
+        lines.join("\n")
+    }
+
+    fn print_node(tree: &SimulationTree<T>, label: &str, depth: usize, lines: &mut Vec<String>) {
+        let node = &tree.val;
+        let id = node.id().to_string();
+        let description = if node.is_group() {
+            "Group".to_string()
+        } else {
+            format!(
+                "{:?} {}/{}",
+                node.state(),
+                node.generation(),
+                node.max_generations()
+            )
+        };
+
+        let failure = node
+            .failure()
+            .map(|f| format!(" failed: {}", f.message))
+            .unwrap_or_default();
+
+        lines.push(format!(
+            "{}{}{} {}{}",
+            "  ".repeat(depth),
+            label,
+            &id[..8],
+            description,
+            failure
+        ));
+
+        if let Some(l) = &tree.left {
+            Gemla::print_node(l, "L ", depth + 1, lines);
+        }
+        if let Some(r) = &tree.right {
+            Gemla::print_node(r, "R ", depth + 1, lines);
+        }
+    }
+
+    /// Summarizes the progress of the simulation tree without processing it.
+    pub fn report(&self) -> SimulationReport {
+        let mut report = SimulationReport {
+            height: self.tree_ref().map(|t| t.height()).unwrap_or(0),
+            nodes: 0,
+            finished: 0,
+            failed: 0,
+            completed: self
+                .tree_ref()
+                .map(|t| Gemla::is_completed(t))
+                .unwrap_or(false),
+            best_fitness: None,
+        };
+
+        if let Some(t) = self.tree_ref() {
+            Gemla::report_nodes(t, &mut report);
+        }
+
+        report
+    }
+
+    fn report_nodes(tree: &SimulationTree<T>, report: &mut SimulationReport) {
+        if !tree.val.is_group() {
+            report.nodes += 1;
+
+            if tree.val.state() == GeneticState::Finish {
+                report.finished += 1;
+            }
+            if tree.val.failure().is_some() {
+                report.failed += 1;
+            }
+        }
+
+        if let Some(fitness) = tree.val.as_ref().map(|n| n.fitness()) {
+            report.best_fitness = Some(report.best_fitness.map_or(fitness, |b| b.max(fitness)));
+        }
+
+        if let Some(l) = &tree.left {
+            Gemla::report_nodes(l, report);
+        }
+        if let Some(r) = &tree.right {
+            Gemla::report_nodes(r, report);
+        }
+    }
+
+    /// Returns a channel that receives a [`SimulationReport`] every time the tree is grown and every time the nodes
+    /// being processed are joined. The channel is closed once the simulation is dropped or shut down.
+    pub fn subscribe_progress(&mut self) -> Receiver<SimulationReport> {
+        let (sender, receiver) = channel::unbounded();
+        self.progress.push(sender);
+
+        receiver
+    }
+
+    fn publish_progress(&mut self) {
+        let report = self.report();
+
+        let mut states = vec![];
+        if let Some(t) = self.tree_ref() {
+            Gemla::node_states(t, &mut states);
+        }
+        self.metrics.record_tree(&states, report.best_fitness);
+
+        // Subscribers that dropped their receiver are forgotten
+        self.progress
+            .retain(|sender| sender.try_send(report.clone()).is_ok());
+    }
+
+    fn node_states(tree: &SimulationTree<T>, states: &mut Vec<GeneticState>) {
+        if !tree.val.is_group() {
+            states.push(tree.val.state());
+        }
+
+        if let Some(l) = &tree.left {
+            Gemla::node_states(l, states);
+        }
+        if let Some(r) = &tree.right {
+            Gemla::node_states(r, states);
+        }
+    }
+
+    /// Returns the metrics collected while growing and processing the simulation.
+    pub fn metrics(&self) -> Arc<Metrics> {
+        Arc::clone(&self.metrics)
+    }
+
+    /// Serves the [`Metrics`] of the simulation in the Prometheus text format over HTTP at `addr`, returning the
+    /// address that was bound so port 0 can be used. Requests are answered from a background thread for as long as
+    /// the program runs.
+    #[cfg(feature = "metrics")]
+    pub fn serve_metrics(
+        &self,
+        addr: impl std::net::ToSocketAddrs,
+    ) -> Result<std::net::SocketAddr, Error> {
+        metrics::serve(self.metrics(), addr)
+    }
+
+    /// Returns the ids of the nodes that are currently being processed.
+    pub fn nodes_in_flight(&self) -> Vec<Uuid> {
+        self.threads.values().map(|(id, _)| *id).collect()
+    }
+
+    fn is_in_flight(&self, id: Uuid) -> bool {
+        self.threads.values().any(|(i, _)| *i == id)
+    }
+
+    /// Returns whether no nodes are currently being processed, in which case it's safe to snapshot the state file or
+    /// shut down.
+    pub fn is_idle(&self) -> bool {
+        self.threads.is_empty()
+    }
+
+    /// Shuts the simulation down gracefully, waiting for the nodes being processed to finish so their results are
+    /// persisted, then waiting for the state file to be written. Errors from writing the state file are returned
+    /// rather than logged.
+    pub async fn shutdown(mut self) -> Result<(), Error> {
+        // Nodes left over by a join deadline are joined again until none are left
+        while !self.is_idle() {
+            self.join_threads().await?;
+        }
+
+        self.data.close()?;
+
+        info!("Shut down simulation");
+
+        Ok(())
+    }
+
+    pub fn tree_ref(&self) -> Option<&SimulationTree<T>> {
+        self.data.readonly().0.as_ref()
+    }
+
+    /// Returns the fitness of every generation completed while [`GemlaConfig::record_history`] was enabled, in the
+    /// order the generations were completed.
+    pub fn export_history(&self) -> Vec<GenerationRecord> {
+        self.data.readonly().2.clone()
+    }
+
+    /// Returns the merges performed since the simulation was loaded, in the order they happened. The log isn't written
+    /// to the state file.
+    pub fn merge_log(&self) -> &[MergeRecord] {
+        &self.merge_log
+    }
+
+    /// Returns the id and data of every node in the tree that has finished processing, in depth first order starting
+    /// from the root.
+    pub fn finished_nodes(&self) -> impl Iterator<Item = (Uuid, &T)> {
+        let mut nodes = vec![];
+
+        if let Some(t) = self.tree_ref() {
+            Gemla::collect_finished(t, &mut nodes);
+        }
+
+        nodes.into_iter()
+    }
+
+    fn collect_finished<'t>(tree: &'t SimulationTree<T>, nodes: &mut Vec<(Uuid, &'t T)>) {
+        if tree.val.state() == GeneticState::Finish {
+            if let Some(n) = tree.val.as_ref() {
+                nodes.push((tree.val.id(), n));
+            }
+        }
+
+        if let Some(l) = &tree.left {
+            Gemla::collect_finished(l, nodes);
+        }
+        if let Some(r) = &tree.right {
+            Gemla::collect_finished(r, nodes);
+        }
+    }
+
+    /// Walks the simulation tree and returns every [`TreeInvariantViolation`] found. This is run automatically when a
+    /// tree is read from a file.
+    pub fn validate_tree(&self) -> Vec<TreeInvariantViolation> {
+        let mut violations = vec![];
+
+        if let Some(tree) = self.tree_ref() {
+            Gemla::find_violations(
+                tree,
+                "root".to_string(),
+                &mut HashSet::new(),
+                &mut violations,
+            );
+        }
+
+        violations
+    }
+
+    fn check_tree(&mut self, repair: bool) -> Result<(), Error> {
+        let violations = self.validate_tree();
+
+        for violation in violations.iter() {
+            warn!("Tree invariant violated: {}", violation);
+        }
+
+        if repair && !violations.is_empty() {
+            info!("Repairing simulation tree");
+
+            self.mutate_data(|(d, _, _)| {
+                if let Some(t) = d {
+                    Gemla::repair_tree(t, "root".to_string());
+                }
+            })?;
+        }
+
+        Ok(())
+    }
+
+    fn find_corruption(tree: &SimulationTree<T>, path: String) -> Option<String> {
+        let node = &tree.val;
+
+        if node.is_group() {
+            if path == "root" {
+                return Some("The root node is a group node".to_string());
+            }
+
+            if tree.left.is_none() && tree.right.is_none() {
+                return Some(format!(
+                    "Group node {} at {} has no members",
+                    node.id(),
+                    path
+                ));
+            }
+        } else if node.state() != GeneticState::Initialize && !node.has_data() {
+            return Some(format!(
+                "Node {} at {} is in state {:?} without any data",
+                node.id(),
+                path,
+                node.state()
+            ));
+        }
+
+        tree.left
+            .as_ref()
+            .and_then(|l| Gemla::find_corruption(l, format!("{}/L", path)))
+            .or_else(|| {
+                tree.right
+                    .as_ref()
+                    .and_then(|r| Gemla::find_corruption(r, format!("{}/R", path)))
+            })
+    }
+
+    fn find_violations(
+        tree: &SimulationTree<T>,
+        path: String,
+        ids: &mut HashSet<Uuid>,
+        violations: &mut Vec<TreeInvariantViolation>,
+    ) {
+        let node = &tree.val;
+
+        if !ids.insert(node.id()) {
+            violations.push(TreeInvariantViolation::DuplicateId {
+                id: node.id(),
+                path: path.clone(),
+            });
+        }
+
+        if !node.is_group() {
+            if Gemla::is_ahead_of_children(tree) {
+                violations.push(TreeInvariantViolation::ParentAheadOfChildren {
+                    id: node.id(),
+                    path: path.clone(),
+                    state: node.state(),
+                });
+            }
+
+            if node.generation() > node.max_generations() {
+                violations.push(TreeInvariantViolation::GenerationExceedsMax {
+                    id: node.id(),
+                    path: path.clone(),
+                    generation: node.generation(),
+                    max_generations: node.max_generations(),
+                });
+            }
+        }
+
+        if let Some(l) = &tree.left {
+            Gemla::find_violations(l, format!("{}/L", path), ids, violations);
+        }
+        if let Some(r) = &tree.right {
+            Gemla::find_violations(r, format!("{}/R", path), ids, violations);
+        }
+    }
+
+    fn repair_tree(tree: &mut SimulationTree<T>, path: String) {
+        // Children are repaired first so that resetting a child also resets any parent depending on it
+        if let Some(l) = &mut tree.left {
+            Gemla::repair_tree(l, format!("{}/L", path));
+        }
+        if let Some(r) = &mut tree.right {
+            Gemla::repair_tree(r, format!("{}/R", path));
+        }
+
+        if !tree.val.is_group() && Gemla::is_ahead_of_children(tree) {
+            warn!("Resetting node {} at {}", tree.val.id(), path);
+            tree.val.reset();
+        }
+    }
+
+    fn is_ahead_of_children(tree: &SimulationTree<T>) -> bool {
+        // A node can't finish before its children have finished, and can't be processed before its children have
+        // started. Nodes can be processed alongside their children after their generations are extended.
+        match tree.val.state() {
+            GeneticState::Initialize => false,
+            GeneticState::Finish => !Gemla::children_finished(tree),
+            _ => !Gemla::children_started(tree),
+        }
+    }
+
+    fn children_started(tree: &SimulationTree<T>) -> bool {
+        tree.left
+            .as_ref()
+            .map(|l| Gemla::is_started(l))
+            .unwrap_or(true)
+            && tree
+                .right
+                .as_ref()
+                .map(|r| Gemla::is_started(r))
+                .unwrap_or(true)
+    }
+
+    fn is_started(tree: &SimulationTree<T>) -> bool {
+        if tree.val.is_group() {
+            Gemla::children_started(tree)
+        } else {
+            tree.val.state() != GeneticState::Initialize
+        }
+    }
+
+    fn children_finished(tree: &SimulationTree<T>) -> bool {
+        tree.left
+            .as_ref()
+            .map(|l| Gemla::is_finished(l))
+            .unwrap_or(true)
+            && tree
+                .right
+                .as_ref()
+                .map(|r| Gemla::is_finished(r))
+                .unwrap_or(true)
+    }
+
+    /// Increases the height of the simulation tree by `steps` and processes the tree until it is completed. This is
+    /// equivalent to calling [`Gemla::grow`] followed by [`Gemla::run`] with [`RunBudget::Unbounded`].
+    pub async fn simulate(&mut self, steps: u64) -> Result<(), Error> {
+        self.grow(steps)?;
+
+        self.run(RunBudget::Unbounded).await
+    }
+
+    /// Increases the maximum generations of every node in the tree by `additional` so that existing nodes continue to be
+    /// trained the next time the tree is run. Finished nodes move on to mutating their population for the next
+    /// generation.
+    pub fn extend_generations(&mut self, additional: u64) -> Result<(), Error> {
+        self.mutate_data(|(d, _, _)| {
+            if let Some(t) = d {
+                Gemla::extend_tree_generations(t, additional);
+            }
+        })?;
+
+        info!("Extended simulation tree by {} generations", additional);
+
+        Ok(())
+    }
+
+    fn extend_tree_generations(tree: &mut SimulationTree<T>, additional: u64) {
+        if !tree.val.is_group() {
+            tree.val.extend_generations(additional);
+        }
+
+        if let Some(l) = &mut tree.left {
+            Gemla::extend_tree_generations(l, additional);
+        }
+        if let Some(r) = &mut tree.right {
+            Gemla::extend_tree_generations(r, additional);
+        }
+    }
+
+    /// Removes the node with the given `id` and every node underneath it from the simulation tree, returning whether a
+    /// node was removed. Nodes that are currently being processed, or that have descendants being processed, can't be
+    /// pruned.
+    pub fn prune(&mut self, id: Uuid) -> Result<bool, Error> {
+        let subtree = match self.tree_ref().and_then(|t| Gemla::find_subtree(t, id)) {
+            Some(subtree) => subtree,
+            None => return Ok(false),
+        };
+
+        if let Some(in_flight) = Gemla::subtree_ids(subtree)
+            .into_iter()
+            .find(|i| self.is_in_flight(*i))
+        {
+            return Err(Error::Other(anyhow!(
+                "Unable to prune node {}, node {} is currently being processed",
+                id,
+                in_flight
+            )));
+        }
+
+        self.mutate_data(|(d, _, _)| match d {
+            Some(t) if t.val.id() == id => {
+                *d = None;
+                true
+            }
+            Some(t) => Gemla::prune_subtree(t, id),
+            None => false,
+        })?;
+
+        info!("Pruned node {} from simulation tree", id);
+
+        Ok(true)
+    }
+
+    /// Sets the node with the given `id` and every node underneath it back to [`GeneticState::Initialize`], discarding
+    /// their data so they're processed again the next time the tree is run, for example after fixing a bug in the
+    /// [`GeneticNode`] implementation. Ancestors of the node depend on its result, so they're reset as well. Nodes keep
+    /// their ids and max generations. Returns the number of nodes that were reset, which is 0 if no node has the id.
+    /// Nodes that are currently being processed can't be reset.
+    ///
+    /// [`GeneticNode`]: genetic_node::GeneticNode
+    pub fn reset_subtree(&mut self, id: Uuid) -> Result<usize, Error> {
+        let tree = match self.tree_ref() {
+            Some(tree) => tree,
+            None => return Ok(0),
+        };
+        let subtree = match Gemla::find_subtree(tree, id) {
+            Some(subtree) => subtree,
+            None => return Ok(0),
+        };
+
+        if let Some(in_flight) = self.nodes_in_flight().into_iter().find(|i| {
+            Gemla::find_subtree(subtree, *i).is_some() || Gemla::is_ancestor(tree, *i, id)
+        }) {
+            return Err(Error::Other(anyhow!(
+                "Unable to reset node {}, node {} is currently being processed",
+                id,
+                in_flight
+            )));
+        }
+
+        let reset = self
+            .mutate_data(|(d, _, _)| d.as_mut().and_then(|t| Gemla::reset_path(t, id)))?
+            .unwrap_or(0);
+
+        info!("Reset {} nodes for node {}", reset, id);
+
+        Ok(reset)
+    }
+
+    /// Whether the node `ancestor` has the node `id` somewhere underneath it.
+    fn is_ancestor(tree: &SimulationTree<T>, ancestor: Uuid, id: Uuid) -> bool {
+        Gemla::find_subtree(tree, ancestor)
+            .map(|a| a.val.id() != id && Gemla::find_subtree(a, id).is_some())
+            .unwrap_or(false)
+    }
+
+    fn reset_path(tree: &mut SimulationTree<T>, id: Uuid) -> Option<usize> {
+        if tree.val.id() == id {
+            return Some(Gemla::reset_tree(tree));
+        }
+
+        let reset = tree
+            .left
+            .as_mut()
+            .and_then(|l| Gemla::reset_path(l, id))
+            .or_else(|| tree.right.as_mut().and_then(|r| Gemla::reset_path(r, id)))?;
+
+        if tree.val.is_group() {
+            Some(reset)
+        } else {
+            tree.val.reset();
+            Some(reset + 1)
+        }
+    }
+
+    fn reset_tree(tree: &mut SimulationTree<T>) -> usize {
+        let mut reset = 0;
+
+        if !tree.val.is_group() {
+            tree.val.reset();
+            reset += 1;
+        }
+        if let Some(l) = &mut tree.left {
+            reset += Gemla::reset_tree(l);
+        }
+        if let Some(r) = &mut tree.right {
+            reset += Gemla::reset_tree(r);
+        }
+
+        reset
+    }
+
+    fn find_node_mut(tree: &mut SimulationTree<T>, id: Uuid) -> Option<&mut GeneticNodeWrapper<T>> {
+        if tree.val.id() == id {
+            Some(&mut tree.val)
+        } else {
+            match tree.left.as_mut().and_then(|l| Gemla::find_node_mut(l, id)) {
+                Some(n) => Some(n),
+                None => tree
+                    .right
+                    .as_mut()
+                    .and_then(|r| Gemla::find_node_mut(r, id)),
+            }
+        }
+    }
+
+    fn find_subtree(tree: &SimulationTree<T>, id: Uuid) -> Option<&SimulationTree<T>> {
+        if tree.val.id() == id {
+            Some(tree)
+        } else {
+            tree.left
+                .as_ref()
+                .and_then(|l| Gemla::find_subtree(l, id))
+                .or_else(|| tree.right.as_ref().and_then(|r| Gemla::find_subtree(r, id)))
+        }
+    }
+
+    fn subtree_ids(tree: &SimulationTree<T>) -> Vec<Uuid> {
+        let mut ids = vec![tree.val.id()];
+
+        if let Some(l) = &tree.left {
+            ids.append(&mut Gemla::subtree_ids(l));
+        }
+        if let Some(r) = &tree.right {
+            ids.append(&mut Gemla::subtree_ids(r));
+        }
+
+        ids
+    }
+
+    fn prune_subtree(tree: &mut SimulationTree<T>, id: Uuid) -> bool {
+        for child in [&mut tree.left, &mut tree.right] {
+            if child.as_ref().map(|c| c.val.id() == id).unwrap_or(false) {
+                *child = None;
+                return true;
+            }
+
+            if let Some(c) = child {
+                if Gemla::prune_subtree(c, id) {
+                    // A group left without any members no longer groups anything together
+                    if c.val.is_group() && c.left.is_none() && c.right.is_none() {
+                        *child = None;
+                    }
+
+                    return true;
+                }
+            }
+        }
+
+        false
+    }
+
+    /// Replaces the leaf nodes that haven't been initialized yet with the data in `seed`, in order from left to right,
+    /// so they start from existing individuals instead of [`GeneticNode::initialize`]. Leaves without a seed are
+    /// initialized as usual. The tree has to be grown before it can be seeded.
+    pub fn with_seed(&mut self, seed: Vec<T>) -> Result<(), Error> {
+        let leaves = self
+            .tree_ref()
+            .map(|t| Gemla::count_seedable(t))
+            .ok_or_else(|| anyhow!("Unable to seed an empty simulation tree"))?;
+
+        if seed.len() > leaves {
+            return Err(Error::Other(anyhow!(
+                "Unable to seed {} nodes into {} uninitialized leaves",
+                seed.len(),
+                leaves
+            )));
+        }
+
+        let seeded = seed.len();
+        let mut seed = seed.into_iter();
+        self.mutate_data(|(d, _, _)| {
+            if let Some(t) = d {
+                Gemla::fill_leaves(t, &mut |leaf| {
+                    seed.next().map(|data| {
+                        GeneticNodeWrapper::from(data, leaf.max_generations(), leaf.id())
+                            .with_height(leaf.height())
+                    })
+                });
+            }
+        })?;
+
+        info!("Seeded {} of {} leaf nodes", seeded, leaves);
+
+        Ok(())
+    }
+
+    /// Rebuilds a simulation tree that was lost, for example because its state file was deleted, from the artifacts
+    /// its nodes stored in `dir`, see [`GeneticNode::recover_from_disk`]. The tree is grown until it has a leaf for
+    /// every recovered node, then each recovered node takes the place of a leaf, keeping its id and continuing from
+    /// the generation it was recovered at. Returns the number of nodes recovered.
+    pub fn rebuild_index(&mut self, dir: &Path) -> Result<usize, Error> {
+        if self.tree_ref().is_some() {
+            return Err(Error::Other(anyhow!(
+                "Unable to rebuild the index of a simulation that already has a tree"
+            )));
+        }
+
+        let recovered = T::recover_from_disk(dir)?;
+        let count = recovered.len();
+        if count == 0 {
+            warn!("No nodes were recovered from {}", dir.display());
+            return Ok(0);
+        }
+
+        while self
+            .tree_ref()
+            .map(|t| Gemla::count_seedable(t))
+            .unwrap_or(0)
+            < count
+        {
+            let height = self.tree_ref().map(|t| t.height()).unwrap_or(0);
+            self.grow(1)?;
+
+            if self.tree_ref().map(|t| t.height()).unwrap_or(0) == height {
+                return Err(Error::Other(anyhow!(
+                    "Unable to fit {} recovered nodes into a tree limited to a height of {}",
+                    count,
+                    height
+                )));
+            }
+        }
+
+        let mut recovered = recovered.into_iter();
+        self.mutate_data(|(d, _, _)| {
+            if let Some(t) = d {
+                Gemla::fill_leaves(t, &mut |leaf| {
+                    recovered.next().map(|r: RecoveredNode<T>| {
+                        GeneticNodeWrapper::from(
+                            r.node,
+                            leaf.max_generations().max(r.generation),
+                            r.id,
+                        )
+                        .with_height(leaf.height())
+                        .with_generation(r.generation)
+                    })
+                });
+            }
+        })?;
+
+        info!(
+            "Rebuilt simulation tree from {} nodes recovered from {}",
+            count,
+            dir.display()
+        );
+
+        Ok(count)
+    }
+
+    fn is_seedable(tree: &SimulationTree<T>) -> bool {
+        !tree.val.is_group()
+            && tree.left.is_none()
+            && tree.right.is_none()
+            && tree.val.state() == GeneticState::Initialize
+    }
+
+    fn count_seedable(tree: &SimulationTree<T>) -> usize {
+        if Gemla::is_seedable(tree) {
+            1
+        } else {
+            tree.left
+                .as_ref()
+                .map(|l| Gemla::count_seedable(l))
+                .unwrap_or(0)
+                + tree
+                    .right
+                    .as_ref()
+                    .map(|r| Gemla::count_seedable(r))
+                    .unwrap_or(0)
+        }
+    }
+
+    /// Replaces the leaves that haven't been initialized yet, from left to right, with the nodes returned by `fill`
+    /// until it returns `None`.
+    fn fill_leaves(
+        tree: &mut SimulationTree<T>,
+        fill: &mut impl FnMut(&GeneticNodeWrapper<T>) -> Option<GeneticNodeWrapper<T>>,
+    ) {
+        if Gemla::is_seedable(tree) {
+            if let Some(node) = fill(&tree.val) {
+                tree.val = node;
+            }
+        } else {
+            if let Some(l) = &mut tree.left {
+                Gemla::fill_leaves(l, fill);
+            }
+            if let Some(r) = &mut tree.right {
+                Gemla::fill_leaves(r, fill);
+            }
+        }
+    }
+
+    /// Returns the plan for the simulation tree after increasing its height by `steps`, without modifying the tree. Used
+    /// to see how much work a configuration will produce before running it.
+    pub fn plan(&self, steps: u64) -> SimulationPlan {
+        let config = self.effective_config();
+        let steps = Gemla::capped_steps(self.tree_ref(), &config, steps);
+        // Only the shape of the tree is needed, so the data held by the nodes isn't cloned
+        let tree = Gemla::increase_height(
+            self.tree_ref().map(Gemla::shape),
+            &config,
+            None,
+            steps,
+            "root".to_string(),
+        );
+
+        let mut plan = SimulationPlan {
+            nodes_per_level: vec![],
+            nodes: vec![],
+            total_generations: 0,
+        };
+
+        if let Some(t) = &tree {
+            Gemla::plan_nodes(t, "root".to_string(), 0, &mut plan);
+        }
+
+        plan
+    }
+
+    fn shape(tree: &SimulationTree<T>) -> SimulationTree<T> {
+        let val = if tree.val.is_group() {
+            GeneticNodeWrapper::group()
+        } else {
+            GeneticNodeWrapper::new(tree.val.max_generations()).with_height(tree.val.height())
+        };
+
+        Box::new(Tree::new(
+            val,
+            tree.left.as_ref().map(|l| Gemla::shape(l)),
+            tree.right.as_ref().map(|r| Gemla::shape(r)),
+        ))
+    }
+
+    fn plan_nodes(tree: &SimulationTree<T>, path: String, level: usize, plan: &mut SimulationPlan) {
+        // Group nodes sit on the same level of the bracket as the nodes they group together
+        let child_level = if tree.val.is_group() {
+            level
+        } else {
+            if plan.nodes_per_level.len() <= level {
+                plan.nodes_per_level.resize(level + 1, 0);
+            }
+            plan.nodes_per_level[level] += 1;
+
+            plan.nodes.push(PlannedNode {
+                path: path.clone(),
+                max_generations: tree.val.max_generations(),
+            });
+            plan.total_generations += tree.val.max_generations();
+
+            level + 1
+        };
+
+        if let Some(l) = &tree.left {
+            Gemla::plan_nodes(l, format!("{}/L", path), child_level, plan);
+        }
+        if let Some(r) = &tree.right {
+            Gemla::plan_nodes(r, format!("{}/R", path), child_level, plan);
+        }
+    }
+
+    /// Increases the height of the simulation tree by `levels` without processing any nodes.
+    pub fn grow(&mut self, levels: u64) -> Result<(), Error> {
+        let _span = info_span!("increase_height", steps = levels).entered();
+
+        // Before we can process nodes we must create blank nodes in their place to keep track of which nodes have been processed
+        // in the tree and which nodes have not.
+        let config = self.effective_config();
+        let capped = Gemla::capped_steps(self.tree_ref(), &config, levels);
+        if capped < levels {
+            info!(
+                "Simulation tree is limited to a height of {:?}, growing by {} instead of {} levels",
+                config.max_height, capped, levels
+            );
+        }
+
+        let write_time = Instant::now();
+        self.mutate_data(|(d, c, _)| -> Result<(), Error> {
+            // Data stored in a sidecar file is loaded so the champion can be copied into the new right branch
+            let champion = match (c.right_branch_mode, d.as_mut()) {
+                (RightBranchMode::CloneChampion, Some(t)) => t.val.load_payload()?.cloned(),
+                _ => None,
+            };
+
+            let mut tree: Option<SimulationTree<T>> =
+                Gemla::increase_height(d.take(), c, champion.as_ref(), capped, "root".to_string());
+            mem::swap(d, &mut tree);
+
+            Ok(())
+        })??;
+        self.metrics.record_file_write(write_time.elapsed());
+
+        if let Some(t) = self.tree_ref().filter(|_| capped > 0) {
+            let event =
+                JournalEvent::new(JournalEventKind::HeightIncrease, &t.val, "root".to_string());
+            self.write_journal(&[event]);
+        }
+
+        info!(
+            "Height of simulation tree increased to {}",
+            self.tree_ref()
+                .map(|t| format!("{}", t.height()))
+                .unwrap_or_else(|| "Tree is not defined".to_string())
+        );
+
+        self.publish_progress();
+
+        Ok(())
+    }
+
+    /// Processes nodes in the simulation tree until the tree is completed or the given `budget` runs out. Nodes that
+    /// are in progress when the budget runs out are joined before returning so that all completed work is persisted,
+    /// except for nodes still being processed once [`GemlaConfig::join_deadline`] has passed. Those are left in
+    /// progress and joined by the next call to `run` or by [`Gemla::shutdown`]. Nodes that fail with a transient error,
+    /// see [`Error::is_transient`], are processed again, any other error stops the run.
+    pub async fn run(&mut self, budget: RunBudget) -> Result<(), Error> {
+        // Futures left over from a previous run are kept as long as their node still has to be processed, under the
+        // node's current path since growing the tree moves it. The rest are dropped.
+        let mut stale = vec![];
+        for (_, (id, thread)) in mem::take(&mut self.threads) {
+            let path = self
+                .tree_ref()
+                .filter(|t| {
+                    Gemla::find_subtree(t, id)
+                        .map(|n| n.val.state() != GeneticState::Finish)
+                        .unwrap_or(false)
+                })
+                .and_then(|t| Gemla::node_path(t, id, "root".to_string()));
+
+            match path {
+                Some(path) => {
+                    self.threads.insert(path, (id, thread));
+                }
+                None => stale.push(id),
+            }
+        }
+        if !stale.is_empty() {
+            warn!("Discarding stale process list for nodes {:?}", stale);
+        }
+
+        let start = Instant::now();
+        // Nodes that have been scheduled during this run, used to limit how many nodes are worked on
+        let mut started: HashSet<Uuid> = self.nodes_in_flight().into_iter().collect();
+        let pool_size = self
+            .effective_config()
+            .worker_pool_size
+            .unwrap_or_else(num_cpus::get)
+            .max(1);
+
+        loop {
+            // We need to keep simulating until the tree has been completely processed.
+            if self
+                .tree_ref()
+                .map(|t| Gemla::is_completed(t))
+                .unwrap_or(false)
+            {
+                self.join_threads().await?;
+
+                info!("Processed tree");
+                break;
+            }
+
+            let exhausted = match budget {
+                RunBudget::Unbounded => false,
+                RunBudget::Nodes(n) => {
+                    self.tree_ref()
+                        .map(|t| Gemla::count_finished(t, &started))
+                        .unwrap_or(0)
+                        >= n
+                }
+                RunBudget::Duration(d) => start.elapsed() >= d,
+            };
+
+            if exhausted {
+                self.join_threads().await?;
+
+                info!("Run budget {:?} exhausted", budget);
+                break;
+            }
+
+            // Scheduled nodes are joined before any more are added so only a bounded number are pending at once
+            if self.threads.len() >= pool_size {
+                trace!(
+                    "Worker pool of {} nodes is full, joining threads",
+                    pool_size
+                );
+
+                self.join_threads().await?;
+                continue;
+            }
+
+            let allowed = |n: &GeneticNodeWrapper<T>| match budget {
+                RunBudget::Nodes(limit) => {
+                    started.contains(&n.id()) || (started.len() as u64) < limit
+                }
+                _ => true,
+            };
+
+            if let Some(node) = self
+                .tree_ref()
+                .and_then(|t| self.get_unprocessed_node(t, &allowed))
+            {
+                started.insert(node.id());
+                self.start_node(node);
+            } else if self.threads.is_empty() {
+                // If no node could be processed even without a budget then the tree can never be completed
+                if let Some(t) = self.tree_ref() {
+                    if self.get_unprocessed_node(t, &|_| true).is_none() {
+                        let mut stalled = vec![];
+                        Gemla::stalled_nodes(t, &mut stalled);
+
+                        return Err(Error::Stalled(stalled));
+                    }
+                }
+
+                // Nothing is running and nothing else is allowed to start
+                info!("No nodes left to process within run budget {:?}", budget);
+                break;
+            } else {
+                trace!("No node found to process, joining threads");
+
+                self.join_threads().await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Adds `node` to the nodes being processed.
+    fn start_node(&mut self, node: GeneticNodeWrapper<T>) {
+        trace!("Adding node to process list {}", node.id());
+
+        let path = self
+            .tree_ref()
+            .and_then(|t| Gemla::node_path(t, node.id(), "root".to_string()))
+            .unwrap_or_default();
+        self.last_scheduled = Some(path.clone());
+
+        let metrics = self.metrics();
+        let state = node.state();
+        let id = node.id();
+        self.threads.insert(
+            path.clone(),
+            (
+                id,
+                Box::pin(async move {
+                    let start = Instant::now();
+                    let result = Gemla::process_node(node, path).await;
+                    metrics.record_node(
+                        state,
+                        result.as_ref().ok().map(|n| n.state()),
+                        start.elapsed(),
+                    );
+
+                    result
+                }),
+            ),
+        );
+    }
+
+    fn stalled_nodes(tree: &SimulationTree<T>, stalled: &mut Vec<Uuid>) {
+        // The nodes blocking the tree are the unfinished nodes whose children have all finished
+        if !tree.val.is_group() && !Gemla::is_finished(tree) && Gemla::children_finished(tree) {
+            stalled.push(tree.val.id());
+        }
+
+        if let Some(l) = &tree.left {
+            Gemla::stalled_nodes(l, stalled);
+        }
+        if let Some(r) = &tree.right {
+            Gemla::stalled_nodes(r, stalled);
+        }
+    }
+
+    fn count_finished(tree: &SimulationTree<T>, ids: &HashSet<Uuid>) -> u64 {
+        let current = if ids.contains(&tree.val.id()) && tree.val.state() == GeneticState::Finish {
+            1
+        } else {
+            0
+        };
+
+        current
+            + tree
+                .left
+                .as_ref()
+                .map(|l| Gemla::count_finished(l, ids))
+                .unwrap_or(0)
+            + tree
+                .right
+                .as_ref()
+                .map(|r| Gemla::count_finished(r, ids))
+                .unwrap_or(0)
+    }
+
+    #[instrument(skip(self), fields(nodes = self.threads.len()))]
+    async fn join_threads(&mut self) -> Result<(), Error> {
+        if !self.threads.is_empty() {
+            trace!("Joining threads for nodes {:?}", self.nodes_in_flight());
+
+            let deadline = self
+                .effective_config()
+                .join_deadline
+                .map(|d| Instant::now() + d);
+            let mut results = vec![];
+            let mut joined = vec![];
+
+            {
+                let mut pending = self
+                    .threads
+                    .iter_mut()
+                    .map(|(path, (id, thread))| async move { (path.clone(), *id, thread.await) })
+                    .collect::<FuturesUnordered<_>>();
+
+                loop {
+                    let next = match deadline {
+                        Some(d) if Instant::now() >= d => break,
+                        Some(d) => match future::select(pending.next(), Timer::at(d)).await {
+                            Either::Left((next, _)) => next,
+                            Either::Right(_) => break,
+                        },
+                        None => pending.next().await,
+                    };
+
+                    match next {
+                        Some((path, id, result)) => {
+                            joined.push(path.clone());
+                            results.push((path, id, result));
+                        }
+                        None => break,
+                    }
+                }
+            }
+
+            for path in joined.iter() {
+                self.threads.remove(path);
+            }
+            if !self.threads.is_empty() {
+                warn!(
+                    "Join deadline reached, nodes {:?} are still being processed",
+                    self.nodes_in_flight()
+                );
+            }
+
+            // Nodes that were processed successfully are kept even if another node failed, otherwise they would have
+            // to be processed again for the same generation
+            let mut processed_nodes = vec![];
+            let mut failures = vec![];
+            let mut events = vec![];
+            let mut first_error = None;
+            // Nodes finish in any order, the results are applied in the order of their paths instead so the history
+            // and the first error don't depend on timing
+            results.sort_by(|(a, _, _), (b, _, _)| a.cmp(b));
+            for (path, id, result) in results {
+                match result {
+                    Ok(n) => {
+                        events.push(JournalEvent::new(
+                            JournalEventKind::StateChange(n.state()),
+                            &n,
+                            path,
+                        ));
+                        processed_nodes.push(n);
+                    }
+                    // The node is left as it was in the tree, so it's scheduled again
+                    Err(e) if e.is_transient() => {
+                        warn!(
+                            "Processing node {} again after a transient error: {}",
+                            id, e
+                        );
+                    }
+                    Err(e) => {
+                        failures.push((id, SerializableError::from(&e)));
+
+                        if first_error.is_none() {
+                            first_error = Some(e);
+                        } else {
+                            warn!("Additional error while joining threads: {}", e);
+                        }
+                    }
+                }
+            }
+
+            // We need to retrieve the processed nodes from the resulting list and replace them in the original list
+            let write_time = Instant::now();
+            let merges = self.mutate_data(|(d, c, h)| {
+                if c.record_history {
+                    h.extend(
+                        processed_nodes
+                            .iter()
+                            .filter_map(GenerationRecord::from_node),
+                    );
+                }
+
+                if let Some(t) = d {
+                    // Failures are kept in the tree so they can still be inspected after a restart
+                    for (id, failure) in failures {
+                        if let Some(n) = Gemla::find_node_mut(t, id) {
+                            n.set_failure(failure);
+                        }
+                    }
+
+                    let failed_nodes = Gemla::replace_nodes(t, processed_nodes);
+                    // We receive a list of nodes that were unable to be found in the original tree
+                    if !failed_nodes.is_empty() {
+                        warn!(
+                            "Unable to find {:?} to replace in tree",
+                            failed_nodes.iter().map(|n| n.id())
+                        )
+                    }
+
+                    // Once the nodes are replaced we need to find nodes that can be merged from the completed children nodes
+                    info_span!("merge_completed_nodes")
+                        .in_scope(|| Gemla::merge_completed_nodes(t, c))
+                } else {
+                    warn!("Unable to replce nodes {:?} in empty tree", processed_nodes);
+                    Ok(vec![])
+                }
+            })??;
+            self.metrics.record_file_write(write_time.elapsed());
+
+            if let Some(t) = self.tree_ref() {
+                for merge in &merges {
+                    let parent = Gemla::find_subtree(t, merge.parent);
+                    let path = Gemla::node_path(t, merge.parent, "root".to_string());
+                    if let (Some(parent), Some(path)) = (parent, path) {
+                        events.push(JournalEvent::new(
+                            JournalEventKind::Merge,
+                            &parent.val,
+                            path,
+                        ));
+                    }
+                }
+            }
+            self.write_journal(&events);
+            self.merge_log.extend(merges);
+
+            self.publish_progress();
+
+            if let Some(e) = first_error {
+                return Err(e);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn merge_completed_nodes(
+        tree: &mut SimulationTree<T>,
+        config: &GemlaConfig,
+    ) -> Result<Vec<MergeRecord>, Error> {
+        let mut merges = vec![];
+
+        if tree.val.is_group() {
+            // Group nodes are only placeholders, the nodes underneath them are merged by the first non group ancestor
+            if let Some(l) = &mut tree.left {
+                merges.extend(Gemla::merge_completed_nodes(l, config)?);
+            }
+            if let Some(r) = &mut tree.right {
+                merges.extend(Gemla::merge_completed_nodes(r, config)?);
+            }
+        } else {
+            let initialized = tree.val.state() == GeneticState::Initialize;
+
+            match (&mut tree.left, &mut tree.right) {
+                // If the current node has been initialized, and has children nodes that are completed, then we need
+                // to merge the children nodes together into the parent node
+                (Some(l), Some(r))
+                    if initialized && Gemla::is_finished(l) && Gemla::is_finished(r) =>
+                {
+                    // Data stored in sidecar files is only loaded once it's needed for the merge
+                    tree.val.load_payload()?;
+                    Gemla::load_members(l)?;
+                    Gemla::load_members(r)?;
+
+                    let mut members = Gemla::group_members(l);
+                    members.append(&mut Gemla::group_members(r));
+                    let children = members.iter().map(|m| m.id()).collect::<Vec<Uuid>>();
+
+                    info!("Merging nodes {:?}", children);
+
+                    let (id, state, generation) =
+                        (tree.val.id(), tree.val.state(), tree.val.generation());
+                    let contributors =
+                        Gemla::merge_into(&mut tree.val, &members, config).map_err(|e| {
+                            Error::Node {
+                                id,
+                                state,
+                                generation,
+                                source: Box::new(e),
+                            }
+                        })?;
+
+                    if let Some(contributors) = contributors {
+                        merges.push(MergeRecord {
+                            parent: id,
+                            children,
+                            contributors,
+                        });
+                    }
+                }
+                // If there is only one child node that's completed then we want to copy it to the parent node
+                (Some(child), None) | (None, Some(child))
+                    if initialized && child.val.state() == GeneticState::Finish =>
+                {
+                    trace!("Copying node {}", child.val.id());
+
+                    // The data is cloned rather than moved since finished nodes have to keep their data
+                    if let Some(child_node) = child.val.load_payload()? {
+                        tree.val = GeneticNodeWrapper::from(
+                            child_node.clone(),
+                            tree.val.max_generations(),
+                            tree.val.id(),
+                        )
+                        .with_height(tree.val.height());
+
+                        merges.push(MergeRecord {
+                            parent: tree.val.id(),
+                            children: vec![child.val.id()],
+                            contributors: vec![child.val.id()],
+                        });
+                    }
+                }
+                // Descendants can still be waiting to be merged when the current node isn't, for example after the
+                // tree was grown or repaired underneath a node that already holds data
+                (left, right) => {
+                    if let Some(l) = left {
+                        merges.extend(Gemla::merge_completed_nodes(l, config)?);
+                    }
+                    if let Some(r) = right {
+                        merges.extend(Gemla::merge_completed_nodes(r, config)?);
+                    }
+                }
+            }
+        }
+
+        Ok(merges)
+    }
+
+    fn merge_into(
+        node: &mut GeneticNodeWrapper<T>,
+        members: &[&GeneticNodeWrapper<T>],
+        config: &GemlaConfig,
+    ) -> Result<Option<Vec<Uuid>>, Error> {
+        // The node may already hold data if a previous merge into it was interrupted
+        if let Some(existing) = node.as_ref() {
+            let id = node.id();
+            let conflict = existing
+                .on_merge_conflict(node.context())
+                .with_context(|| format!("Error resolving merge conflict for node {}", id))?;
+            warn!(
+                "Node {} already holds data before merging, resolved with {:?}",
+                id, conflict
+            );
+
+            if conflict == MergeConflict::Reuse {
+                // The existing data is moved into the restarted node rather than cloned
+                if let Some(existing) = node.take_node() {
+                    *node = GeneticNodeWrapper::from(existing, node.max_generations(), id)
+                        .with_height(node.height());
+                }
+
+                return Ok(None);
+            }
+        }
+
+        let (ids, nodes): (Vec<Uuid>, Vec<&T>) = members
+            .iter()
+            .filter_map(|m| m.as_ref().map(|n| (m.id(), n)))
+            .unzip();
+        let context = MergeContext {
+            target_population_hint: config.target_population,
+            height: node.height(),
+            id: node.id(),
+        };
+        let merged_node = Gemla::merge_members(config.merge_strategy, &nodes, context)?;
+        Gemla::check_population(config, &nodes, &merged_node)?;
+        merged_node
+            .validate()
+            .with_context(|| format!("Error validating merged node: {:?}", merged_node))?;
+
+        *node = GeneticNodeWrapper::from(*merged_node, node.max_generations(), node.id())
+            .with_height(node.height());
+
+        let contributors = match config.merge_strategy {
+            MergeStrategy::TakeBest => Gemla::best_member(&nodes)
+                .map(|i| ids[i])
+                .into_iter()
+                .collect(),
+            _ => ids,
+        };
+
+        Ok(Some(contributors))
+    }
+
+    fn merge_members(
+        strategy: MergeStrategy,
+        nodes: &[&T],
+        context: MergeContext,
+    ) -> Result<Box<T>, Error> {
+        match strategy {
+            MergeStrategy::Standard => T::merge_with_context(nodes, context),
+            MergeStrategy::FitnessWeighted => {
+                let mut sorted = nodes.to_vec();
+                sorted.sort_by(|a, b| b.fitness().total_cmp(&a.fitness()));
+
+                T::merge_with_context(&sorted, context)
+            }
+            MergeStrategy::TakeBest => Gemla::best_member(nodes)
+                .map(|i| Box::new(nodes[i].clone()))
+                .ok_or_else(|| Error::Other(anyhow!("Unable to take the best of zero nodes"))),
+        }
+    }
+
+    /// The index of the node with the highest fitness, the last one if several are tied.
+    fn best_member(nodes: &[&T]) -> Option<usize> {
+        (0..nodes.len()).max_by(|a, b| nodes[*a].fitness().total_cmp(&nodes[*b].fitness()))
+    }
+
+    fn check_population(config: &GemlaConfig, nodes: &[&T], merged: &T) -> Result<(), Error> {
+        // Merges are expected to keep populations at a constant size, otherwise they drift over many levels
+        let expected = config
+            .target_population
+            .or_else(|| nodes.first().map(|n| n.population_size()));
+
+        match expected {
+            Some(size) if merged.population_size() != size => Err(Error::Other(anyhow!(
+                "Merged node has a population of {}, expected {}",
+                merged.population_size(),
+                size
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    fn load_members(tree: &mut SimulationTree<T>) -> Result<(), Error> {
+        if tree.val.is_group() {
+            if let Some(l) = &mut tree.left {
+                Gemla::load_members(l)?;
+            }
+            if let Some(r) = &mut tree.right {
+                Gemla::load_members(r)?;
+            }
+        } else {
+            tree.val.load_payload()?;
+        }
+
+        Ok(())
+    }
+
+    fn group_members(tree: &SimulationTree<T>) -> Vec<&GeneticNodeWrapper<T>> {
+        // Collecting the nodes that are grouped together underneath a group node
+        if tree.val.is_group() {
+            let mut members = tree
+                .left
+                .as_ref()
+                .map(|l| Gemla::group_members(l))
+                .unwrap_or_default();
+            members.append(
+                &mut tree
+                    .right
+                    .as_ref()
+                    .map(|r| Gemla::group_members(r))
+                    .unwrap_or_default(),
+            );
+            members
+        } else {
+            vec![&tree.val]
+        }
+    }
+
+    fn is_finished(tree: &SimulationTree<T>) -> bool {
+        // A group is finished once every node within it is finished
+        if tree.val.is_group() {
+            Gemla::children_finished(tree)
+        } else {
+            tree.val.state() == GeneticState::Finish
+        }
+    }
+
+    fn get_unprocessed_node(
+        &self,
+        tree: &SimulationTree<T>,
+        allowed: &dyn Fn(&GeneticNodeWrapper<T>) -> bool,
+    ) -> Option<GeneticNodeWrapper<T>> {
+        let mut nodes = vec![];
+        self.unprocessed_nodes(tree, "root".to_string(), allowed, &mut nodes);
+
+        let node = match self.effective_config().scheduling_order {
+            SchedulingOrder::DepthFirst => nodes.first().map(|(_, n)| *n),
+            // Nodes without data to score are ordered after every node with data
+            SchedulingOrder::BestFirst => {
+                Gemla::first_max_by_key(&nodes, |n| n.as_ref().map(|d| d.fitness()))
+            }
+            SchedulingOrder::CriticalPathFirst => Gemla::first_max_by_key(&nodes, |n| {
+                n.max_generations().saturating_sub(n.generation())
+            }),
+            SchedulingOrder::RoundRobin => {
+                // The subtree of the root a node is in, which is empty for the root itself
+                let branch = |path: &str| path.split('/').nth(1).unwrap_or("").to_string();
+                let last = self.last_scheduled.as_deref().map(branch);
+
+                nodes
+                    .iter()
+                    .find(|(p, _)| Some(branch(p)) > last)
+                    .or_else(|| nodes.first())
+                    .map(|(_, n)| *n)
+            }
+        };
+
+        node.cloned()
+    }
+
+    /// Returns the node with the highest `key`, ties go to the earlier node.
+    fn first_max_by_key<'t, K: PartialOrd>(
+        nodes: &[(String, &'t GeneticNodeWrapper<T>)],
+        key: impl Fn(&GeneticNodeWrapper<T>) -> K,
+    ) -> Option<&'t GeneticNodeWrapper<T>> {
+        nodes.iter().fold(None, |best, (_, n)| match best {
+            Some(b) if key(n) > key(b) => Some(*n),
+            Some(b) => Some(b),
+            None => Some(*n),
+        })
+    }
+
+    /// Collects the nodes that can be processed next along with their paths, in the order of a depth first traversal.
+    fn unprocessed_nodes<'t>(
+        &self,
+        tree: &'t SimulationTree<T>,
+        path: String,
+        allowed: &dyn Fn(&GeneticNodeWrapper<T>) -> bool,
+        nodes: &mut Vec<(String, &'t GeneticNodeWrapper<T>)>,
+    ) {
+        let children = |nodes: &mut Vec<(String, &'t GeneticNodeWrapper<T>)>| {
+            if let Some(l) = &tree.left {
+                self.unprocessed_nodes(l, format!("{}/L", path), allowed, nodes);
+            }
+            if let Some(r) = &tree.right {
+                self.unprocessed_nodes(r, format!("{}/R", path), allowed, nodes);
+            }
+        };
+
+        if tree.val.is_group() {
+            // Group nodes are never processed, only the nodes underneath them
+            children(nodes);
+            return;
+        }
+
+        // If the current node has been processed or exists in the thread list then we want to stop recursing. Checking if it exists in the thread list
+        // should be fine because we process the tree from bottom to top.
+        if tree.val.state() != GeneticState::Finish && !self.is_in_flight(tree.val.id()) {
+            match (&tree.left, &tree.right) {
+                // If the children are finished we can start processing the currrent node. The current node should be merged from the children already
+                // during join_threads.
+                (Some(l), Some(r)) if Gemla::is_finished(l) && Gemla::is_finished(r) => {
+                    if allowed(&tree.val) {
+                        nodes.push((path.clone(), &tree.val));
+                    }
+                }
+                (None, None) => {
+                    if allowed(&tree.val) {
+                        nodes.push((path.clone(), &tree.val));
+                    }
+                }
+                _ => children(nodes),
+            }
+        }
+    }
+
+    fn replace_nodes(
+        tree: &mut SimulationTree<T>,
+        mut nodes: Vec<GeneticNodeWrapper<T>>,
+    ) -> Vec<GeneticNodeWrapper<T>> {
+        // Replacing nodes as we recurse through the tree
+        if let Some(i) = nodes.iter().position(|n| n.id() == tree.val.id()) {
+            let node = nodes.remove(i);
+
+            // A node from an earlier generation than the one in the tree is a stale result and would undo progress
+            if node.generation() < tree.val.generation() {
+                warn!(
+                    "Discarding stale result for node {} from generation {}, tree is on generation {}",
+                    node.id(),
+                    node.generation(),
+                    tree.val.generation()
+                );
+            } else {
+                tree.val = node;
+            }
+        }
+
+        match (&mut tree.left, &mut tree.right) {
+            (Some(l), Some(r)) => Gemla::replace_nodes(r, Gemla::replace_nodes(l, nodes)),
+            (Some(l), None) => Gemla::replace_nodes(l, nodes),
+            (None, Some(r)) => Gemla::replace_nodes(r, nodes),
+            _ => nodes,
+        }
+    }
+
+    fn capped_steps(tree: Option<&SimulationTree<T>>, config: &GemlaConfig, steps: u64) -> u64 {
+        match config.max_height {
+            Some(max) => {
+                let levels = tree.map(|t| Gemla::levels(t)).unwrap_or(0);
+                steps.min(max.saturating_sub(levels))
+            }
+            None => steps,
+        }
+    }
+
+    /// Adds `amount` levels above `tree`, whose root ends up at `path`. New right branches start from `champion` when
+    /// it's given and [`RightBranchMode::CloneChampion`] is used.
+    fn increase_height(
+        tree: Option<SimulationTree<T>>,
+        config: &GemlaConfig,
+        champion: Option<&T>,
+        amount: u64,
+        path: String,
+    ) -> Option<SimulationTree<T>> {
+        if amount == 0 {
+            tree
+        } else {
+            let left_branch_right =
+                tree.as_ref().map(|t| Gemla::levels(t)).unwrap_or(0) + amount - 1;
+            // Paths are reused as the tree grows, but never together with the level the node was created for
+            let level = left_branch_right + 1;
+            let new_id = |p: &str| config.node_id(&format!("{}:{}", level, p));
+
+            Some(Box::new(Tree::new(
+                GeneticNodeWrapper::new(config.generations(1))
+                    .with_height(level)
+                    .with_id(new_id(&path)),
+                Gemla::increase_height(tree, config, champion, amount - 1, format!("{}/L", path)),
+                // The right branch height has to equal the left branches total height
+                if left_branch_right > 0 {
+                    Some(Box::new(Gemla::group_branch(
+                        config.fan_in - 1,
+                        format!("{}/R", path),
+                        &|p| GeneticNodeWrapper::group().with_id(new_id(p)),
+                        &|p| match (config.right_branch_mode, champion) {
+                            (RightBranchMode::MirrorTree, _) => {
+                                Gemla::mirror_branch(config, left_branch_right, p)
+                            }
+                            (RightBranchMode::CloneChampion, Some(c)) => {
+                                let id = new_id(p);
+                                btree!(GeneticNodeWrapper::from(
+                                    *c.clone_for_branch(id),
+                                    config.generations(left_branch_right),
+                                    id
+                                ))
+                            }
+                            _ => btree!(GeneticNodeWrapper::new(
+                                config.generations(left_branch_right)
+                            )
+                            .with_id(new_id(p))),
+                        },
+                    )))
+                } else {
+                    None
+                },
+            )))
+        }
+    }
+
+    /// Builds a full bracket of new nodes at `path` that is `levels` levels high.
+    fn mirror_branch(config: &GemlaConfig, levels: u64, path: &str) -> Tree<GeneticNodeWrapper<T>> {
+        match Gemla::increase_height(None, config, None, levels, path.to_string()) {
+            Some(tree) => *tree,
+            // Mirrored branches are at least one level high
+            None => btree!(GeneticNodeWrapper::new(config.generations(levels))),
+        }
+    }
+
+    /// Creates a branch at `path` holding `members` subtrees made with `new_member`, held underneath group nodes made
+    /// with `new_group`.
+    fn group_branch(
+        members: usize,
+        path: String,
+        new_group: &impl Fn(&str) -> GeneticNodeWrapper<T>,
+        new_member: &impl Fn(&str) -> Tree<GeneticNodeWrapper<T>>,
+    ) -> Tree<GeneticNodeWrapper<T>> {
+        // Multiple nodes on the right branch are held underneath group nodes so they can be merged all at once
+        if members == 1 {
+            new_member(&path)
+        } else {
+            let left = members / 2;
+
+            btree!(
+                new_group(&path),
+                Gemla::group_branch(left, format!("{}/L", path), new_group, new_member),
+                Gemla::group_branch(members - left, format!("{}/R", path), new_group, new_member)
+            )
+        }
+    }
+
+    fn levels(tree: &SimulationTree<T>) -> u64 {
+        // The number of levels in the bracket, which is the height of the tree not counting group nodes
+        let children = max(
+            tree.left.as_ref().map(|l| Gemla::levels(l)).unwrap_or(0),
+            tree.right.as_ref().map(|r| Gemla::levels(r)).unwrap_or(0),
+        );
+
+        if tree.val.is_group() {
+            children
+        } else {
+            children + 1
+        }
+    }
+
+    fn is_completed(tree: &SimulationTree<T>) -> bool {
+        // If the current node is finished, then by convention the children should all be finished as well
+        tree.val.state() == GeneticState::Finish
+    }
+
+    fn node_path(tree: &SimulationTree<T>, id: Uuid, path: String) -> Option<String> {
+        if tree.val.id() == id {
+            Some(path)
+        } else {
+            tree.left
+                .as_ref()
+                .and_then(|l| Gemla::node_path(l, id, format!("{}/L", path)))
+                .or_else(|| {
+                    tree.right
+                        .as_ref()
+                        .and_then(|r| Gemla::node_path(r, id, format!("{}/R", path)))
+                })
+        }
+    }
+
+    #[instrument(
+        skip(node, path),
+        fields(id = %node.id(), path = %path, generation = node.generation(), state = ?node.state())
+    )]
+    async fn process_node(
+        mut node: GeneticNodeWrapper<T>,
+        path: String,
+    ) -> Result<GeneticNodeWrapper<T>, Error> {
+        let node_state_time = Instant::now();
+        let node_state = node.state();
+
+        // Nodes read back from sidecar storage only load their data once they are processed
+        let id = node.id();
+        node.load_payload()
+            .with_context(|| format!("Unable to load the data of node {}", id))?;
+        node.process_node()?;
+
+        trace!(
+            "{:?} completed in {:?} for {}",
+            node_state,
+            node_state_time.elapsed(),
+            node.id()
+        );
+
+        if node.state() == GeneticState::Finish {
+            info!("Processed node {}", node.id());
+        }
+
+        Ok(node)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::*;
+    use genetic_node::GeneticNodeContext;
+    use serde::{Deserialize, Serialize};
+    use std::fs;
+    use std::path::PathBuf;
+    use std::sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex,
+    };
+    use tracing::{
+        field::{Field, Visit},
+        span::{Attributes, Id},
+        Subscriber,
+    };
+    use tracing_subscriber::{
+        layer::{Context, SubscriberExt},
+        Layer,
+    };
+    use tracing_test::traced_test;
+
+    struct CleanUp {
+        path: PathBuf,
+    }
+
+    impl CleanUp {
+        fn new(path: &Path) -> CleanUp {
+            CleanUp {
+                path: path.to_path_buf(),
+            }
+        }
+
+        pub fn run<F: FnOnce(&Path) -> Result<(), Error>>(&self, op: F) -> Result<(), Error> {
+            op(&self.path)
+        }
+    }
+
+    impl Drop for CleanUp {
+        fn drop(&mut self) {
+            if self.path.exists() {
+                fs::remove_file(&self.path).expect("Unable to remove file");
+            }
+        }
+    }
+
+    #[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+    struct TestState {
+        pub score: f64,
+    }
+
+    impl genetic_node::GeneticNode for TestState {
+        fn simulate(&mut self, _context: GeneticNodeContext) -> Result<(), Error> {
+            self.score += 1.0;
+            Ok(())
+        }
+
+        fn mutate(&mut self, _context: GeneticNodeContext) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn initialize(_context: GeneticNodeContext) -> Result<Box<TestState>, Error> {
+            Ok(Box::new(TestState { score: 0.0 }))
+        }
+
+        fn fitness(&self) -> f64 {
+            self.score
+        }
+
+        fn population_size(&self) -> usize {
+            1
+        }
+
+        fn merge(left: &TestState, right: &TestState) -> Result<Box<TestState>, Error> {
+            Ok(Box::new(if left.score > right.score {
+                left.clone()
+            } else {
+                right.clone()
+            }))
+        }
+    }
+
+    #[test]
+    fn test_new() -> Result<(), Error> {
+        let path = PathBuf::from("test_new_non_existing");
+        CleanUp::new(&path).run(|p| {
+            assert!(!path.exists());
+
+            // Testing initial creation
+            let mut config = GemlaConfig {
+                overwrite: true,
+                ..GemlaConfig::default()
+            };
+            let mut gemla = Gemla::<TestState>::new(p, config)?;
+
+            smol::block_on(gemla.simulate(2))?;
+            assert_eq!(gemla.data.readonly().0.as_ref().unwrap().height(), 2);
+
+            drop(gemla);
+            assert!(path.exists());
+
+            // Testing overwriting data
+            let mut gemla = Gemla::<TestState>::new(p, config)?;
+
+            smol::block_on(gemla.simulate(2))?;
+            assert_eq!(gemla.data.readonly().0.as_ref().unwrap().height(), 2);
+
+            drop(gemla);
+            assert!(path.exists());
+
+            // Testing not-overwriting data
+            config.overwrite = false;
+            let mut gemla = Gemla::<TestState>::new(p, config)?;
+
+            smol::block_on(gemla.simulate(2))?;
+            assert_eq!(gemla.tree_ref().unwrap().height(), 4);
+
+            drop(gemla);
+            assert!(path.exists());
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_simulate() -> Result<(), Error> {
+        let config = GemlaConfig {
+            generations_per_node: 10,
+            overwrite: true,
+            ..GemlaConfig::default()
+        };
+
+        let mut gemla = Gemla::<TestState>::in_memory(config)?;
+
+        smol::block_on(gemla.simulate(5))?;
+        let tree = gemla.tree_ref().unwrap();
+        assert_eq!(tree.height(), 5);
+        assert_eq!(tree.val.as_ref().unwrap().score, 50.0);
+
+        // Simulating in memory gives the same results as simulating with a file
+        let path = PathBuf::from("test_simulate");
+        CleanUp::new(&path).run(|p| {
+            let mut linked = Gemla::<TestState>::new(p, config)?;
+            smol::block_on(linked.simulate(5))?;
+
+            let scores = |t: &SimulationTree<TestState>| {
+                let mut records = vec![];
+                Gemla::export_records(t, "root".to_string(), &mut records);
+                records
+                    .iter()
+                    .map(|r| (r.path.clone(), r.state, r.node.map(|n| n.score)))
+                    .collect::<Vec<(String, GeneticState, Option<f64>)>>()
+            };
+            assert_eq!(
+                scores(linked.tree_ref().unwrap()),
+                scores(gemla.tree_ref().unwrap())
+            );
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    #[traced_test]
+    fn test_simulate_spans() -> Result<(), Error> {
+        let path = PathBuf::from("test_simulate_spans");
+        CleanUp::new(&path).run(|p| {
+            let config = GemlaConfig {
+                overwrite: true,
+                ..GemlaConfig::default()
+            };
+            let mut gemla = Gemla::<TestState>::new(p, config)?;
+
+            smol::block_on(gemla.simulate(2))?;
+
+            assert!(logs_contain("increase_height{steps=2}"));
+            assert!(logs_contain("join_threads{nodes="));
+            assert!(logs_contain(&format!(
+                "process_node{{id={} path=root generation=1 state=Simulate}}",
+                gemla.tree_ref().unwrap().val.id()
+            )));
+
+            Ok(())
+        })
+    }
+
+    fn count_finished<S: GeneticNode + Debug>(tree: &SimulationTree<S>) -> usize {
+        let current = if tree.val.state() == GeneticState::Finish {
+            1
+        } else {
+            0
+        };
+
+        current
+            + tree.left.as_ref().map(count_finished).unwrap_or(0)
+            + tree.right.as_ref().map(count_finished).unwrap_or(0)
+    }
+
+    #[test]
+    fn test_run_budget() -> Result<(), Error> {
+        let path = PathBuf::from("test_run_budget");
+        CleanUp::new(&path).run(|p| {
+            let config = GemlaConfig {
+                generations_per_node: 2,
+                overwrite: true,
+                ..GemlaConfig::default()
+            };
+            let mut gemla = Gemla::<TestState>::new(p, config)?;
+
+            gemla.grow(4)?;
+            assert_eq!(count_finished(gemla.tree_ref().unwrap()), 0);
+
+            // No time is given so nothing should be processed
+            smol::block_on(gemla.run(RunBudget::Duration(Duration::from_secs(0))))?;
+            assert_eq!(count_finished(gemla.tree_ref().unwrap()), 0);
+
+            smol::block_on(gemla.run(RunBudget::Nodes(2)))?;
+            assert_eq!(count_finished(gemla.tree_ref().unwrap()), 2);
+            drop(gemla);
+
+            // Completed work should be persisted
+            let config = GemlaConfig {
+                overwrite: false,
+                ..config
+            };
+            let mut gemla = Gemla::<TestState>::new(p, config)?;
+            assert_eq!(count_finished(gemla.tree_ref().unwrap()), 2);
+
+            smol::block_on(gemla.run(RunBudget::Unbounded))?;
+            let tree = gemla.tree_ref().unwrap();
+            assert_eq!(count_finished(tree), 7);
+            assert_eq!(tree.val.as_ref().unwrap().score, 8.0);
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_export_history() -> Result<(), Error> {
+        let path = PathBuf::from("test_export_history");
+        CleanUp::new(&path).run(|p| {
+            let mut config = GemlaConfig {
+                generations_per_node: 3,
+                overwrite: true,
+                ..GemlaConfig::default()
+            };
+            let mut gemla = Gemla::<TestState>::new(p, config)?;
+            smol::block_on(gemla.simulate(1))?;
+            assert!(gemla.export_history().is_empty());
+            drop(gemla);
+
+            config.record_history = true;
+            let mut gemla = Gemla::<TestState>::new(p, config)?;
+            smol::block_on(gemla.simulate(1))?;
+
+            let id = gemla.tree_ref().unwrap().val.id();
+            assert_eq!(
+                gemla.export_history(),
+                (1..=3)
+                    .map(|g| GenerationRecord {
+                        id,
+                        generation: g,
+                        fitness: g as f64
+                    })
+                    .collect::<Vec<GenerationRecord>>()
+            );
+
+            // Records should continue to accumulate as the tree grows
+            smol::block_on(gemla.simulate(1))?;
+            let history = gemla.export_history();
+            assert_eq!(history.len(), 3 + 3 + 3);
+            assert_eq!(history.last().unwrap().fitness, 6.0);
+            drop(gemla);
+
+            config.overwrite = false;
+            let gemla = Gemla::<TestState>::new(p, config)?;
+            assert_eq!(gemla.export_history(), history);
+
+            Ok(())
+        })
+    }
+
+    static SIMULATE_CALLS: Mutex<Vec<(Uuid, u64)>> = Mutex::new(vec![]);
+    static FAIL_ON_CALL: AtomicUsize = AtomicUsize::new(usize::MAX);
+    static CALL_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+    #[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+    struct CountingState {
+        pub tag: Uuid,
+        pub generation: u64,
+    }
+
+    impl genetic_node::GeneticNode for CountingState {
+        fn simulate(&mut self, _context: GeneticNodeContext) -> Result<(), Error> {
+            if CALL_COUNT.fetch_add(1, Ordering::SeqCst) == FAIL_ON_CALL.load(Ordering::SeqCst) {
+                return Err(Error::Other(anyhow!("Injected failure")));
+            }
+
+            self.generation += 1;
+            SIMULATE_CALLS
+                .lock()
+                .unwrap()
+                .push((self.tag, self.generation));
+            Ok(())
+        }
+
+        fn mutate(&mut self, _context: GeneticNodeContext) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn initialize(_context: GeneticNodeContext) -> Result<Box<CountingState>, Error> {
+            Ok(Box::new(CountingState {
+                tag: Uuid::new_v4(),
+                generation: 0,
+            }))
+        }
+
+        fn fitness(&self) -> f64 {
+            self.generation as f64
+        }
+
+        fn population_size(&self) -> usize {
+            1
+        }
+
+        fn merge(_l: &CountingState, _r: &CountingState) -> Result<Box<CountingState>, Error> {
+            Ok(Box::new(CountingState {
+                tag: Uuid::new_v4(),
+                generation: 0,
+            }))
+        }
+    }
+
+    #[test]
+    fn test_resume_after_error() -> Result<(), Error> {
+        let path = PathBuf::from("test_resume_after_error");
+        CleanUp::new(&path).run(|p| {
+            let config = GemlaConfig {
+                generations_per_node: 2,
+                overwrite: true,
+                ..GemlaConfig::default()
+            };
+            let mut gemla = Gemla::<CountingState>::new(p, config)?;
+
+            // Both leaves are simulated together, the second simulation fails and aborts the run
+            FAIL_ON_CALL.store(1, Ordering::SeqCst);
+            assert!(smol::block_on(gemla.simulate(2)).is_err());
+            assert_eq!(SIMULATE_CALLS.lock().unwrap().len(), 1);
+
+            smol::block_on(gemla.simulate(0))?;
+            assert_eq!(gemla.tree_ref().unwrap().val.state(), GeneticState::Finish);
+
+            let calls = SIMULATE_CALLS.lock().unwrap();
+            let unique_calls = calls.iter().collect::<HashSet<&(Uuid, u64)>>();
+            assert_eq!(calls.len(), unique_calls.len());
+            // Two generations for each of the three nodes
+            assert_eq!(calls.len(), 6);
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_replace_stale_node() {
+        let mut node = GeneticNodeWrapper::<TestState>::new(2);
+        let stale = node.clone();
+        for _ in 0..4 {
+            node.process_node().unwrap();
+        }
+        assert_eq!(node.generation(), 2);
+
+        let mut tree: SimulationTree<TestState> = Box::new(btree!(node.clone()));
+        let unmatched = Gemla::replace_nodes(&mut tree, vec![stale]);
+
+        assert!(unmatched.is_empty());
+        assert_eq!(tree.val, node);
+    }
+
+    struct FieldVisitor(HashMap<String, String>);
+
+    impl Visit for FieldVisitor {
+        fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+            self.0
+                .insert(field.name().to_string(), format!("{:?}", value));
+        }
+    }
+
+    type CapturedSpan = (String, HashMap<String, String>);
+
+    #[derive(Clone, Default)]
+    struct SpanCapture {
+        spans: Arc<Mutex<Vec<CapturedSpan>>>,
+    }
+
+    impl<S: Subscriber> Layer<S> for SpanCapture {
+        fn on_new_span(&self, attrs: &Attributes<'_>, _id: &Id, _ctx: Context<'_, S>) {
+            let mut visitor = FieldVisitor(HashMap::new());
+            attrs.record(&mut visitor);
+
+            self.spans
+                .lock()
+                .unwrap()
+                .push((attrs.metadata().name().to_string(), visitor.0));
+        }
+    }
+
+    #[test]
+    fn test_process_node_span_fields() -> Result<(), Error> {
+        let path = PathBuf::from("test_process_node_span_fields");
+        CleanUp::new(&path).run(|p| {
+            let config = GemlaConfig {
+                overwrite: true,
+                ..GemlaConfig::default()
+            };
+            let mut gemla = Gemla::<TestState>::new(p, config)?;
+
+            let capture = SpanCapture::default();
+            let subscriber = tracing_subscriber::registry().with(capture.clone());
+            tracing::subscriber::with_default(subscriber, || smol::block_on(gemla.simulate(1)))?;
+
+            let id = gemla.tree_ref().unwrap().val.id().to_string();
+            let spans = capture.spans.lock().unwrap();
+            let process_spans = spans
+                .iter()
+                .filter(|(name, _)| name == "process_node")
+                .map(|(_, fields)| fields)
+                .collect::<Vec<&HashMap<String, String>>>();
+
+            assert_eq!(process_spans.len(), 3);
+            for (fields, state) in process_spans
+                .iter()
+                .zip(["Initialize", "Simulate", "Score"])
+            {
+                assert_eq!(fields["id"], id);
+                assert_eq!(fields["path"], "root");
+                assert_eq!(fields["generation"], "1");
+                assert_eq!(fields["state"], state);
+            }
+
+            assert!(spans.iter().any(|(name, _)| name == "join_threads"));
+            assert!(spans
+                .iter()
+                .any(|(name, _)| name == "merge_completed_nodes"));
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_worker_pool_size() -> Result<(), Error> {
+        let config = GemlaConfig {
+            fan_in: 8,
+            worker_pool_size: Some(3),
+            ..GemlaConfig::default()
+        };
+        let mut gemla = Gemla::<TestState>::in_memory(config)?;
+        gemla.grow(2)?;
+
+        let capture = SpanCapture::default();
+        let subscriber = tracing_subscriber::registry().with(capture.clone());
+        tracing::subscriber::with_default(subscriber, || {
+            smol::block_on(gemla.run(RunBudget::Unbounded))
+        })?;
+        assert!(gemla.report().completed);
+
+        // Eight leaves are ready at once, but no more than three are ever pending
+        let in_flight = capture
+            .spans
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(name, _)| name == "join_threads")
+            .map(|(_, fields)| fields["nodes"].parse::<usize>().unwrap())
+            .collect::<Vec<_>>();
+        assert!(in_flight.iter().all(|n| *n <= 3));
+        assert!(in_flight.contains(&3));
+
+        assert!(Gemla::<TestState>::in_memory(GemlaConfig {
+            worker_pool_size: Some(0),
+            ..GemlaConfig::default()
+        })
+        .is_err());
+
+        Ok(())
+    }
+
+    #[cfg(feature = "metrics")]
+    #[test]
+    fn test_serve_metrics() -> Result<(), Error> {
+        use std::{
+            io::{Read, Write},
+            net::TcpStream,
+        };
+
+        let mut gemla = Gemla::<TestState>::in_memory(GemlaConfig {
+            generations_per_node: 2,
+            ..GemlaConfig::default()
+        })?;
+        let addr = gemla.serve_metrics("127.0.0.1:0")?;
+
+        gemla.grow(2)?;
+        smol::block_on(gemla.run(RunBudget::Unbounded))?;
+
+        let mut stream = TcpStream::connect(addr)?;
+        stream.write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n")?;
+        let mut response = String::new();
+        stream.read_to_string(&mut response)?;
+
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        let value = |name: &str| {
+            response
+                .lines()
+                .find_map(|l| l.strip_prefix(name))
+                .and_then(|v| v.trim().parse::<f64>().ok())
+                .unwrap_or_else(|| panic!("{} missing from\n{}", name, response))
+        };
+        // Two generations on each of the three nodes
+        assert_eq!(value("gemla_generations_completed_total "), 6.0);
+        assert_eq!(value("gemla_nodes{state=\"Finish\"} "), 3.0);
+        assert!(value("gemla_node_processing_seconds_count ") > 0.0);
+        assert!(value("gemla_file_write_seconds_count ") > 0.0);
+        assert!(value("gemla_best_fitness ") > 0.0);
+
+        Ok(())
+    }
+
+    #[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+    struct RolloutState {
+        pub rollouts: u64,
+        pub score: f64,
+    }
+
+    impl genetic_node::GeneticNode for RolloutState {
+        fn simulate(&mut self, _context: GeneticNodeContext) -> Result<(), Error> {
+            self.rollouts += 1;
+            Ok(())
+        }
+
+        fn mutate(&mut self, _context: GeneticNodeContext) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn next_state(&self, current: GeneticState, context: &GeneticNodeContext) -> GeneticState {
+            // Rollouts are evaluated in their own state instead of being scored
+            match current {
+                GeneticState::Simulate => GeneticState::Custom(1),
+                GeneticState::Custom(_) if context.generation >= context.max_generations => {
+                    GeneticState::Finish
+                }
+                GeneticState::Custom(_) => GeneticState::Mutate,
+                _ => GeneticState::Simulate,
+            }
+        }
+
+        fn custom_step(&mut self, _step: u8, _context: GeneticNodeContext) -> Result<(), Error> {
+            self.score = self.rollouts as f64;
+            Ok(())
+        }
+
+        fn initialize(_context: GeneticNodeContext) -> Result<Box<RolloutState>, Error> {
+            Ok(Box::new(RolloutState {
+                rollouts: 0,
+                score: 0.0,
+            }))
+        }
+
+        fn fitness(&self) -> f64 {
+            self.score
+        }
+
+        fn population_size(&self) -> usize {
+            1
+        }
+
+        fn merge(left: &RolloutState, right: &RolloutState) -> Result<Box<RolloutState>, Error> {
+            Ok(Box::new(if left.score > right.score {
+                left.clone()
+            } else {
+                right.clone()
+            }))
+        }
+    }
+
+    #[test]
+    fn test_metrics_custom_state() -> Result<(), Error> {
+        let mut gemla = Gemla::<RolloutState>::in_memory(GemlaConfig {
+            generations_per_node: 3,
+            ..GemlaConfig::default()
+        })?;
+        gemla.grow(1)?;
+        let rendered = |gemla: &Gemla<RolloutState>| gemla.metrics().render();
+
+        // Initialized, then simulated into the custom state
+        for _ in 0..2 {
+            let node = gemla.tree_ref().unwrap().val.clone();
+            gemla.start_node(node);
+            smol::block_on(gemla.join_threads())?;
+        }
+        assert!(rendered(&gemla)
+            .lines()
+            .any(|l| l == "gemla_nodes{state=\"Custom(1)\"} 1"));
+
+        // Generations are completed even though the node is never scored
+        smol::block_on(gemla.run(RunBudget::Unbounded))?;
+        let rendered = rendered(&gemla);
+        for line in [
+            "gemla_nodes{state=\"Finish\"} 1",
+            "gemla_generations_completed_total 3",
+        ] {
+            assert!(
+                rendered.lines().any(|l| l == line),
+                "{} missing from\n{}",
+                line,
+                rendered
+            );
+        }
+        // Custom states no node is in are left out
+        assert!(!rendered.contains("Custom"));
+
+        Ok(())
+    }
+
+    static FLAKY_SIMULATIONS: AtomicUsize = AtomicUsize::new(0);
+    static FLAKY_FATAL: AtomicBool = AtomicBool::new(false);
+
+    #[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+    struct FlakyState {
+        pub score: f64,
+    }
+
+    impl genetic_node::GeneticNode for FlakyState {
+        fn simulate(&mut self, _context: GeneticNodeContext) -> Result<(), Error> {
+            // The first simulation times out, later ones only fail once they are made to
+            if FLAKY_SIMULATIONS.fetch_add(1, Ordering::SeqCst) == 0 {
+                return Err(Error::transient(anyhow!("Match timed out")));
+            }
+            if FLAKY_FATAL.load(Ordering::SeqCst) {
+                return Err(Error::Other(anyhow!("Invalid population")));
+            }
+
+            self.score += 1.0;
+            Ok(())
+        }
+
+        fn mutate(&mut self, _context: GeneticNodeContext) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn initialize(_context: GeneticNodeContext) -> Result<Box<FlakyState>, Error> {
+            Ok(Box::new(FlakyState { score: 0.0 }))
+        }
+
+        fn fitness(&self) -> f64 {
+            self.score
+        }
+
+        fn population_size(&self) -> usize {
+            1
+        }
+
+        fn merge(left: &FlakyState, right: &FlakyState) -> Result<Box<FlakyState>, Error> {
+            Ok(Box::new(FlakyState {
+                score: left.score.max(right.score),
+            }))
+        }
+    }
+
+    #[test]
+    fn test_transient_errors() -> Result<(), Error> {
+        // A transient error is logged and the node is processed again
+        let mut gemla = Gemla::<FlakyState>::in_memory(GemlaConfig::default())?;
+        smol::block_on(gemla.simulate(1))?;
+        assert_eq!(FLAKY_SIMULATIONS.load(Ordering::SeqCst), 2);
+
+        let root = &gemla.tree_ref().unwrap().val;
+        assert_eq!(root.state(), GeneticState::Finish);
+        assert_eq!(root.as_ref().unwrap().score, 1.0);
+        assert!(root.failure().is_none());
+
+        // Other errors are recorded on the node and stop the run
+        FLAKY_FATAL.store(true, Ordering::SeqCst);
+        let mut gemla = Gemla::<FlakyState>::in_memory(GemlaConfig::default())?;
+        let error = smol::block_on(gemla.simulate(1)).unwrap_err();
+        assert!(!error.is_transient());
+        assert!(gemla.tree_ref().unwrap().val.failure().is_some());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_prune() -> Result<(), Error> {
+        let path = PathBuf::from("test_prune");
+        CleanUp::new(&path).run(|p| {
+            let mut gemla = Gemla::<TestState>::new(
+                p,
+                GemlaConfig {
+                    overwrite: true,
+                    ..GemlaConfig::default()
+                },
+            )?;
+            gemla.grow(2)?;
+
+            let tree = gemla.tree_ref().unwrap();
+            let leaf = tree.left.as_ref().unwrap().val.id();
+            let other = tree.right.as_ref().unwrap().val.id();
+
+            // Nodes that are being processed can't be pruned
+            gemla.threads.insert(
+                "root/R".to_string(),
+                (
+                    other,
+                    Box::pin(async { Err(Error::Other(anyhow!("Unused"))) }),
+                ),
+            );
+            assert!(gemla.prune(other).is_err());
+            gemla.threads.clear();
+
+            assert!(gemla.prune(leaf)?);
+            assert!(!gemla.prune(leaf)?);
+
+            let tree = gemla.tree_ref().unwrap();
+            assert!(Gemla::find_subtree(tree, leaf).is_none());
+            assert!(Gemla::find_subtree(tree, other).is_some());
+            assert!(tree.left.is_none());
+
+            drop(gemla);
+
+            // The pruned tree is persisted to the file
+            let gemla = Gemla::<TestState>::new(p, GemlaConfig::default())?;
+            assert!(Gemla::find_subtree(gemla.tree_ref().unwrap(), leaf).is_none());
+
+            Ok(())
+        })
+    }
+
+    static SIMULATED: Mutex<Vec<Uuid>> = Mutex::new(vec![]);
+
+    #[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+    struct ResetState;
+
+    impl genetic_node::GeneticNode for ResetState {
+        fn simulate(&mut self, context: GeneticNodeContext) -> Result<(), Error> {
+            SIMULATED.lock().unwrap().push(context.id);
+            Ok(())
+        }
+
+        fn mutate(&mut self, _context: GeneticNodeContext) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn initialize(_context: GeneticNodeContext) -> Result<Box<ResetState>, Error> {
+            Ok(Box::new(ResetState))
+        }
+
+        fn fitness(&self) -> f64 {
+            0.0
+        }
+
+        fn population_size(&self) -> usize {
+            1
+        }
+
+        fn merge(_l: &ResetState, _r: &ResetState) -> Result<Box<ResetState>, Error> {
+            Ok(Box::new(ResetState))
+        }
+    }
+
+    #[test]
+    fn test_right_branch_mode() -> Result<(), Error> {
+        // Grows a finished tree of two levels by one more level, returning the champion and the new right branch
+        let grow = |right_branch_mode| -> Result<(TestState, SimulationTree<TestState>), Error> {
+            let mut gemla = Gemla::<TestState>::in_memory(GemlaConfig {
+                right_branch_mode,
+                ..GemlaConfig::default()
+            })?;
+            smol::block_on(gemla.simulate(2))?;
+            let champion = gemla.tree_ref().unwrap().val.as_ref().unwrap().clone();
+
+            gemla.grow(1)?;
+            let right = gemla.tree_ref().unwrap().right.clone().unwrap();
+
+            smol::block_on(gemla.run(RunBudget::Unbounded))?;
+            assert!(gemla.report().completed);
+
+            Ok((champion, right))
+        };
+
+        // A single new node standing in for both levels
+        let (_, fresh) = grow(RightBranchMode::Fresh)?;
+        assert_eq!(fresh.height(), 1);
+        assert_eq!(fresh.val.state(), GeneticState::Initialize);
+        assert_eq!(fresh.val.max_generations(), 2);
+
+        let (champion, cloned) = grow(RightBranchMode::CloneChampion)?;
+        assert_eq!(cloned.height(), 1);
+        assert_eq!(cloned.val.state(), GeneticState::Simulate);
+        assert_eq!(cloned.val.as_ref(), Some(&champion));
+        assert_eq!(cloned.val.max_generations(), 2);
+
+        // A new bracket of the same height as the existing tree
+        let (_, mirrored) = grow(RightBranchMode::MirrorTree)?;
+        assert_eq!(mirrored.height(), 2);
+        assert_eq!(mirrored.val.height(), 2);
+        for node in [
+            &mirrored.val,
+            &mirrored.left.as_ref().unwrap().val,
+            &mirrored.right.as_ref().unwrap().val,
+        ] {
+            assert_eq!(node.state(), GeneticState::Initialize);
+            assert_eq!(node.max_generations(), 1);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_scheduling_order() -> Result<(), Error> {
+        // Returns the scores of the nodes in the order they're scheduled, each leaf runs for `score` generations
+        let next = |scheduling_order, tree| -> Result<Vec<f64>, Error> {
+            let mut gemla = Gemla::<TestState>::in_memory(GemlaConfig {
+                scheduling_order,
+                ..GemlaConfig::default()
+            })?;
+            gemla.data.mutate(|(d, _, _)| *d = Some(Box::new(tree)))?;
+
+            // Each node is marked as in flight once it's scheduled so the next one is picked
+            let mut scores = vec![];
+            while let Some(node) = gemla
+                .tree_ref()
+                .and_then(|t| gemla.get_unprocessed_node(t, &|_| true))
+            {
+                scores.push(node.as_ref().unwrap().score);
+                gemla.start_node(node);
+            }
+            Ok(scores)
+        };
+        let leaf = |score: f64| {
+            btree!(GeneticNodeWrapper::from(
+                TestState { score },
+                score as u64,
+                Uuid::new_v4()
+            ))
+        };
+
+        let pair = || btree!(GeneticNodeWrapper::new(1), leaf(1.0), leaf(5.0));
+        assert_eq!(next(SchedulingOrder::DepthFirst, pair())?, vec![1.0, 5.0]);
+        assert_eq!(next(SchedulingOrder::BestFirst, pair())?, vec![5.0, 1.0]);
+        assert_eq!(
+            next(SchedulingOrder::CriticalPathFirst, pair())?,
+            vec![5.0, 1.0]
+        );
+
+        // A deeper left subtree is interleaved with the right one
+        let uneven = || {
+            btree!(
+                GeneticNodeWrapper::new(1),
+                btree!(GeneticNodeWrapper::new(1), leaf(1.0), leaf(2.0)),
+                leaf(3.0)
+            )
+        };
+        assert_eq!(
+            next(SchedulingOrder::DepthFirst, uneven())?,
+            vec![1.0, 2.0, 3.0]
+        );
+        assert_eq!(
+            next(SchedulingOrder::RoundRobin, uneven())?,
+            vec![1.0, 3.0, 2.0]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_reset_subtree() -> Result<(), Error> {
+        let path = PathBuf::from("test_reset_subtree");
+        CleanUp::new(&path).run(|p| {
+            let config = GemlaConfig {
+                overwrite: true,
+                ..GemlaConfig::default()
+            };
+            let mut gemla = Gemla::<ResetState>::new(p, config)?;
+            smol::block_on(gemla.simulate(3))?;
+
+            let tree = gemla.tree_ref().unwrap();
+            let root = tree.val.id();
+            let middle = tree.left.as_ref().unwrap();
+            let mut expected = Gemla::subtree_ids(middle);
+            expected.push(root);
+            expected.sort();
+            let middle = middle.val.id();
+
+            assert_eq!(gemla.reset_subtree(Uuid::new_v4())?, 0);
+            assert_eq!(gemla.reset_subtree(middle)?, 4);
+            assert!(!gemla.report().completed);
+            drop(gemla);
+
+            // The reset is persisted, and only the middle node, its children and the root are processed again
+            let mut gemla = Gemla::<ResetState>::new(
+                p,
+                GemlaConfig {
+                    overwrite: false,
+                    ..config
+                },
+            )?;
+            SIMULATED.lock().unwrap().clear();
+            smol::block_on(gemla.run(RunBudget::Unbounded))?;
+            assert!(gemla.report().completed);
+
+            let mut simulated = SIMULATED.lock().unwrap().clone();
+            simulated.sort();
+            assert_eq!(simulated, expected);
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    #[traced_test]
+    fn test_effective_config() -> Result<(), Error> {
+        let path = PathBuf::from("test_effective_config");
+        CleanUp::new(&path).run(|p| {
+            let mut gemla = Gemla::<TestState>::new(
+                p,
+                GemlaConfig {
+                    overwrite: true,
+                    ..GemlaConfig::default()
+                },
+            )?;
+            gemla.grow(1)?;
+            drop(gemla);
+
+            let mut gemla = Gemla::<TestState>::new(
+                p,
+                GemlaConfig {
+                    generations_per_node: 10,
+                    fan_in: 4,
+                    record_history: true,
+                    merge_strategy: MergeStrategy::TakeBest,
+                    target_population: Some(8),
+                    ..GemlaConfig::default()
+                },
+            )?;
+
+            let expected = GemlaConfig {
+                generations_per_node: 10,
+                record_history: true,
+                merge_strategy: MergeStrategy::TakeBest,
+                target_population: Some(8),
+                ..GemlaConfig::default()
+            };
+            assert_eq!(gemla.effective_config(), expected);
+            assert!(logs_contain(
+                "Runtime config overrides persisted config for fields: generations_per_node, overwrite, \
+                 record_history, merge_strategy, target_population"
+            ));
+            assert!(logs_contain(
+                "Keeping persisted values to stay consistent with the existing tree for fields: fan_in"
+            ));
+
+            // Nodes added after resuming use the runtime generations_per_node
+            let old_root = gemla.tree_ref().unwrap().val.id();
+            gemla.grow(1)?;
+
+            let tree = gemla.tree_ref().unwrap();
+            assert_eq!(tree.val.max_generations(), 10);
+            assert_eq!(tree.right.as_ref().unwrap().val.max_generations(), 10);
+            assert_eq!(tree.left.as_ref().unwrap().val.id(), old_root);
+            assert_eq!(tree.left.as_ref().unwrap().val.max_generations(), 1);
+            drop(gemla);
+
+            // The merged config is persisted
+            let gemla = Gemla::<TestState>::new(p, expected)?;
+            assert_eq!(gemla.effective_config(), expected);
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_nodes_in_flight() -> Result<(), Error> {
+        let mut gemla = Gemla::<TestState>::in_memory(GemlaConfig {
+            overwrite: true,
+            ..GemlaConfig::default()
+        })?;
+        assert!(gemla.is_idle());
+        assert!(gemla.nodes_in_flight().is_empty());
+
+        gemla.grow(2)?;
+
+        // Scheduling a single node leaves it in flight until the threads are joined
+        let node = gemla
+            .tree_ref()
+            .and_then(|t| gemla.get_unprocessed_node(t, &|_| true))
+            .unwrap();
+        let id = node.id();
+        gemla.threads.insert(
+            "root/L".to_string(),
+            (
+                id,
+                Box::pin(Gemla::process_node(node, "root/L".to_string())),
+            ),
+        );
+
+        assert!(!gemla.is_idle());
+        assert_eq!(gemla.nodes_in_flight(), vec![id]);
+
+        smol::block_on(gemla.join_threads())?;
+        assert!(gemla.is_idle());
+
+        smol::block_on(gemla.run(RunBudget::Unbounded))?;
+        assert!(gemla.is_idle());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_runtime_agnostic() -> Result<(), Error> {
+        // A join deadline makes joining wait on a timer and subscribing makes every join send on a channel
+        let config = GemlaConfig {
+            generations_per_node: 2,
+            join_deadline: Some(Duration::from_secs(10)),
+            ..GemlaConfig::default()
+        };
+
+        let mut with_smol = Gemla::<TestState>::in_memory(config)?;
+        let smol_progress = with_smol.subscribe_progress();
+        smol::block_on(with_smol.simulate(3))?;
+
+        let mut with_futures = Gemla::<TestState>::in_memory(config)?;
+        let futures_progress = with_futures.subscribe_progress();
+        futures::executor::block_on(with_futures.simulate(3))?;
+
+        for gemla in [&with_smol, &with_futures] {
+            assert!(gemla.report().completed);
+            assert_eq!(gemla.tree_ref().unwrap().val.as_ref().unwrap().score, 6.0);
+        }
+        assert!(!smol_progress.is_empty());
+        assert_eq!(smol_progress.len(), futures_progress.len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_join_deadline() -> Result<(), Error> {
+        let mut gemla = Gemla::<TestState>::in_memory(GemlaConfig {
+            join_deadline: Some(Duration::from_millis(50)),
+            ..GemlaConfig::default()
+        })?;
+        gemla.grow(2)?;
+
+        let tree = gemla.tree_ref().unwrap();
+        let fast = tree.left.as_ref().unwrap().val.clone();
+        let slow = tree.right.as_ref().unwrap().val.id();
+        gemla.threads.insert(
+            "root/L".to_string(),
+            (
+                fast.id(),
+                Box::pin(Gemla::process_node(fast, "root/L".to_string())),
+            ),
+        );
+        gemla.threads.insert(
+            "root/R".to_string(),
+            (
+                slow,
+                Box::pin(async {
+                    Timer::after(Duration::from_secs(60)).await;
+                    Err(Error::Other(anyhow!("Slow node should not be joined")))
+                }),
+            ),
+        );
+
+        // The fast node is collected and the slow node is left to be joined later
+        smol::block_on(gemla.join_threads())?;
+        assert_eq!(gemla.nodes_in_flight(), vec![slow]);
+
+        let tree = gemla.tree_ref().unwrap();
+        assert_eq!(
+            tree.left.as_ref().unwrap().val.state(),
+            GeneticState::Simulate
+        );
+        assert_eq!(
+            tree.right.as_ref().unwrap().val.state(),
+            GeneticState::Initialize
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_run_after_join_deadline() -> Result<(), Error> {
+        let mut gemla = Gemla::<TestState>::in_memory(GemlaConfig {
+            join_deadline: Some(Duration::from_millis(50)),
+            ..GemlaConfig::default()
+        })?;
+        gemla.grow(2)?;
+
+        let slow = gemla
+            .tree_ref()
+            .unwrap()
+            .right
+            .as_ref()
+            .unwrap()
+            .val
+            .clone();
+        let (id, max_generations) = (slow.id(), slow.max_generations());
+        gemla.threads.insert(
+            "root/R".to_string(),
+            (
+                id,
+                Box::pin(async move {
+                    Timer::after(Duration::from_millis(200)).await;
+                    Ok(GeneticNodeWrapper::from(
+                        TestState { score: 100.0 },
+                        max_generations,
+                        id,
+                    ))
+                }),
+            ),
+        );
+
+        // The budget runs out straight away and the slow node is still pending once the deadline passes
+        smol::block_on(gemla.run(RunBudget::Duration(Duration::ZERO)))?;
+        assert_eq!(gemla.nodes_in_flight(), vec![id]);
+
+        // The next run joins the slow node instead of processing it again
+        smol::block_on(gemla.run(RunBudget::Unbounded))?;
+        assert!(gemla.is_idle());
+        assert!(gemla.report().completed);
+        assert!(gemla.tree_ref().unwrap().val.as_ref().unwrap().score > 100.0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_export() -> Result<(), Error> {
+        let path = PathBuf::from("test_export");
+        CleanUp::new(&path).run(|p| {
+            let mut gemla = Gemla::<TestState>::new(
+                p,
+                GemlaConfig {
+                    overwrite: true,
+                    ..GemlaConfig::default()
+                },
+            )?;
+            smol::block_on(gemla.simulate(2))?;
+            let len = gemla.tree_ref().unwrap().len();
+
+            let csv_path = PathBuf::from("test_export.csv");
+            CleanUp::new(&csv_path).run(|c| {
+                gemla.export(c, ExportFormat::Csv)?;
+
+                let contents = fs::read_to_string(c)?;
+                let mut lines = contents.lines();
+                assert_eq!(
+                    lines.next(),
+                    Some("id,path,state,generation,max_generations,fitness,failure")
+                );
+
+                let rows = lines
+                    .map(|l| l.split(',').collect::<Vec<&str>>())
+                    .collect::<Vec<Vec<&str>>>();
+                assert_eq!(rows.len(), len);
+                assert_eq!(rows[0][1], "root");
+                assert_eq!(rows[0][2], "Finish");
+                assert_eq!(rows[0][5].parse::<f64>().ok(), Some(2.0));
+
+                Ok(())
+            })?;
+
+            let json_path = PathBuf::from("test_export.json");
+            CleanUp::new(&json_path).run(|j| {
+                gemla.export(j, ExportFormat::Json)?;
+
+                let records: Vec<serde_json::Value> =
+                    serde_json::from_str(&fs::read_to_string(j)?).map_err(anyhow::Error::from)?;
+                assert_eq!(records.len(), len);
+                assert_eq!(records[0]["path"], "root");
+                assert_eq!(records[0]["node"]["score"], 2.0);
+
+                Ok(())
+            })?;
+
+            let dot_path = PathBuf::from("test_export.dot");
+            CleanUp::new(&dot_path).run(|d| {
+                gemla.export(d, ExportFormat::Dot)?;
+
+                let contents = fs::read_to_string(d)?;
+                let tree = gemla.tree_ref().unwrap();
+                assert!(contents.starts_with("digraph gemla {\n"));
+                assert!(contents.ends_with("}\n"));
+                assert!(contents.contains(&format!(
+                    "\"{}\" [label=\"root\\nFinish 1/1\"];",
+                    tree.val.id()
+                )));
+                assert!(contents.contains(&format!(
+                    "\"{}\" -> \"{}\";",
+                    tree.val.id(),
+                    tree.left.as_ref().unwrap().val.id()
+                )));
+                assert_eq!(contents.matches("->").count(), len - 1);
+
+                Ok(())
+            })
+        })
+    }
+
+    #[test]
+    fn test_shutdown() -> Result<(), Error> {
+        let path = PathBuf::from("test_shutdown");
+        CleanUp::new(&path).run(|p| {
+            let mut gemla = Gemla::<TestState>::new(
+                p,
+                GemlaConfig {
+                    overwrite: true,
+                    ..GemlaConfig::default()
+                },
+            )?;
+            gemla.grow(2)?;
+            smol::block_on(gemla.run(RunBudget::Nodes(1)))?;
+            smol::block_on(gemla.shutdown())?;
+
+            let gemla = Gemla::<TestState>::open_read_only(p)?;
+            assert_eq!(gemla.report().finished, 1);
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_subscribe_progress() -> Result<(), Error> {
+        let mut gemla = Gemla::<TestState>::in_memory(GemlaConfig::default())?;
+        let progress = gemla.subscribe_progress();
+        let dropped = gemla.subscribe_progress();
+        drop(dropped);
+
+        smol::block_on(gemla.simulate(2))?;
+        assert_eq!(gemla.progress.len(), 1);
+
+        let reports =
+            std::iter::from_fn(|| progress.try_recv().ok()).collect::<Vec<SimulationReport>>();
+        assert_eq!(reports[0].height, 2);
+        assert_eq!(reports[0].finished, 0);
+        assert_eq!(reports[0].best_fitness, None);
+
+        // Each report follows the previous one as nodes finish
+        assert!(reports.windows(2).all(|w| w[0].finished <= w[1].finished));
+        assert_eq!(reports.last(), Some(&gemla.report()));
+        assert!(gemla.report().completed);
+
+        drop(gemla);
+        assert!(progress.is_closed());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_open_read_only() -> Result<(), Error> {
+        let path = PathBuf::from("test_open_read_only");
+        CleanUp::new(&path).run(|p| {
+            let mut gemla = Gemla::<TestState>::new(
+                p,
+                GemlaConfig {
+                    overwrite: true,
+                    ..GemlaConfig::default()
+                },
+            )?;
+            gemla.grow(2)?;
+            smol::block_on(gemla.run(RunBudget::Nodes(1)))?;
+            drop(gemla);
+            let written = fs::read(p)?;
+
+            let mut gemla = Gemla::<TestState>::open_read_only(p)?;
+            assert_eq!(
+                gemla.report(),
+                SimulationReport {
+                    height: 2,
+                    nodes: 3,
+                    finished: 1,
+                    failed: 0,
+                    completed: false,
+                    best_fitness: Some(1.0),
+                }
+            );
+
+            // Changes are kept in memory only
+            smol::block_on(gemla.simulate(1))?;
+            assert!(gemla.report().completed);
+            drop(gemla);
+            assert_eq!(fs::read(p)?, written);
+
+            assert!(Gemla::<TestState>::open_read_only(&PathBuf::from(
+                "test_open_read_only_missing"
+            ))
+            .is_err());
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_plan() -> Result<(), Error> {
+        let path = PathBuf::from("test_plan");
+        CleanUp::new(&path).run(|p| {
+            let config = GemlaConfig {
+                overwrite: true,
+                ..GemlaConfig::default()
+            };
+
+            let gemla = Gemla::<TestState>::new(p, config)?;
+            let plan = gemla.plan(2);
+            assert_eq!(plan.nodes_per_level, vec![1, 2]);
+            assert_eq!(
+                plan.nodes
+                    .iter()
+                    .map(|n| (n.path.as_str(), n.max_generations))
+                    .collect::<Vec<(&str, u64)>>(),
+                vec![("root", 1), ("root/L", 1), ("root/R", 1)]
+            );
+            assert_eq!(plan.total_generations, 3);
+
+            // Planning doesn't modify the tree
+            assert!(gemla.tree_ref().is_none());
+            drop(gemla);
+
+            // Right branches train for as many generations as the left branch they are merged with
+            let gemla = Gemla::<TestState>::new(
+                p,
+                GemlaConfig {
+                    generations_per_node: 2,
+                    ..config
+                },
+            )?;
+            let plan = gemla.plan(3);
+            assert_eq!(plan.nodes_per_level, vec![1, 2, 2]);
+            assert_eq!(plan.total_generations, 2 + (2 + 2 + 2) + 4);
+            drop(gemla);
+
+            let mut gemla = Gemla::<TestState>::new(
+                p,
+                GemlaConfig {
+                    fan_in: 3,
+                    ..config
+                },
+            )?;
+            let plan = gemla.plan(2);
+            assert_eq!(plan.nodes_per_level, vec![1, 3]);
+            assert_eq!(plan.total_generations, 4);
+
+            // Plans for existing trees include the nodes already in the tree
+            gemla.grow(2)?;
+            let plan = gemla.plan(1);
+            assert_eq!(plan.nodes_per_level, vec![1, 3, 3]);
+            assert_eq!(plan.total_generations, 1 + (1 + 2 + 2) + (1 + 1 + 1));
+            assert_eq!(gemla.tree_ref().unwrap().height(), 3);
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_run_stalled() -> Result<(), Error> {
+        let mut gemla = Gemla::<TestState>::in_memory(GemlaConfig::default())?;
+
+        // A parent with a single child is never scheduled once the child has finished
+        let root = GeneticNodeWrapper::new(1);
+        let id = root.id();
+        gemla.data.mutate(|(d, _, _)| {
+            *d = Some(Box::new(btree!(root, btree!(GeneticNodeWrapper::new(1)),)))
+        })?;
+
+        match smol::block_on(gemla.run(RunBudget::Unbounded)) {
+            Err(Error::Stalled(ids)) => assert_eq!(ids, vec![id]),
+            result => panic!("Expected the simulation to stall, got {:?}", result),
+        }
+        assert!(gemla.is_idle());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_copy_single_child() -> Result<(), Error> {
+        let mut gemla = Gemla::<TestState>::in_memory(GemlaConfig::default())?;
+
+        let root = GeneticNodeWrapper::new(1);
+        let id = root.id();
+        let child = GeneticNodeWrapper::new(1);
+        let child_id = child.id();
+        gemla
+            .data
+            .mutate(|(d, _, _)| *d = Some(Box::new(btree!(root, btree!(child),))))?;
+
+        // The parent isn't scheduled afterwards, but it receives the data of its only child once the child finishes
+        assert!(smol::block_on(gemla.run(RunBudget::Unbounded)).is_err());
+        let tree = gemla.tree_ref().unwrap();
+        assert_eq!(tree.val.id(), id);
+        assert_eq!(tree.val.as_ref(), Some(&TestState { score: 1.0 }));
+        assert_eq!(tree.val.state(), GeneticState::Simulate);
+
+        assert_eq!(
+            gemla.merge_log(),
+            &[MergeRecord {
+                parent: id,
+                children: vec![child_id],
+                contributors: vec![child_id],
+            }]
+        );
+
+        Ok(())
+    }
+
+    #[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+    struct HeightState {
+        pub height: u64,
+    }
+
+    impl genetic_node::GeneticNode for HeightState {
+        fn simulate(&mut self, context: GeneticNodeContext) -> Result<(), Error> {
+            self.height = context.height;
+            Ok(())
+        }
+
+        fn mutate(&mut self, _context: GeneticNodeContext) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn initialize(context: GeneticNodeContext) -> Result<Box<HeightState>, Error> {
+            Ok(Box::new(HeightState {
+                height: context.height,
+            }))
+        }
+
+        fn fitness(&self) -> f64 {
+            0.0
+        }
+
+        fn population_size(&self) -> usize {
+            1
+        }
+
+        fn merge(left: &HeightState, _right: &HeightState) -> Result<Box<HeightState>, Error> {
+            Ok(Box::new(left.clone()))
+        }
+    }
+
+    #[test]
+    fn test_context_height() -> Result<(), Error> {
+        let mut gemla = Gemla::<HeightState>::in_memory(GemlaConfig::default())?;
+
+        smol::block_on(gemla.simulate(3))?;
+
+        let tree = gemla.tree_ref().unwrap();
+        assert_eq!(tree.val.height(), 3);
+        assert_eq!(tree.val.as_ref().unwrap().height, 3);
+
+        let left = tree.left.as_ref().unwrap();
+        assert_eq!(left.val.as_ref().unwrap().height, 2);
+
+        for leaf in [
+            left.left.as_ref().unwrap(),
+            left.right.as_ref().unwrap(),
+            tree.right.as_ref().unwrap(),
+        ] {
+            assert_eq!(leaf.val.height(), 1);
+            assert_eq!(leaf.val.as_ref().unwrap().height, 1);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_with_seed() -> Result<(), Error> {
+        let path = PathBuf::from("test_with_seed");
+        CleanUp::new(&path).run(|p| {
+            let config = GemlaConfig {
+                overwrite: true,
+                ..GemlaConfig::default()
+            };
+            let mut gemla = Gemla::<TestState>::new(p, config)?;
+            assert!(gemla.with_seed(vec![TestState { score: 1.0 }]).is_err());
+
+            gemla.grow(2)?;
+            assert!(gemla.with_seed(vec![TestState { score: 1.0 }; 3]).is_err());
+
+            gemla.with_seed(vec![TestState { score: 10.0 }, TestState { score: 20.0 }])?;
+            drop(gemla);
+
+            // The seeded leaves are persisted
+            let mut gemla = Gemla::<TestState>::new(
+                p,
+                GemlaConfig {
+                    overwrite: false,
+                    ..config
+                },
+            )?;
+            let tree = gemla.tree_ref().unwrap();
+            let leaves = [tree.left.as_ref().unwrap(), tree.right.as_ref().unwrap()];
+            assert_eq!(leaves[0].val.as_ref(), Some(&TestState { score: 10.0 }));
+            assert_eq!(leaves[1].val.as_ref(), Some(&TestState { score: 20.0 }));
+            assert_eq!(leaves[0].val.state(), GeneticState::Simulate);
+
+            smol::block_on(gemla.run(RunBudget::Unbounded))?;
+            assert_eq!(gemla.tree_ref().unwrap().val.as_ref().unwrap().score, 22.0);
+
+            Ok(())
+        })
+    }
+
+    /// Stores the score of every generation in its own file, in a folder named after the node
+    #[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+    struct DiskState {
+        pub score: f64,
+    }
+
+    impl genetic_node::GeneticNode for DiskState {
+        fn simulate(&mut self, _context: GeneticNodeContext) -> Result<(), Error> {
+            self.score += 1.0;
+            Ok(())
+        }
+
+        fn mutate(&mut self, _context: GeneticNodeContext) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn initialize(_context: GeneticNodeContext) -> Result<Box<DiskState>, Error> {
+            Ok(Box::new(DiskState { score: 0.0 }))
+        }
+
+        fn fitness(&self) -> f64 {
+            self.score
+        }
+
+        fn population_size(&self) -> usize {
+            1
+        }
+
+        fn merge(left: &DiskState, right: &DiskState) -> Result<Box<DiskState>, Error> {
+            Ok(Box::new(DiskState {
+                score: left.score.max(right.score),
+            }))
+        }
+
+        fn recover_from_disk(dir: &Path) -> Result<Vec<RecoveredNode<DiskState>>, Error> {
+            let mut recovered = vec![];
+
+            for entry in fs::read_dir(dir)? {
+                let entry = entry?;
+                let name = entry.file_name().to_string_lossy().into_owned();
+                let id = match name.strip_prefix("disk_state_").map(Uuid::parse_str) {
+                    Some(Ok(id)) => id,
+                    _ => continue,
+                };
+
+                let mut generations = fs::read_dir(entry.path())?
+                    .map(|g| {
+                        let g = g?;
+                        let generation = g.file_name().to_string_lossy().parse::<u64>();
+                        Ok((generation, g.path()))
+                    })
+                    .collect::<Result<Vec<_>, std::io::Error>>()?
+                    .into_iter()
+                    .filter_map(|(generation, path)| generation.ok().map(|g| (g, path)))
+                    .collect::<Vec<_>>();
+                generations.sort();
+
+                if let Some((generation, path)) = generations.pop() {
+                    let score = fs::read_to_string(path)?
+                        .trim()
+                        .parse::<f64>()
+                        .map_err(anyhow::Error::from)?;
+
+                    recovered.push(RecoveredNode {
+                        id,
+                        generation,
+                        node: DiskState { score },
+                    });
+                }
+            }
+
+            recovered.sort_by_key(|r| r.id);
+            Ok(recovered)
+        }
+    }
+
+    #[test]
+    fn test_rebuild_index() -> Result<(), Error> {
+        let dir = PathBuf::from("test_rebuild_index");
+        let ids = [Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4()];
+
+        let result = (|| {
+            for (i, id) in ids.iter().enumerate() {
+                let node_dir = dir.join(format!("disk_state_{}", id));
+                fs::create_dir_all(&node_dir)?;
+                for generation in 1..=(i as u64 + 1) {
+                    fs::write(
+                        node_dir.join(generation.to_string()),
+                        format!("{}", generation * 10),
+                    )?;
+                }
+            }
+            // Files that don't belong to a node are ignored
+            fs::write(dir.join("notes"), "")?;
+
+            let config = GemlaConfig {
+                generations_per_node: 2,
+                ..GemlaConfig::default()
+            };
+            let mut gemla = Gemla::<DiskState>::in_memory(config)?;
+            assert_eq!(gemla.rebuild_index(&dir)?, 3);
+            assert!(gemla.rebuild_index(&dir).is_err());
+
+            // Three leaves are needed so the tree grows to a height of 3, which has exactly three leaves
+            let tree = gemla.tree_ref().unwrap();
+            assert_eq!(tree.height(), 3);
+            for (i, id) in ids.iter().enumerate() {
+                // Each node continues from the latest generation written for it
+                let generation = i as u64 + 1;
+                let node = &Gemla::find_subtree(tree, *id).unwrap().val;
+                assert_eq!(node.generation(), generation);
+                // Leaves keep the generations they were given when the tree grew, which depend on their level
+                assert!(node.max_generations() >= generation.max(2));
+                assert_eq!(node.state(), GeneticState::Simulate);
+                assert_eq!(node.height(), 1);
+                assert_eq!(node.as_ref().unwrap().score, generation as f64 * 10.0);
+            }
+            assert_eq!(Gemla::count_seedable(tree), 0);
+
+            // Scores only grow, so the best recovered node carries its score of 30 up to the root
+            smol::block_on(gemla.run(RunBudget::Unbounded))?;
+            assert!(gemla.report().completed);
+            assert!(gemla.tree_ref().unwrap().val.as_ref().unwrap().score > 30.0);
+
+            let mut gemla = Gemla::<DiskState>::in_memory(config)?;
+            assert!(gemla.rebuild_index(&dir.join("missing")).is_err());
+
+            // Nothing is recovered from an empty directory
+            fs::create_dir_all(dir.join("empty"))?;
+            assert_eq!(gemla.rebuild_index(&dir.join("empty"))?, 0);
+            assert!(gemla.tree_ref().is_none());
+
+            Ok(())
+        })();
+
+        fs::remove_dir_all(&dir)?;
+        result
+    }
+
+    #[test]
+    fn test_print_tree() -> Result<(), Error> {
+        let mut gemla = Gemla::<TestState>::in_memory(GemlaConfig {
+            generations_per_node: 2,
+            ..GemlaConfig::default()
+        })?;
+        assert_eq!(gemla.print_tree(), "");
+
+        gemla.grow(2)?;
+        smol::block_on(gemla.run(RunBudget::Nodes(1)))?;
+
+        let tree = gemla.tree_ref().unwrap();
+        let short_id = |n: &GeneticNodeWrapper<TestState>| n.id().to_string()[..8].to_string();
+        assert_eq!(
+            gemla.print_tree(),
+            format!(
+                "{} Initialize 1/2\n  L {} Finish 2/2\n  R {} Initialize 1/2",
+                short_id(&tree.val),
+                short_id(&tree.left.as_ref().unwrap().val),
+                short_id(&tree.right.as_ref().unwrap().val)
+            )
+        );
+
+        Ok(())
+    }
+
+    /// The total generations along every path from the root to a leaf, not counting group nodes.
+    fn path_generations(tree: &SimulationTree<TestState>, total: u64, totals: &mut Vec<u64>) {
+        let total = if tree.val.is_group() {
+            total
+        } else {
+            total + tree.val.max_generations()
+        };
+
+        if tree.left.is_none() && tree.right.is_none() {
+            totals.push(total);
+        }
+        for child in [&tree.left, &tree.right].iter().copied().flatten() {
+            path_generations(child, total, totals);
+        }
+    }
+
+    fn node_generations(tree: &SimulationTree<TestState>, generations: &mut Vec<u64>) {
+        if !tree.val.is_group() {
+            generations.push(tree.val.max_generations());
+        }
+        for child in [&tree.left, &tree.right].iter().copied().flatten() {
+            node_generations(child, generations);
+        }
+    }
+
+    #[test]
+    fn test_iteration_scaling() -> Result<(), Error> {
+        for height in 1..=4 {
+            for fan_in in [2, 3] {
+                // Linear scaling gives every leaf as many generations as the levels it skips, so every path through
+                // the bracket runs the same number of generations
+                let mut gemla = Gemla::<TestState>::in_memory(GemlaConfig {
+                    fan_in,
+                    iteration_scaling: Some(IterationScaling::Linear(2)),
+                    ..GemlaConfig::default()
+                })?;
+                gemla.grow(height)?;
+
+                let mut totals = vec![];
+                path_generations(gemla.tree_ref().unwrap(), 0, &mut totals);
+                assert!(totals.iter().all(|t| *t == 2 * height));
+
+                // Leaving the scaling unset matches linear scaling of generations_per_node
+                let mut unset = Gemla::<TestState>::in_memory(GemlaConfig {
+                    fan_in,
+                    generations_per_node: 2,
+                    ..GemlaConfig::default()
+                })?;
+                unset.grow(height)?;
+
+                let mut unset_totals = vec![];
+                path_generations(unset.tree_ref().unwrap(), 0, &mut unset_totals);
+                assert_eq!(unset_totals, totals);
+
+                let mut gemla = Gemla::<TestState>::in_memory(GemlaConfig {
+                    fan_in,
+                    iteration_scaling: Some(IterationScaling::Constant(3)),
+                    ..GemlaConfig::default()
+                })?;
+                gemla.grow(height)?;
+
+                let mut generations = vec![];
+                node_generations(gemla.tree_ref().unwrap(), &mut generations);
+                assert!(generations.iter().all(|g| *g == 3));
+            }
+        }
+
+        for scaling in [IterationScaling::Linear(0), IterationScaling::Constant(0)] {
+            assert!(Gemla::<TestState>::in_memory(GemlaConfig {
+                iteration_scaling: Some(scaling),
+                ..GemlaConfig::default()
+            })
+            .is_err());
+        }
+
+        Ok(())
+    }
+
+    fn node_ids(tree: &SimulationTree<TestState>, ids: &mut Vec<Uuid>) {
+        ids.push(tree.val.id());
+        for child in [&tree.left, &tree.right].iter().copied().flatten() {
+            node_ids(child, ids);
+        }
+    }
+
+    #[test]
+    fn test_id_seed() -> Result<(), Error> {
+        let run = |id_seed| -> Result<Vec<Uuid>, Error> {
+            let mut gemla = Gemla::<TestState>::in_memory(GemlaConfig {
+                fan_in: 3,
+                id_seed,
+                ..GemlaConfig::default()
+            })?;
+            // Growing more than once reuses paths for the nodes created each time
+            smol::block_on(gemla.simulate(2))?;
+            smol::block_on(gemla.simulate(1))?;
+
+            let mut ids = vec![];
+            node_ids(gemla.tree_ref().unwrap(), &mut ids);
+            Ok(ids)
+        };
+
+        let ids = run(Some(7))?;
+        assert_eq!(ids, run(Some(7))?);
+        assert_eq!(ids.iter().collect::<HashSet<_>>().len(), ids.len());
+        assert!(ids
+            .iter()
+            .all(|id| id.get_version() == Some(uuid::Version::Sha1)));
+
+        let other = run(Some(8))?;
+        assert_eq!(other.len(), ids.len());
+        assert!(ids.iter().all(|id| !other.contains(id)));
+
+        // Ids are random without a seed
+        assert_ne!(run(None)?, run(None)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_deterministic_order() -> Result<(), Error> {
+        let run = || -> Result<Vec<u8>, Error> {
+            let mut gemla = Gemla::<TestState>::in_memory(GemlaConfig {
+                fan_in: 3,
+                record_history: true,
+                worker_pool_size: Some(4),
+                id_seed: Some(7),
+                ..GemlaConfig::default()
+            })?;
+            smol::block_on(gemla.simulate(3))?;
+
+            bincode::serialize(gemla.data.readonly()).map_err(|e| Error::Other(e.into()))
+        };
+
+        assert_eq!(run()?, run()?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_iteration_scaling_serde() -> Result<(), Error> {
+        // The tags used by the scaling of the earlier bracket module
+        let parse =
+            |json| serde_json::from_str::<IterationScaling>(json).map_err(anyhow::Error::from);
+        let scaling = parse(r#"{"enumType":"Constant","enumContent":5}"#)?;
+        assert_eq!(scaling, IterationScaling::Constant(5));
+        let scaling = parse(r#"{"enumType":"Linear","enumContent":2}"#)?;
+        assert_eq!(scaling, IterationScaling::Linear(2));
+        assert_eq!(
+            serde_json::to_string(&IterationScaling::Linear(2)).map_err(anyhow::Error::from)?,
+            r#"{"enumType":"Linear","enumContent":2}"#
+        );
+
+        let config: GemlaConfig = serde_json::from_str(
+            r#"{"iteration_scaling":{"enumType":"Constant","enumContent":4}}"#,
+        )
+        .map_err(anyhow::Error::from)?;
+        assert_eq!(
+            config.iteration_scaling,
+            Some(IterationScaling::Constant(4))
+        );
+
+        // The scaling is kept in the state file along with the rest of the config
+        let path = PathBuf::from("test_iteration_scaling_serde");
+        CleanUp::new(&path).run(|p| {
+            let config = GemlaConfig {
+                overwrite: true,
+                iteration_scaling: Some(IterationScaling::Constant(4)),
+                ..GemlaConfig::default()
+            };
+            Gemla::<TestState>::new(p, config)?.data.close()?;
+
+            let gemla = Gemla::<TestState>::open_read_only(p)?;
+            assert_eq!(
+                gemla.effective_config().iteration_scaling,
+                Some(IterationScaling::Constant(4))
+            );
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_extend_generations() -> Result<(), Error> {
+        let path = PathBuf::from("test_extend_generations");
+        CleanUp::new(&path).run(|p| {
+            let mut config = GemlaConfig {
+                generations_per_node: 2,
+                overwrite: true,
+                ..GemlaConfig::default()
+            };
+            let mut gemla = Gemla::<TestState>::new(p, config)?;
+
+            smol::block_on(gemla.simulate(1))?;
+            assert_eq!(gemla.tree_ref().unwrap().val.as_ref().unwrap().score, 2.0);
+
+            gemla.extend_generations(3)?;
+            assert_eq!(gemla.tree_ref().unwrap().val.state(), GeneticState::Mutate);
+            drop(gemla);
+
+            // The extension should be persisted
+            config.overwrite = false;
+            let mut gemla = Gemla::<TestState>::new(p, config)?;
+            assert_eq!(gemla.tree_ref().unwrap().val.max_generations(), 5);
+
+            smol::block_on(gemla.simulate(0))?;
+            let tree = gemla.tree_ref().unwrap();
+            assert_eq!(tree.val.state(), GeneticState::Finish);
+            assert_eq!(tree.val.generation(), 5);
+            assert_eq!(tree.val.as_ref().unwrap().score, 5.0);
+
+            // Parents continue training after their children
+            smol::block_on(gemla.simulate(1))?;
+            assert_eq!(gemla.tree_ref().unwrap().val.as_ref().unwrap().score, 7.0);
+            gemla.extend_generations(1)?;
+            assert!(gemla.validate_tree().is_empty());
+            smol::block_on(gemla.simulate(0))?;
+            assert_eq!(gemla.tree_ref().unwrap().val.as_ref().unwrap().score, 8.0);
+
+            Ok(())
+        })
+    }
+
+    #[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+    struct MeanState {
+        pub score: f64,
+    }
+
+    impl genetic_node::GeneticNode for MeanState {
+        fn simulate(&mut self, _context: GeneticNodeContext) -> Result<(), Error> {
+            self.score += 1.0;
+            Ok(())
+        }
+
+        fn mutate(&mut self, _context: GeneticNodeContext) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn initialize(_context: GeneticNodeContext) -> Result<Box<MeanState>, Error> {
+            Ok(Box::new(MeanState { score: 0.0 }))
+        }
+
+        fn fitness(&self) -> f64 {
+            self.score
+        }
+
+        fn population_size(&self) -> usize {
+            1
+        }
+
+        fn merge(left: &MeanState, right: &MeanState) -> Result<Box<MeanState>, Error> {
+            Ok(Box::new(MeanState {
+                score: (left.score + right.score) / 2.0,
+            }))
+        }
+    }
+
+    #[test]
+    fn test_merge_members() -> Result<(), Error> {
+        let nodes = [
+            MeanState { score: 2.0 },
+            MeanState { score: 8.0 },
+            MeanState { score: 4.0 },
+        ];
+        let refs = nodes.iter().collect::<Vec<&MeanState>>();
+        let context = MergeContext {
+            target_population_hint: None,
+            height: 2,
+            id: Uuid::new_v4(),
+        };
+
+        // Folding left to right gives ((2 + 8) / 2 + 4) / 2
+        let standard = Gemla::merge_members(MergeStrategy::Standard, &refs, context)?;
+        assert_eq!(standard.score, 4.5);
+
+        // Folding from the fittest gives ((8 + 4) / 2 + 2) / 2
+        let weighted = Gemla::merge_members(MergeStrategy::FitnessWeighted, &refs, context)?;
+        assert_eq!(weighted.score, 4.0);
+
+        let best = Gemla::merge_members(MergeStrategy::TakeBest, &refs, context)?;
+        assert_eq!(*best, nodes[1]);
+
+        assert!(Gemla::<MeanState>::merge_members(MergeStrategy::TakeBest, &[], context).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge_strategy_take_best() -> Result<(), Error> {
+        let mut left = GeneticNodeWrapper::from(MeanState { score: 2.0 }, 1, Uuid::new_v4());
+        let mut right = GeneticNodeWrapper::from(MeanState { score: 6.0 }, 1, Uuid::new_v4());
+        for _ in 0..2 {
+            left.process_node()?;
+            right.process_node()?;
+        }
+
+        let left_id = left.id();
+        let mut tree: SimulationTree<MeanState> = Box::new(btree!(
+            GeneticNodeWrapper::new(1),
+            btree!(left),
+            btree!(right.clone())
+        ));
+
+        let config = GemlaConfig {
+            merge_strategy: MergeStrategy::TakeBest,
+            ..GemlaConfig::default()
+        };
+        let merges = Gemla::merge_completed_nodes(&mut tree, &config)?;
+        assert_eq!(tree.val.as_ref(), right.as_ref());
+        assert_eq!(tree.val.state(), GeneticState::Simulate);
+        assert_eq!(
+            merges,
+            vec![MergeRecord {
+                parent: tree.val.id(),
+                children: vec![left_id, right.id()],
+                contributors: vec![right.id()],
+            }]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge_under_processed_node() -> Result<(), Error> {
+        let finished = |score| -> Result<GeneticNodeWrapper<MeanState>, Error> {
+            let mut node = GeneticNodeWrapper::from(MeanState { score }, 1, Uuid::new_v4());
+            for _ in 0..2 {
+                node.process_node()?;
+            }
+            Ok(node)
+        };
+
+        // The last leaf is still in flight and finishes during the join
+        let last = finished(8.0)?;
+        let pending = GeneticNodeWrapper::from(MeanState { score: 8.0 }, 1, last.id());
+        let (left, right) = (GeneticNodeWrapper::new(1), GeneticNodeWrapper::new(1));
+        let (left_id, right_id) = (left.id(), right.id());
+
+        // The root already holds data, so both pairs underneath it have to be reached through it
+        let tree = btree!(
+            GeneticNodeWrapper::from(MeanState { score: 0.0 }, 1, Uuid::new_v4()),
+            btree!(left, btree!(finished(2.0)?), btree!(finished(6.0)?)),
+            btree!(right, btree!(finished(4.0)?), btree!(pending))
+        );
+
+        let mut gemla = Gemla::<MeanState>::in_memory(GemlaConfig::default())?;
+        gemla.data.mutate(|(d, _, _)| *d = Some(Box::new(tree)))?;
+        gemla.threads.insert(
+            "root/R/R".to_string(),
+            (last.id(), Box::pin(async move { Ok(last) })),
+        );
+        smol::block_on(gemla.join_threads())?;
+
+        let parents = gemla
+            .merge_log()
+            .iter()
+            .map(|m| m.parent)
+            .collect::<Vec<Uuid>>();
+        assert_eq!(parents, vec![left_id, right_id]);
+
+        // Every leaf was simulated once before finishing
+        let tree = gemla.tree_ref().unwrap();
+        assert_eq!(tree.val.as_ref(), Some(&MeanState { score: 0.0 }));
+        assert_eq!(
+            tree.left.as_ref().unwrap().val.as_ref(),
+            Some(&MeanState { score: 5.0 })
+        );
+        assert_eq!(
+            tree.right.as_ref().unwrap().val.as_ref(),
+            Some(&MeanState { score: 7.0 })
+        );
+
+        Ok(())
+    }
+
+    #[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+    struct ShrinkingState {
+        pub population: Vec<u64>,
+    }
+
+    impl genetic_node::GeneticNode for ShrinkingState {
+        fn simulate(&mut self, _context: GeneticNodeContext) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn mutate(&mut self, _context: GeneticNodeContext) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn initialize(_context: GeneticNodeContext) -> Result<Box<ShrinkingState>, Error> {
+            Ok(Box::new(ShrinkingState {
+                population: vec![0; 4],
+            }))
+        }
+
+        fn fitness(&self) -> f64 {
+            0.0
+        }
+
+        fn population_size(&self) -> usize {
+            self.population.len()
+        }
+
+        // Drops a member of the population with every merge
+        fn merge(
+            left: &ShrinkingState,
+            _right: &ShrinkingState,
+        ) -> Result<Box<ShrinkingState>, Error> {
+            Ok(Box::new(ShrinkingState {
+                population: left.population[1..].to_vec(),
+            }))
+        }
+    }
+
+    #[test]
+    fn test_merge_population_size() -> Result<(), Error> {
+        let finished = || -> Result<GeneticNodeWrapper<ShrinkingState>, Error> {
+            let mut node = GeneticNodeWrapper::new(1);
+            for _ in 0..3 {
+                node.process_node()?;
+            }
+            Ok(node)
+        };
+
+        let mut tree: SimulationTree<ShrinkingState> = Box::new(btree!(
+            GeneticNodeWrapper::new(1),
+            btree!(finished()?),
+            btree!(finished()?)
+        ));
+
+        assert!(Gemla::merge_completed_nodes(&mut tree, &GemlaConfig::default()).is_err());
+        assert_eq!(tree.val.state(), GeneticState::Initialize);
+
+        // The merge is accepted when the configured target matches
+        let config = GemlaConfig {
+            target_population: Some(3),
+            ..GemlaConfig::default()
+        };
+        Gemla::merge_completed_nodes(&mut tree, &config)?;
+        assert_eq!(tree.val.as_ref().map(|n| n.population_size()), Some(3));
+
+        Ok(())
+    }
+
+    #[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+    struct SizedState {
+        pub population: usize,
+    }
+
+    impl genetic_node::GeneticNode for SizedState {
+        fn simulate(&mut self, _context: GeneticNodeContext) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn mutate(&mut self, _context: GeneticNodeContext) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn initialize(_context: GeneticNodeContext) -> Result<Box<SizedState>, Error> {
+            Ok(Box::new(SizedState { population: 4 }))
+        }
+
+        fn fitness(&self) -> f64 {
+            0.0
+        }
+
+        fn population_size(&self) -> usize {
+            self.population
+        }
+
+        fn merge(left: &SizedState, _right: &SizedState) -> Result<Box<SizedState>, Error> {
+            Ok(Box::new(left.clone()))
+        }
+
+        fn merge_with_context(
+            nodes: &[&SizedState],
+            context: MergeContext,
+        ) -> Result<Box<SizedState>, Error> {
+            assert_eq!(context.height, 2);
+            Ok(Box::new(SizedState {
+                population: context
+                    .target_population_hint
+                    .unwrap_or(nodes[0].population),
+            }))
+        }
+    }
+
+    #[test]
+    fn test_merge_population_hint() -> Result<(), Error> {
+        let merge = |target_population| -> Result<Option<usize>, Error> {
+            let mut gemla = Gemla::<SizedState>::in_memory(GemlaConfig {
+                target_population,
+                ..GemlaConfig::default()
+            })?;
+            smol::block_on(gemla.simulate(2))?;
+
+            Ok(gemla.tree_ref().unwrap().val.as_ref().map(|n| n.population))
+        };
+
+        assert_eq!(merge(None)?, Some(4));
+        assert_eq!(merge(Some(8))?, Some(8));
+
+        Ok(())
+    }
+
+    #[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+    struct UnmergeableState {
+        pub merged: bool,
+    }
+
+    impl genetic_node::GeneticNode for UnmergeableState {
+        fn simulate(&mut self, _context: GeneticNodeContext) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn mutate(&mut self, _context: GeneticNodeContext) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn initialize(_context: GeneticNodeContext) -> Result<Box<UnmergeableState>, Error> {
+            Ok(Box::new(UnmergeableState { merged: false }))
+        }
+
+        fn fitness(&self) -> f64 {
+            0.0
+        }
+
+        fn population_size(&self) -> usize {
+            1
+        }
+
+        fn merge(
+            _left: &UnmergeableState,
+            _right: &UnmergeableState,
+        ) -> Result<Box<UnmergeableState>, Error> {
+            Ok(Box::new(UnmergeableState { merged: true }))
+        }
+
+        fn validate(&self) -> Result<(), Error> {
+            if self.merged {
+                Err(Error::Other(anyhow!("Merged nodes are invalid")))
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    #[test]
+    fn test_validate_merged_node() -> Result<(), Error> {
+        let mut gemla = Gemla::<UnmergeableState>::in_memory(GemlaConfig::default())?;
+
+        let result = smol::block_on(gemla.simulate(2));
+        assert!(format!("{:?}", result.unwrap_err()).contains("Merged nodes are invalid"));
+
+        // The leaves were processed but the parent was never given the invalid merged node
+        let tree = gemla.tree_ref().unwrap();
+        assert_eq!(count_finished(tree), 2);
+        assert_eq!(tree.val.state(), GeneticState::Initialize);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_finished_nodes() -> Result<(), Error> {
+        let mut gemla = Gemla::<TestState>::in_memory(GemlaConfig::default())?;
+        assert_eq!(gemla.finished_nodes().count(), 0);
+
+        smol::block_on(gemla.simulate(3))?;
+
+        let finished = gemla.finished_nodes().collect::<Vec<(Uuid, &TestState)>>();
+        assert_eq!(finished.len(), 5);
+        assert_eq!(finished[0].0, gemla.tree_ref().unwrap().val.id());
+
+        let tree = gemla.tree_ref().unwrap();
+        for (id, node) in finished {
+            let subtree = Gemla::find_subtree(tree, id).unwrap();
+            assert_eq!(subtree.val.state(), GeneticState::Finish);
+            assert!(node.score > 0.0);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_node_error_context() -> Result<(), Error> {
+        let mut gemla = Gemla::<UnmergeableState>::in_memory(GemlaConfig {
+            fan_in: 3,
+            ..GemlaConfig::default()
+        })?;
+        gemla.grow(2)?;
+
+        // Only the first leaf fails, the other leaves are processed alongside it
+        gemla.with_seed(vec![UnmergeableState { merged: true }])?;
+        let failing = gemla.tree_ref().unwrap().left.as_ref().unwrap().val.id();
+
+        match smol::block_on(gemla.simulate(0)) {
+            Err(Error::Node {
+                id,
+                state,
+                generation,
+                ..
+            }) => {
+                assert_eq!(id, failing);
+                assert_eq!(state, GeneticState::Simulate);
+                assert_eq!(generation, 1);
+            }
+            _ => panic!("Expected the seeded node to fail"),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_node_failure() -> Result<(), Error> {
+        let path = PathBuf::from("test_node_failure.json");
+        CleanUp::new(&path).run(|p| {
+            let mut gemla = Gemla::<UnmergeableState>::in_memory(GemlaConfig::default())?;
+            gemla.grow(2)?;
+            gemla.with_seed(vec![UnmergeableState { merged: true }])?;
+            let failing = gemla.tree_ref().unwrap().left.as_ref().unwrap().val.id();
+
+            assert!(smol::block_on(gemla.simulate(0)).is_err());
+            assert!(gemla.print_tree().contains("failed: Node"));
+
+            // The failure is written along with the rest of the tree
+            let tree = gemla.tree_ref().unwrap().clone();
+            FileLinked::new_with_format(tree, p, DataFormat::Json { pretty: true })?.close()?;
+
+            let linked: FileLinked<SimulationTree<UnmergeableState>> =
+                FileLinked::from_file_with_format(p, DataFormat::Json { pretty: true })?;
+            let tree = linked.readonly();
+            let failure = tree.left.as_ref().unwrap().val.failure().unwrap();
+            assert_eq!(failure.kind, "Node");
+            assert!(failure.message.contains(&failing.to_string()));
+            assert!(failure
+                .chain
+                .iter()
+                .any(|c| c.contains("Merged nodes are invalid")));
+            assert!(tree.right.as_ref().unwrap().val.failure().is_none());
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_simulate_fan_in() -> Result<(), Error> {
+        let config = GemlaConfig {
+            generations_per_node: 10,
+            overwrite: true,
+            fan_in: 4,
+            ..GemlaConfig::default()
+        };
+        let mut gemla = Gemla::<TestState>::in_memory(config)?;
+
+        smol::block_on(gemla.simulate(3))?;
+        let tree = gemla.tree_ref().unwrap();
+        assert_eq!(Gemla::levels(tree), 3);
+        assert_eq!(Gemla::group_members(tree.right.as_ref().unwrap()).len(), 3);
+        assert_eq!(tree.val.as_ref().unwrap().score, 30.0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_max_height() -> Result<(), Error> {
+        let config = GemlaConfig {
+            max_height: Some(3),
+            ..GemlaConfig::default()
+        };
+        let mut gemla = Gemla::<TestState>::in_memory(config)?;
+
+        smol::block_on(gemla.simulate(2))?;
+        assert_eq!(gemla.tree_ref().unwrap().height(), 2);
+
+        for _ in 0..3 {
+            smol::block_on(gemla.simulate(2))?;
+            assert_eq!(gemla.tree_ref().unwrap().height(), 3);
+            assert!(Gemla::is_completed(gemla.tree_ref().unwrap()));
+        }
+
+        assert_eq!(gemla.plan(2).nodes_per_level, vec![1, 2, 2]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate_tree() -> Result<(), Error> {
+        let path = PathBuf::from("test_validate_tree");
+        CleanUp::new(&path).run(|p| {
+            let config = GemlaConfig {
+                overwrite: true,
+                ..GemlaConfig::default()
+            };
+            let mut gemla = Gemla::<TestState>::new(p, config)?;
+            assert!(gemla.validate_tree().is_empty());
+
+            let id = Uuid::new_v4();
+            let left = GeneticNodeWrapper::new(1);
+            let left_id = left.id();
+            gemla.data.mutate(|(d, _, _)| {
+                *d = Some(Box::new(btree!(
+                    GeneticNodeWrapper::from(TestState { score: 0.0 }, 1, id),
+                    btree!(left),
+                    btree!(GeneticNodeWrapper::from(TestState { score: 0.0 }, 0, id))
+                )))
+            })?;
+
+            let violations = gemla.validate_tree();
+            assert_eq!(violations.len(), 3);
+            assert!(
+                violations.contains(&TreeInvariantViolation::ParentAheadOfChildren {
+                    id,
+                    path: "root".to_string(),
+                    state: GeneticState::Simulate
+                })
+            );
+            assert!(violations.contains(&TreeInvariantViolation::DuplicateId {
+                id,
+                path: "root/R".to_string()
+            }));
+            assert!(
+                violations.contains(&TreeInvariantViolation::GenerationExceedsMax {
+                    id,
+                    path: "root/R".to_string(),
+                    generation: 1,
+                    max_generations: 0
+                })
+            );
+            assert!(!violations.iter().any(|v| match v {
+                TreeInvariantViolation::DuplicateId { id, .. } => *id == left_id,
+                _ => false,
+            }));
+
+            Ok(())
+        })
+    }
+
+    #[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+    struct ConflictState {
+        pub value: i64,
+    }
+
+    impl genetic_node::GeneticNode for ConflictState {
+        fn simulate(&mut self, _context: GeneticNodeContext) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn mutate(&mut self, _context: GeneticNodeContext) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn initialize(_context: GeneticNodeContext) -> Result<Box<ConflictState>, Error> {
+            Ok(Box::new(ConflictState { value: 1 }))
+        }
+
+        fn fitness(&self) -> f64 {
+            self.value as f64
+        }
+
+        fn population_size(&self) -> usize {
+            1
+        }
 
+        fn merge(left: &ConflictState, right: &ConflictState) -> Result<Box<ConflictState>, Error> {
+            Ok(Box::new(ConflictState {
+                value: left.value + right.value,
+            }))
+        }
+
+        fn on_merge_conflict(&self, _context: GeneticNodeContext) -> Result<MergeConflict, Error> {
+            match self.value {
+                v if v < 0 => Err(Error::Other(anyhow!("Existing data can't be resolved"))),
+                10 => Ok(MergeConflict::Reuse),
+                _ => Ok(MergeConflict::Replace),
+            }
+        }
+    }
+
+    #[test]
+    fn test_merge_conflict() -> Result<(), Error> {
+        let with_state = |value: i64, state: &str| -> Result<_, Error> {
+            let mut node = serde_json::to_value(GeneticNodeWrapper::from(
+                ConflictState { value },
+                1,
+                Uuid::new_v4(),
+            ))
+            .map_err(anyhow::Error::from)?;
+            node["state"] = serde_json::Value::from(state);
+            Ok(
+                serde_json::from_value::<GeneticNodeWrapper<ConflictState>>(node)
+                    .map_err(anyhow::Error::from)?,
+            )
+        };
+        let merge_into = |existing: i64| -> Result<SimulationTree<ConflictState>, Error> {
+            let mut tree = Box::new(btree!(
+                with_state(existing, "Initialize")?,
+                btree!(with_state(1, "Finish")?),
+                btree!(with_state(2, "Finish")?)
+            ));
+            Gemla::merge_completed_nodes(&mut tree, &GemlaConfig::default())?;
+            Ok(tree)
+        };
+
+        let reused = merge_into(10)?;
+        assert_eq!(reused.val.as_ref().unwrap().value, 10);
+        assert_eq!(reused.val.state(), GeneticState::Simulate);
+
+        let replaced = merge_into(20)?;
+        assert_eq!(replaced.val.as_ref().unwrap().value, 3);
+        assert_eq!(replaced.val.state(), GeneticState::Simulate);
+
+        assert!(merge_into(-1).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_snapshot() -> Result<(), Error> {
+        let path = PathBuf::from("test_snapshot");
+        let snapshot_path = PathBuf::from("test_snapshot_saved");
+        let _snapshot_cleanup = CleanUp::new(&snapshot_path);
+        CleanUp::new(&path).run(|p| {
+            let mut gemla = Gemla::<TestState>::new(
+                p,
+                GemlaConfig {
+                    overwrite: true,
+                    ..GemlaConfig::default()
+                },
+            )?;
+            smol::block_on(gemla.simulate(2))?;
+
+            gemla.snapshot(&snapshot_path)?;
+            let snapshot = gemla.tree_ref().cloned();
+
+            smol::block_on(gemla.simulate(1))?;
+            assert_ne!(gemla.tree_ref().cloned(), snapshot);
             drop(gemla);
-            assert!(path.exists());
 
-            // Testing not-overwriting data
-            config.overwrite = false;
-            let mut gemla = Gemla::<TestState>::new(&p, config)?;
+            let gemla = Gemla::<TestState>::restore(&snapshot_path, p)?;
+            assert_eq!(gemla.tree_ref().cloned(), snapshot);
+            drop(gemla);
+
+            // The restored state is persisted to the live file
+            let gemla = Gemla::<TestState>::new(p, GemlaConfig::default())?;
+            assert_eq!(gemla.tree_ref().cloned(), snapshot);
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_checkpoints() -> Result<(), Error> {
+        let path = PathBuf::from("test_checkpoints");
+        let checkpoint_dir = PathBuf::from("checkpoints").join("test_checkpoints");
+        CleanUp::new(&path).run(|p| {
+            let mut gemla = Gemla::<TestState>::new(
+                p,
+                GemlaConfig {
+                    overwrite: true,
+                    ..GemlaConfig::default()
+                },
+            )?;
+            assert!(gemla.list_checkpoints()?.is_empty());
 
             smol::block_on(gemla.simulate(2))?;
-            assert_eq!(gemla.tree_ref().unwrap().height(), 4);
+            gemla.tag_checkpoint("before/mutation")?;
+            let tagged = gemla.tree_ref().cloned();
+
+            // Names are sanitized before checking for collisions
+            assert!(gemla.tag_checkpoint("before mutation").is_err());
+            gemla.tag_checkpoint("empty")?;
+            assert_eq!(
+                gemla.list_checkpoints()?,
+                vec!["before_mutation".to_string(), "empty".to_string()]
+            );
 
+            smol::block_on(gemla.simulate(1))?;
+            assert_ne!(gemla.tree_ref().cloned(), tagged);
+
+            gemla.rollback_to("before/mutation")?;
+            assert_eq!(gemla.tree_ref().cloned(), tagged);
+            assert!(gemla.rollback_to("missing").is_err());
             drop(gemla);
-            assert!(path.exists());
+
+            // The rolled back state is persisted to the live file
+            let gemla = Gemla::<TestState>::new(p, GemlaConfig::default())?;
+            assert_eq!(gemla.tree_ref().cloned(), tagged);
+
+            fs::remove_dir_all(&checkpoint_dir)?;
+            fs::remove_dir("checkpoints")?;
+            Ok(())
+        })
+    }
+
+    #[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+    struct PayloadState {
+        pub score: f64,
+        pub weights: Vec<f64>,
+    }
+
+    impl genetic_node::GeneticNode for PayloadState {
+        fn simulate(&mut self, _context: GeneticNodeContext) -> Result<(), Error> {
+            self.score += 1.0;
+            Ok(())
+        }
+
+        fn mutate(&mut self, _context: GeneticNodeContext) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn initialize(_context: GeneticNodeContext) -> Result<Box<PayloadState>, Error> {
+            Ok(Box::new(PayloadState {
+                score: 0.0,
+                weights: vec![0.5; 10_000],
+            }))
+        }
+
+        fn fitness(&self) -> f64 {
+            self.score
+        }
+
+        fn population_size(&self) -> usize {
+            1
+        }
+
+        fn merge(left: &PayloadState, right: &PayloadState) -> Result<Box<PayloadState>, Error> {
+            Ok(Box::new(if left.score > right.score {
+                left.clone()
+            } else {
+                right.clone()
+            }))
+        }
+    }
+
+    #[test]
+    fn test_sidecar_storage() -> Result<(), Error> {
+        let path = PathBuf::from("test_sidecar_storage");
+        let sidecar_dir = PathBuf::from("sidecars").join("test_sidecar_storage");
+        let config = GemlaConfig {
+            overwrite: true,
+            sidecar_storage: true,
+            ..GemlaConfig::default()
+        };
+        let sidecars = || -> Result<Vec<u64>, Error> {
+            fs::read_dir(&sidecar_dir)?
+                .map(|e| Ok(e?.metadata()?.len()))
+                .collect()
+        };
+
+        let result = CleanUp::new(&path).run(|p| {
+            let mut gemla = Gemla::<PayloadState>::new(p, config)?;
+            smol::block_on(gemla.simulate(2))?;
+            let (old_root, old_leaf) = {
+                let tree = gemla.tree_ref().unwrap();
+                (tree.val.id(), tree.left.as_ref().unwrap().val.id())
+            };
+            drop(gemla);
+
+            // Every node holds 80kB of weights, which are only written to the sidecar files
+            assert!(fs::metadata(p)?.len() < 4096);
+            let sizes = sidecars()?;
+            assert_eq!(sizes.len(), 3);
+            assert!(sizes.iter().all(|s| *s > 80_000));
+
+            let mut gemla = Gemla::<PayloadState>::new(
+                p,
+                GemlaConfig {
+                    overwrite: false,
+                    ..config
+                },
+            )?;
+
+            // Resuming only reads the references to the sidecar files
+            gemla.grow(1)?;
+            let unloaded = |gemla: &Gemla<PayloadState>, id| {
+                let tree = gemla.tree_ref().unwrap();
+                let node = &Gemla::find_subtree(tree, id).unwrap().val;
+                node.has_data() && node.as_ref().is_none()
+            };
+            assert!(unloaded(&gemla, old_root));
+            assert!(unloaded(&gemla, old_leaf));
+
+            // Merging loads the old root but leaves the nodes underneath it on disk
+            smol::block_on(gemla.run(RunBudget::Unbounded))?;
+            assert!(!unloaded(&gemla, old_root));
+            assert!(unloaded(&gemla, old_leaf));
+            let root = gemla.tree_ref().unwrap().val.as_ref().unwrap();
+            assert_eq!(root.score, 3.0);
+            assert_eq!(root.weights.len(), 10_000);
+            drop(gemla);
+
+            // Sidecar files of replaced generations are removed, leaving one for each of the 5 nodes
+            assert!(fs::metadata(p)?.len() < 4096);
+            assert_eq!(sidecars()?.len(), 5);
+
+            Ok(())
+        });
+
+        fs::remove_dir_all(&sidecar_dir)?;
+        fs::remove_dir("sidecars")?;
+        result
+    }
+
+    #[test]
+    fn test_event_journal() -> Result<(), Error> {
+        let path = PathBuf::from("test_event_journal.state");
+        let config = GemlaConfig {
+            overwrite: true,
+            event_journal: true,
+            ..GemlaConfig::default()
+        };
+
+        let result = CleanUp::new(&path).run(|p| {
+            let mut gemla = Gemla::<TestState>::new(p, config)?;
+            smol::block_on(gemla.simulate(2))?;
+            let tree = gemla.tree_ref().unwrap();
+            let ids = (
+                tree.val.id(),
+                tree.left.as_ref().unwrap().val.id(),
+                tree.right.as_ref().unwrap().val.id(),
+            );
+            drop(gemla);
+
+            let events = Gemla::<TestState>::read_journal(p)?;
+            assert!(events.windows(2).all(|e| e[0].timestamp <= e[1].timestamp));
+            assert_eq!(events[0].kind, JournalEventKind::HeightIncrease);
+            assert_eq!((events[0].id, events[0].height), (ids.0, 2));
+
+            // Replaying the journal gives the order the nodes finished in, leaves before the root they're merged into
+            let finished = events
+                .iter()
+                .filter(|e| e.kind == JournalEventKind::StateChange(GeneticState::Finish))
+                .map(|e| (e.id, e.path.as_str()))
+                .collect::<Vec<(Uuid, &str)>>();
+            assert_eq!(
+                finished,
+                vec![(ids.1, "root/L"), (ids.2, "root/R"), (ids.0, "root")]
+            );
+
+            let merged = events
+                .iter()
+                .position(|e| e.kind == JournalEventKind::Merge)
+                .unwrap();
+            assert_eq!(events[merged].id, ids.0);
+            assert!(events[..merged]
+                .iter()
+                .all(|e| e.id != ids.0 || e.kind == JournalEventKind::HeightIncrease));
+
+            Ok(())
+        });
+
+        fs::remove_file("test_event_journal.state.journal")?;
+        result
+    }
+
+    static CLONES: AtomicUsize = AtomicUsize::new(0);
+
+    #[derive(Deserialize, Serialize, Debug, PartialEq)]
+    struct CloneCountingState {
+        pub score: f64,
+    }
+
+    impl Clone for CloneCountingState {
+        fn clone(&self) -> Self {
+            CLONES.fetch_add(1, Ordering::SeqCst);
+            CloneCountingState { score: self.score }
+        }
+    }
+
+    impl genetic_node::GeneticNode for CloneCountingState {
+        fn simulate(&mut self, _context: GeneticNodeContext) -> Result<(), Error> {
+            self.score += 1.0;
+            Ok(())
+        }
+
+        fn mutate(&mut self, _context: GeneticNodeContext) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn initialize(_context: GeneticNodeContext) -> Result<Box<CloneCountingState>, Error> {
+            Ok(Box::new(CloneCountingState { score: 0.0 }))
+        }
+
+        fn fitness(&self) -> f64 {
+            self.score
+        }
+
+        fn population_size(&self) -> usize {
+            1
+        }
+
+        fn merge(
+            left: &CloneCountingState,
+            right: &CloneCountingState,
+        ) -> Result<Box<CloneCountingState>, Error> {
+            Ok(Box::new(CloneCountingState {
+                score: left.score.max(right.score),
+            }))
+        }
+
+        fn on_merge_conflict(&self, _context: GeneticNodeContext) -> Result<MergeConflict, Error> {
+            Ok(MergeConflict::Reuse)
+        }
+    }
+
+    #[test]
+    fn test_clone_usage() -> Result<(), Error> {
+        let mut gemla = Gemla::<CloneCountingState>::in_memory(GemlaConfig::default())?;
+        smol::block_on(gemla.simulate(2))?;
+
+        // Planning only needs the shape of the tree
+        let clones = CLONES.load(Ordering::SeqCst);
+        assert_eq!(gemla.plan(2).nodes.len(), 7);
+        assert_eq!(CLONES.load(Ordering::SeqCst), clones);
+
+        // Existing data that is reused is moved into the restarted node
+        let mut existing = serde_json::to_value(GeneticNodeWrapper::from(
+            CloneCountingState { score: 10.0 },
+            1,
+            Uuid::new_v4(),
+        ))
+        .map_err(anyhow::Error::from)?;
+        existing["state"] = serde_json::Value::from("Initialize");
+        let existing = serde_json::from_value(existing).map_err(anyhow::Error::from)?;
+
+        let tree = gemla.tree_ref().unwrap();
+        let mut tree = Box::new(btree!(
+            existing,
+            btree!(tree.left.as_ref().unwrap().val.clone()),
+            btree!(tree.right.as_ref().unwrap().val.clone())
+        ));
+
+        let clones = CLONES.load(Ordering::SeqCst);
+        Gemla::merge_completed_nodes(&mut tree, &GemlaConfig::default())?;
+        assert_eq!(CLONES.load(Ordering::SeqCst), clones);
+        assert_eq!(tree.val.as_ref().unwrap().score, 10.0);
+        assert_eq!(tree.val.state(), GeneticState::Simulate);
+
+        // A single child is copied into its parent with one clone of its data, and keeps its own copy
+        let child = gemla.tree_ref().unwrap().left.as_ref().unwrap().val.clone();
+        let score = child.as_ref().unwrap().score;
+        let mut tree = Box::new(btree!(GeneticNodeWrapper::new(1), btree!(child),));
+
+        let clones = CLONES.load(Ordering::SeqCst);
+        Gemla::merge_completed_nodes(&mut tree, &GemlaConfig::default())?;
+        assert_eq!(CLONES.load(Ordering::SeqCst), clones + 1);
+        assert_eq!(tree.val.as_ref().unwrap().score, score);
+        assert_eq!(
+            tree.left.as_ref().unwrap().val.as_ref().unwrap().score,
+            score
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_corrupt_tree() -> Result<(), Error> {
+        let path = PathBuf::from("test_corrupt_tree");
+        CleanUp::new(&path).run(|p| {
+            let config = GemlaConfig::default();
+            let write_tree = |tree: Tree<GeneticNodeWrapper<TestState>>| {
+                FileLinked::new(
+                    (Some(Box::new(tree)), config, Vec::<GenerationRecord>::new()),
+                    p,
+                )
+                .map(drop)
+            };
+
+            write_tree(btree!(
+                GeneticNodeWrapper::new(1),
+                btree!(GeneticNodeWrapper::new(1)),
+                btree!(GeneticNodeWrapper::group())
+            ))?;
+            match Gemla::<TestState>::new(p, config) {
+                Err(Error::CorruptTree { reason }) => assert!(reason.contains("root/R")),
+                _ => panic!("Expected a group without members to be rejected"),
+            }
+
+            write_tree(btree!(GeneticNodeWrapper::group()))?;
+            assert!(matches!(
+                Gemla::<TestState>::new(p, config),
+                Err(Error::CorruptTree { .. })
+            ));
+
+            // A node past initialization has to have data to be processed
+            let mut simulating = serde_json::to_value(GeneticNodeWrapper::from(
+                TestState { score: 0.0 },
+                1,
+                Uuid::new_v4(),
+            ))
+            .map_err(anyhow::Error::from)?;
+            simulating["node"] = serde_json::Value::Null;
+            let simulating = serde_json::from_value(simulating).map_err(anyhow::Error::from)?;
+            write_tree(btree!(
+                GeneticNodeWrapper::new(1),
+                btree!(simulating),
+                btree!(GeneticNodeWrapper::new(1))
+            ))?;
+            match Gemla::<TestState>::new(p, config) {
+                Err(Error::CorruptTree { reason }) => assert!(reason.contains("root/L")),
+                _ => panic!("Expected a node without data to be rejected"),
+            }
+
+            write_tree(btree!(
+                GeneticNodeWrapper::new(1),
+                btree!(GeneticNodeWrapper::new(1)),
+                btree!(GeneticNodeWrapper::new(1))
+            ))?;
+            assert!(Gemla::<TestState>::new(p, config).is_ok());
 
             Ok(())
         })
     }
 
     #[test]
-    fn test_simulate() -> Result<(), Error> {
-        let path = PathBuf::from("test_simulate");
+    fn test_new_unreadable_file() -> Result<(), Error> {
+        let path = PathBuf::from("test_new_unreadable_file");
         CleanUp::new(&path).run(|p| {
-            // Testing initial creation
+            fs::write(p, [1])?;
+
+            match Gemla::<TestState>::new(p, GemlaConfig::default()) {
+                Err(Error::FileLinked(file_linked::error::Error::Corrupt { .. })) => (),
+                _ => panic!("Expected an unreadable file to be rejected"),
+            }
+            assert_eq!(fs::read(p)?, vec![1]);
+
+            // Overwriting discards the unreadable file on purpose
             let config = GemlaConfig {
-                generations_per_node: 10,
-                overwrite: true
+                overwrite: true,
+                ..GemlaConfig::default()
             };
-            let mut gemla = Gemla::<TestState>::new(&p, config)?;
+            assert!(Gemla::<TestState>::new(p, config).is_ok());
 
-            smol::block_on(gemla.simulate(5))?;
-            let tree = gemla.tree_ref().unwrap();
-            assert_eq!(tree.height(), 5);
-            assert_eq!(tree.val.as_ref().unwrap().score, 50.0);
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_repair_tree() -> Result<(), Error> {
+        let path = PathBuf::from("test_repair_tree");
+        CleanUp::new(&path).run(|p| {
+            let mut config = GemlaConfig::default();
+
+            let tree: SimulationTree<TestState> = Box::new(btree!(
+                GeneticNodeWrapper::from(TestState { score: 0.0 }, 1, Uuid::new_v4()),
+                btree!(GeneticNodeWrapper::new(1)),
+                btree!(GeneticNodeWrapper::new(1))
+            ));
+            drop(FileLinked::new(
+                (Some(tree), config, Vec::<GenerationRecord>::new()),
+                p,
+            )?);
+
+            // Without repairing the violations are only reported
+            let gemla = Gemla::<TestState>::new(p, config)?;
+            assert_eq!(gemla.validate_tree().len(), 1);
+            assert_eq!(
+                gemla.tree_ref().unwrap().val.state(),
+                GeneticState::Simulate
+            );
+            drop(gemla);
+
+            config.repair = true;
+            let mut gemla = Gemla::<TestState>::new(p, config)?;
+            assert!(gemla.validate_tree().is_empty());
+            assert_eq!(
+                gemla.tree_ref().unwrap().val.state(),
+                GeneticState::Initialize
+            );
+
+            smol::block_on(gemla.simulate(0))?;
+            assert_eq!(gemla.tree_ref().unwrap().val.state(), GeneticState::Finish);
 
             Ok(())
         })
     }
 
+    #[test]
+    fn test_invalid_fan_in() {
+        let path = PathBuf::from("test_invalid_fan_in");
+        let config = GemlaConfig {
+            overwrite: true,
+            fan_in: 1,
+            ..GemlaConfig::default()
+        };
+
+        assert!(Gemla::<TestState>::new(&path, config).is_err());
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_config_from_path() -> Result<(), Error> {
+        let path = PathBuf::from("test_config_from_path.toml");
+        fs::write(
+            &path,
+            "generations_per_node = 4\nmerge_strategy = \"TakeBest\"\nunknown_key = 1\n",
+        )?;
+
+        let config = GemlaConfig::from_path(&path);
+        fs::remove_file(&path)?;
+
+        assert_eq!(
+            config?,
+            GemlaConfig {
+                generations_per_node: 4,
+                merge_strategy: MergeStrategy::TakeBest,
+                ..GemlaConfig::default()
+            }
+        );
+
+        let path = PathBuf::from("test_config_from_path.json");
+        fs::write(&path, r#"{ "fan_in": 3, "record_history": true }"#)?;
+
+        let config = GemlaConfig::from_path(&path);
+        fs::remove_file(&path)?;
+
+        assert_eq!(
+            config?,
+            GemlaConfig {
+                fan_in: 3,
+                record_history: true,
+                ..GemlaConfig::default()
+            }
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_config_from_path_invalid() -> Result<(), Error> {
+        let path = PathBuf::from("test_config_from_path_invalid.toml");
+        fs::write(&path, "generations_per_node = 0\n")?;
+
+        let config = GemlaConfig::from_path(&path);
+        fs::remove_file(&path)?;
+        assert!(config.is_err());
+
+        let path = PathBuf::from("test_config_from_path_invalid.yaml");
+        fs::write(&path, "fan_in: 2\n")?;
+
+        let config = GemlaConfig::from_path(&path);
+        fs::remove_file(&path)?;
+        assert!(config.is_err());
+
+        Ok(())
+    }
 }
@@ -1,5 +1,9 @@
+use crate::core::genetic_node::GeneticState;
 use log::error;
+use serde::{Deserialize, Serialize};
+use std::io::ErrorKind;
 use thiserror::Error;
+use uuid::Uuid;
 
 #[derive(Error, Debug)]
 pub enum Error {
@@ -7,10 +11,99 @@ pub enum Error {
     FileLinked(file_linked::error::Error),
     #[error(transparent)]
     IO(std::io::Error),
+    #[error("Simulation tree is corrupt: {reason}")]
+    CorruptTree { reason: String },
+    #[error("Simulation stalled, nodes {0:?} are unable to be processed")]
+    Stalled(Vec<Uuid>),
+    #[error("Node {id} failed while in state {state:?} on generation {generation}: {source}")]
+    Node {
+        id: Uuid,
+        state: GeneticState,
+        generation: u64,
+        #[source]
+        source: Box<Error>,
+    },
+    /// An error that is expected to go away if the operation is tried again, see [`Error::transient`].
+    #[error(transparent)]
+    Transient(anyhow::Error),
     #[error(transparent)]
     Other(#[from] anyhow::Error),
 }
 
+impl Error {
+    /// Wraps an error that is worth retrying, such as a timeout in an external process, so [`Error::is_transient`]
+    /// reports it as transient.
+    pub fn transient<E: Into<anyhow::Error>>(error: E) -> Error {
+        Error::Transient(error.into())
+    }
+
+    /// Returns whether the error is expected to go away if the failed operation is tried again. Interrupted and timed
+    /// out IO and errors wrapped with [`Error::transient`] are transient, everything else, such as serialization errors
+    /// or a corrupt tree, is fatal.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Error::IO(e) | Error::FileLinked(file_linked::error::Error::IO(e)) => {
+                is_transient_io(e)
+            }
+            Error::Transient(_) => true,
+            Error::Node { source, .. } => source.is_transient(),
+            Error::Other(e) => e.chain().any(|c| {
+                c.downcast_ref::<Error>()
+                    .map(|e| matches!(e, Error::Transient(_)))
+                    .or_else(|| c.downcast_ref::<std::io::Error>().map(is_transient_io))
+                    .unwrap_or(false)
+            }),
+            _ => false,
+        }
+    }
+}
+
+/// A copy of an [`Error`] that can be stored alongside the simulation tree, so the reason a node failed survives
+/// restarts.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SerializableError {
+    /// The message of the outermost error
+    pub message: String,
+    /// The messages of the errors that caused it, from outermost to innermost
+    pub chain: Vec<String>,
+    /// The name of the [`Error`] variant
+    pub kind: String,
+}
+
+impl From<&Error> for SerializableError {
+    fn from(error: &Error) -> SerializableError {
+        let kind = match error {
+            Error::FileLinked(_) => "FileLinked",
+            Error::IO(_) => "IO",
+            Error::CorruptTree { .. } => "CorruptTree",
+            Error::Stalled(_) => "Stalled",
+            Error::Node { .. } => "Node",
+            Error::Transient(_) => "Transient",
+            Error::Other(_) => "Other",
+        };
+
+        let mut chain = vec![];
+        let mut source = std::error::Error::source(error);
+        while let Some(e) = source {
+            chain.push(e.to_string());
+            source = e.source();
+        }
+
+        SerializableError {
+            message: error.to_string(),
+            chain,
+            kind: kind.to_string(),
+        }
+    }
+}
+
+fn is_transient_io(error: &std::io::Error) -> bool {
+    matches!(
+        error.kind(),
+        ErrorKind::Interrupted | ErrorKind::TimedOut | ErrorKind::WouldBlock
+    )
+}
+
 impl From<file_linked::error::Error> for Error {
     fn from(error: file_linked::error::Error) -> Error {
         match error {
@@ -32,3 +125,65 @@ pub fn log_error<T>(result: Result<T, Error>) -> Result<T, Error> {
         e
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::{anyhow, Context};
+    use std::io;
+
+    #[test]
+    fn test_is_transient() {
+        let io_error = |kind| io::Error::new(kind, "io");
+
+        assert!(Error::IO(io_error(ErrorKind::Interrupted)).is_transient());
+        assert!(Error::IO(io_error(ErrorKind::TimedOut)).is_transient());
+        assert!(!Error::IO(io_error(ErrorKind::NotFound)).is_transient());
+        assert!(
+            Error::from(file_linked::error::Error::IO(io_error(ErrorKind::TimedOut)))
+                .is_transient()
+        );
+        assert!(!Error::from(file_linked::error::Error::NotFound("missing".into())).is_transient());
+
+        assert!(Error::transient(anyhow!("Match timed out")).is_transient());
+        assert!(!Error::Other(anyhow!("Invalid population")).is_transient());
+        assert!(!Error::CorruptTree {
+            reason: "missing data".to_string()
+        }
+        .is_transient());
+        assert!(!Error::Stalled(vec![]).is_transient());
+
+        // Transient errors are still found once they have been given context or wrapped by a node
+        let wrapped: Result<(), Error> = Err(Error::transient(anyhow!("Match timed out")));
+        let wrapped = Error::from(wrapped.context("Error simulating node").unwrap_err());
+        assert!(wrapped.is_transient());
+
+        let wrapped_io: Result<(), io::Error> = Err(io_error(ErrorKind::Interrupted));
+        assert!(
+            Error::from(wrapped_io.context("Error reading scores").unwrap_err()).is_transient()
+        );
+
+        let node = Error::Node {
+            id: Uuid::new_v4(),
+            state: GeneticState::Simulate,
+            generation: 1,
+            source: Box::new(wrapped),
+        };
+        assert!(node.is_transient());
+    }
+
+    #[test]
+    fn test_serializable_error() {
+        let error = Error::Node {
+            id: Uuid::new_v4(),
+            state: GeneticState::Mutate,
+            generation: 2,
+            source: Box::new(Error::Other(anyhow!("Population is empty"))),
+        };
+
+        let serializable = SerializableError::from(&error);
+        assert_eq!(serializable.kind, "Node");
+        assert_eq!(serializable.message, error.to_string());
+        assert_eq!(serializable.chain, vec!["Population is empty".to_string()]);
+    }
+}
@@ -3,3 +3,14 @@ pub mod tree;
 pub mod constants;
 pub mod core;
 pub mod error;
+#[cfg(feature = "sync")]
+pub mod sync;
+
+/// Installs a global `tracing` subscriber that prints spans and events to stdout, filtered with the `RUST_LOG`
+/// environment variable. Records from the `log` crate are forwarded to the subscriber as well.
+#[cfg(feature = "init-tracing")]
+pub fn init_tracing() {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .init();
+}
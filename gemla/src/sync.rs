@@ -0,0 +1,212 @@
+//! A blocking interface to [`Gemla`] for programs that don't run an async executor, enabled with the `sync` feature.
+
+use crate::{
+    core::{genetic_node::GeneticNode, Gemla, GemlaConfig, RunBudget},
+    error::Error,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    fmt::Debug,
+    ops::{Deref, DerefMut},
+    path::Path,
+};
+
+/// Wraps a [`Gemla`] simulation so it can be driven without an async executor. The asynchronous methods of [`Gemla`]
+/// are replaced with versions that block until they finish, and every other method is reached through [`Deref`].
+///
+/// # Examples
+/// ```
+/// # use gemla::core::genetic_node::{GeneticNode, GeneticNodeContext};
+/// # use gemla::core::GemlaConfig;
+/// # use gemla::error::Error;
+/// # use gemla::sync::SyncGemla;
+/// # use serde::{Deserialize, Serialize};
+/// #
+/// # #[derive(Serialize, Deserialize, Debug, Clone)]
+/// # struct TestState {
+/// #     pub score: f64,
+/// # }
+/// #
+/// # impl GeneticNode for TestState {
+/// #     fn simulate(&mut self, _context: GeneticNodeContext) -> Result<(), Error> {
+/// #         self.score += 1.0;
+/// #         Ok(())
+/// #     }
+/// #
+/// #     fn mutate(&mut self, _context: GeneticNodeContext) -> Result<(), Error> {
+/// #         Ok(())
+/// #     }
+/// #
+/// #     fn initialize(_context: GeneticNodeContext) -> Result<Box<TestState>, Error> {
+/// #         Ok(Box::new(TestState { score: 0.0 }))
+/// #     }
+/// #
+/// #     fn fitness(&self) -> f64 {
+/// #         self.score
+/// #     }
+/// #
+/// #     fn population_size(&self) -> usize {
+/// #         1
+/// #     }
+/// #
+/// #     fn merge(left: &TestState, right: &TestState) -> Result<Box<TestState>, Error> {
+/// #         Ok(Box::new(TestState {
+/// #             score: left.score.max(right.score),
+/// #         }))
+/// #     }
+/// # }
+/// #
+/// # fn main() -> Result<(), Error> {
+/// let mut gemla = SyncGemla::<TestState>::in_memory(GemlaConfig::default())?;
+/// gemla.simulate(2)?;
+///
+/// assert_eq!(gemla.tree_ref().unwrap().val.as_ref().unwrap().score, 2.0);
+/// #
+/// # Ok(())
+/// # }
+/// ```
+pub struct SyncGemla<'a, T>
+where
+    T: Serialize + Clone,
+{
+    inner: Gemla<'a, T>,
+}
+
+impl<'a, T: 'a> SyncGemla<'a, T>
+where
+    T: GeneticNode + Serialize + DeserializeOwned + Debug + Clone + Send,
+{
+    /// See [`Gemla::new`].
+    pub fn new(path: &Path, config: GemlaConfig) -> Result<Self, Error> {
+        Ok(SyncGemla {
+            inner: Gemla::new(path, config)?,
+        })
+    }
+
+    /// See [`Gemla::in_memory`].
+    pub fn in_memory(config: GemlaConfig) -> Result<Self, Error> {
+        Ok(SyncGemla {
+            inner: Gemla::in_memory(config)?,
+        })
+    }
+
+    /// Blocks until [`Gemla::simulate`] finishes.
+    pub fn simulate(&mut self, steps: u64) -> Result<(), Error> {
+        smol::block_on(self.inner.simulate(steps))
+    }
+
+    /// Blocks until [`Gemla::run`] finishes.
+    pub fn run(&mut self, budget: RunBudget) -> Result<(), Error> {
+        smol::block_on(self.inner.run(budget))
+    }
+
+    /// Blocks until [`Gemla::shutdown`] finishes.
+    pub fn shutdown(self) -> Result<(), Error> {
+        smol::block_on(self.inner.shutdown())
+    }
+
+    /// Returns the wrapped simulation, for use with an async executor.
+    pub fn into_inner(self) -> Gemla<'a, T> {
+        self.inner
+    }
+}
+
+impl<'a, T> Deref for SyncGemla<'a, T>
+where
+    T: Serialize + Clone,
+{
+    type Target = Gemla<'a, T>;
+
+    fn deref(&self) -> &Gemla<'a, T> {
+        &self.inner
+    }
+}
+
+impl<'a, T> DerefMut for SyncGemla<'a, T>
+where
+    T: Serialize + Clone,
+{
+    fn deref_mut(&mut self) -> &mut Gemla<'a, T> {
+        &mut self.inner
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::genetic_node::GeneticNodeContext;
+    use serde::Deserialize;
+    use std::path::PathBuf;
+
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    struct TestState {
+        pub score: f64,
+    }
+
+    impl GeneticNode for TestState {
+        fn simulate(&mut self, _context: GeneticNodeContext) -> Result<(), Error> {
+            self.score += 1.0;
+            Ok(())
+        }
+
+        fn mutate(&mut self, _context: GeneticNodeContext) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn initialize(_context: GeneticNodeContext) -> Result<Box<TestState>, Error> {
+            Ok(Box::new(TestState { score: 0.0 }))
+        }
+
+        fn fitness(&self) -> f64 {
+            self.score
+        }
+
+        fn population_size(&self) -> usize {
+            1
+        }
+
+        fn merge(left: &TestState, right: &TestState) -> Result<Box<TestState>, Error> {
+            Ok(Box::new(TestState {
+                score: left.score.max(right.score),
+            }))
+        }
+    }
+
+    #[test]
+    fn test_sync_gemla() -> Result<(), Error> {
+        let path = PathBuf::from("test_sync_gemla");
+        let config = GemlaConfig {
+            generations_per_node: 2,
+            overwrite: true,
+            ..GemlaConfig::default()
+        };
+
+        let result = (|| {
+            let mut gemla = SyncGemla::<TestState>::new(&path, config)?;
+
+            // Only the first leaf is processed, the rest of the tree is left for the next run
+            gemla.grow(2)?;
+            gemla.run(RunBudget::Nodes(1))?;
+            assert_eq!(gemla.report().finished, 1);
+            assert!(!gemla.report().completed);
+
+            gemla.run(RunBudget::Unbounded)?;
+            assert!(gemla.report().completed);
+            gemla.shutdown()?;
+
+            let gemla = SyncGemla::<TestState>::new(
+                &path,
+                GemlaConfig {
+                    overwrite: false,
+                    ..config
+                },
+            )?;
+            assert_eq!(gemla.tree_ref().unwrap().val.as_ref().unwrap().score, 4.0);
+
+            Ok(())
+        })();
+
+        std::fs::remove_file(&path)?;
+        result
+    }
+}
@@ -17,7 +17,7 @@
 //! ```
 
 use serde::{Deserialize, Serialize};
-use std::cmp::max;
+use std::{cmp::max, collections::VecDeque};
 
 /// An unbalanced binary tree type where each node has an optional left and right child.
 ///
@@ -139,6 +139,240 @@ impl<T> Tree<T> {
             _ => 1,
         }
     }
+
+    /// Pairs up the nodes of two [`Tree`]s with the same shape, returning `None` if the shapes differ.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gemla::tree::*;
+    /// use gemla::btree;
+    ///
+    /// let a = btree!(1, btree!(2), btree!(3));
+    /// let b = btree!("a", btree!("b"), btree!("c"));
+    ///
+    /// let zipped = a.zip(&b).unwrap();
+    /// assert_eq!(zipped.val, (&1, &"a"));
+    /// assert_eq!(zipped.right.unwrap().val, (&3, &"c"));
+    ///
+    /// assert_eq!(a.zip(&btree!("a", btree!("b"),)), None);
+    /// ```
+    pub fn zip<'a, U>(&'a self, other: &'a Tree<U>) -> Option<Tree<(&'a T, &'a U)>> {
+        let left = match (&self.left, &other.left) {
+            (Some(l), Some(o)) => Some(Box::new(l.zip(o)?)),
+            (None, None) => None,
+            _ => return None,
+        };
+        let right = match (&self.right, &other.right) {
+            (Some(r), Some(o)) => Some(Box::new(r.zip(o)?)),
+            (None, None) => None,
+            _ => return None,
+        };
+
+        Some(Tree::new((&self.val, &other.val), left, right))
+    }
+
+    /// Obtains the depth of the first node matching `pred` when searching the [`Tree`] breadth first, where the root
+    /// is at a depth of 1.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gemla::tree::*;
+    /// use gemla::btree;
+    ///
+    /// let t = btree!(1, btree!(2, btree!(4),), btree!(3));
+    ///
+    /// assert_eq!(t.depth_of(|v| *v == 1), Some(1));
+    /// assert_eq!(t.depth_of(|v| *v > 2), Some(2));
+    /// assert_eq!(t.depth_of(|v| *v == 5), None);
+    /// ```
+    pub fn depth_of<F: Fn(&T) -> bool>(&self, pred: F) -> Option<u64> {
+        let mut queue = VecDeque::new();
+        queue.push_back((self, 1));
+
+        while let Some((tree, depth)) = queue.pop_front() {
+            if pred(&tree.val) {
+                return Some(depth);
+            }
+
+            if let Some(l) = &tree.left {
+                queue.push_back((l, depth + 1));
+            }
+            if let Some(r) = &tree.right {
+                queue.push_back((r, depth + 1));
+            }
+        }
+
+        None
+    }
+
+    /// Obtains the number of nodes in a [`Tree`]
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gemla::tree::*;
+    /// use gemla::btree;
+    ///
+    /// let t =
+    ///     btree!("a",
+    ///         btree!("aa",
+    ///             btree!("aaa"),),
+    ///         btree!("ab"));
+    /// assert_eq!(t.len(), 4);
+    /// ```
+    // A tree always contains at least its root node, so it is never empty
+    #[allow(clippy::len_without_is_empty)]
+    pub fn len(&self) -> usize {
+        1 + self.left.as_ref().map(|l| l.len()).unwrap_or(0)
+            + self.right.as_ref().map(|r| r.len()).unwrap_or(0)
+    }
+
+    /// Inserts `val` as a new leaf in the first free position of the [`Tree`], searching level by level from the root
+    /// and from left to right within each level. A tree built entirely with `insert_balanced` is always complete, so
+    /// a tree with `n` nodes has a height of `ceil(log2(n + 1))`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gemla::tree::*;
+    /// use gemla::btree;
+    ///
+    /// let mut t = btree!(1);
+    /// t.insert_balanced(2);
+    /// t.insert_balanced(3);
+    /// t.insert_balanced(4);
+    ///
+    /// assert_eq!(t, btree!(1, btree!(2, btree!(4),), btree!(3)));
+    /// ```
+    pub fn insert_balanced(&mut self, val: T) {
+        // The path to the parent of the free position is found first, true meaning the right child is taken
+        let mut queue = VecDeque::new();
+        queue.push_back((&*self, vec![]));
+
+        let path = loop {
+            let (tree, path) = match queue.pop_front() {
+                Some(next) => next,
+                None => unreachable!("A tree always has a free position below its leaves"),
+            };
+
+            match (&tree.left, &tree.right) {
+                (Some(l), Some(r)) => {
+                    let mut left_path = path.clone();
+                    left_path.push(false);
+                    queue.push_back((&**l, left_path));
+
+                    let mut right_path = path;
+                    right_path.push(true);
+                    queue.push_back((&**r, right_path));
+                }
+                _ => break path,
+            }
+        };
+
+        let mut parent = self;
+        for right in path {
+            parent = match (right, &mut parent.left, &mut parent.right) {
+                (false, Some(l), _) => l,
+                (true, _, Some(r)) => r,
+                _ => unreachable!("Every node on the path has both children"),
+            };
+        }
+
+        let child = if parent.left.is_none() {
+            &mut parent.left
+        } else {
+            &mut parent.right
+        };
+        *child = Some(Box::new(Tree::new(val, None, None)));
+    }
+
+    /// Returns mutable references to the values of every node without children, from left to right.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gemla::tree::*;
+    /// use gemla::btree;
+    ///
+    /// let mut t = btree!(1, btree!(2, btree!(4),), btree!(3));
+    /// for v in t.leaves_mut() {
+    ///     *v *= 10;
+    /// }
+    ///
+    /// assert_eq!(t, btree!(1, btree!(2, btree!(40),), btree!(30)));
+    /// ```
+    pub fn leaves_mut(&mut self) -> Vec<&mut T> {
+        match (&mut self.left, &mut self.right) {
+            (None, None) => vec![&mut self.val],
+            (left, right) => {
+                let mut leaves = left.as_mut().map(|l| l.leaves_mut()).unwrap_or_default();
+                leaves.extend(right.as_mut().map(|r| r.leaves_mut()).unwrap_or_default());
+                leaves
+            }
+        }
+    }
+
+    /// Returns the values of every node with at least one child, visiting each node before its children and left
+    /// children before right children.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gemla::tree::*;
+    /// use gemla::btree;
+    ///
+    /// let t = btree!(1, btree!(2, btree!(4),), btree!(3));
+    ///
+    /// assert_eq!(t.internal_nodes(), vec![&1, &2]);
+    /// ```
+    pub fn internal_nodes(&self) -> Vec<&T> {
+        if self.left.is_none() && self.right.is_none() {
+            return vec![];
+        }
+
+        let mut nodes = vec![&self.val];
+        if let Some(l) = &self.left {
+            nodes.extend(l.internal_nodes());
+        }
+        if let Some(r) = &self.right {
+            nodes.extend(r.internal_nodes());
+        }
+        nodes
+    }
+
+    /// Removes every subtree whose root value matches `pred`, along with everything underneath it. Children are
+    /// checked before their parents, so `pred` is called on every node below the root, even those removed later along
+    /// with an ancestor. The root of the [`Tree`] itself is never removed, a whole tree can only be pruned by setting
+    /// the child holding it to `None`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gemla::tree::*;
+    /// use gemla::btree;
+    ///
+    /// let mut t = btree!(1, btree!(2, btree!(4),), btree!(3));
+    /// t.prune_where(|v| *v % 2 == 0);
+    ///
+    /// assert_eq!(t, btree!(1, , btree!(3)));
+    /// ```
+    pub fn prune_where<F: Fn(&T) -> bool>(&mut self, pred: F) {
+        self.prune_children(&pred);
+    }
+
+    fn prune_children<F: Fn(&T) -> bool>(&mut self, pred: &F) {
+        for child in [&mut self.left, &mut self.right] {
+            if let Some(c) = child {
+                c.prune_children(pred);
+
+                if pred(&c.val) {
+                    *child = None;
+                }
+            }
+        }
+    }
 }
 
 #[cfg(test)]
@@ -167,4 +401,127 @@ mod tests {
 
         assert_eq!(3, btree!(1, btree!(2), btree!(2, btree!(3),)).height());
     }
+
+    #[test]
+    fn test_zip() {
+        let a = btree!(1, btree!(2), btree!(3, btree!(4),));
+        let b = btree!('a', btree!('b'), btree!('c', btree!('d'),));
+
+        assert_eq!(
+            a.zip(&b),
+            Some(btree!(
+                (&1, &'a'),
+                btree!((&2, &'b')),
+                btree!((&3, &'c'), btree!((&4, &'d')),)
+            ))
+        );
+
+        assert_eq!(a.zip(&btree!('a', btree!('b'), btree!('c'))), None);
+        assert_eq!(
+            a.zip(&btree!('a', btree!('b'), btree!('c', , btree!('d')))),
+            None
+        );
+        assert_eq!(a.zip(&btree!('a')), None);
+    }
+
+    #[test]
+    fn test_depth_of() {
+        let t = btree!(
+            1,
+            btree!(2, btree!(4, btree!(3),),),
+            btree!(5, btree!(6), btree!(3))
+        );
+
+        assert_eq!(t.depth_of(|v| *v == 1), Some(1));
+        assert_eq!(t.depth_of(|v| *v == 6), Some(3));
+        // The shallowest match is found even when a deeper match comes first depth first
+        assert_eq!(t.depth_of(|v| *v == 3), Some(3));
+        assert_eq!(t.depth_of(|v| *v == 7), None);
+    }
+
+    #[test]
+    fn test_len() {
+        assert_eq!(1, btree!(1).len());
+
+        assert_eq!(4, btree!(1, btree!(2), btree!(2, btree!(3),)).len());
+    }
+
+    #[test]
+    fn test_insert_balanced() {
+        for n in 1..=64 {
+            let mut t = btree!(0);
+            for v in 1..n {
+                t.insert_balanced(v);
+            }
+
+            assert_eq!(t.len(), n);
+            assert_eq!(t.height(), (n as f64 + 1.0).log2().ceil() as usize);
+            for v in 0..n {
+                assert!(t.depth_of(|x| *x == v).is_some());
+            }
+        }
+
+        // Free positions higher in the tree are filled before going deeper
+        let mut t = btree!(1, btree!(2, btree!(3),),);
+        t.insert_balanced(4);
+        assert_eq!(t, btree!(1, btree!(2, btree!(3),), btree!(4)));
+    }
+
+    #[test]
+    fn test_leaves_and_internal_nodes() {
+        assert_eq!(btree!(1).internal_nodes(), Vec::<&i32>::new());
+        assert_eq!(btree!(1).leaves_mut(), vec![&mut 1]);
+
+        // Nodes with only a left or only a right child are internal
+        let mut t = btree!(
+            1,
+            btree!(2, btree!(4),),
+            btree!(3, , btree!(5, btree!(6), btree!(7)))
+        );
+        assert_eq!(t.internal_nodes(), vec![&1, &2, &3, &5]);
+        assert_eq!(t.leaves_mut(), vec![&mut 4, &mut 6, &mut 7]);
+
+        // Every node is either a leaf or internal
+        assert_eq!(t.internal_nodes().len() + t.leaves_mut().len(), t.len());
+
+        for v in t.leaves_mut() {
+            *v = 0;
+        }
+        assert_eq!(
+            t,
+            btree!(
+                1,
+                btree!(2, btree!(0),),
+                btree!(3, , btree!(5, btree!(0), btree!(0)))
+            )
+        );
+    }
+
+    #[test]
+    fn test_prune_where() {
+        let tree = || {
+            btree!(
+                1,
+                btree!(2, btree!(4), btree!(5)),
+                btree!(3, , btree!(6, btree!(7),))
+            )
+        };
+
+        let mut t = tree();
+        t.prune_where(|v| *v == 5);
+        assert_eq!(
+            t,
+            btree!(1, btree!(2, btree!(4),), btree!(3, , btree!(6, btree!(7),)))
+        );
+
+        // Pruning an internal node removes everything underneath it
+        let mut t = tree();
+        t.prune_where(|v| *v == 6);
+        assert_eq!(t, btree!(1, btree!(2, btree!(4), btree!(5)), btree!(3)));
+
+        // The root is never pruned
+        let mut t = tree();
+        t.prune_where(|v| *v != 2);
+        assert_eq!(t, btree!(1, btree!(2),));
+    }
 }